@@ -14,12 +14,44 @@ pub struct Model {
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::action_job::Entity")]
+    ActionJob,
+    #[sea_orm(has_many = "super::action_schedule::Entity")]
+    ActionSchedule,
+    #[sea_orm(has_many = "super::device_group::Entity")]
+    DeviceGroup,
+    #[sea_orm(has_many = "super::idempotency_key::Entity")]
+    IdempotencyKey,
     #[sea_orm(has_many = "super::microdevice::Entity")]
     Microdevice,
     #[sea_orm(has_many = "super::user_cluster::Entity")]
     UserCluster,
 }
 
+impl Related<super::action_job::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ActionJob.def()
+    }
+}
+
+impl Related<super::action_schedule::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ActionSchedule.def()
+    }
+}
+
+impl Related<super::device_group::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::DeviceGroup.def()
+    }
+}
+
+impl Related<super::idempotency_key::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::IdempotencyKey.def()
+    }
+}
+
 impl Related<super::microdevice::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Microdevice.def()
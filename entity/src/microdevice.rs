@@ -11,10 +11,15 @@ pub struct Model {
     pub name: String,
     pub description: Option<String>,
     pub topics: Option<Json>,
+    pub lifecycle_state: String,
+    #[sea_orm(column_type = "JsonBinary")]
+    pub labels: Json,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::action_job_device::Entity")]
+    ActionJobDevice,
     #[sea_orm(
         belongs_to = "super::cluster::Entity",
         from = "Column::ClusterId",
@@ -23,16 +28,54 @@ pub enum Relation {
         on_delete = "Cascade"
     )]
     Cluster,
+    #[sea_orm(has_many = "super::device_group_member::Entity")]
+    DeviceGroupMember,
+    #[sea_orm(has_many = "super::microdevice_action::Entity")]
+    MicrodeviceAction,
+    #[sea_orm(has_many = "super::microdevice_lifecycle_event::Entity")]
+    MicrodeviceLifecycleEvent,
+    #[sea_orm(has_one = "super::microdevice_shadow::Entity")]
+    MicrodeviceShadow,
     #[sea_orm(has_many = "super::telemetry_record::Entity")]
     TelemetryRecord,
 }
 
+impl Related<super::action_job_device::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ActionJobDevice.def()
+    }
+}
+
 impl Related<super::cluster::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Cluster.def()
     }
 }
 
+impl Related<super::device_group_member::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::DeviceGroupMember.def()
+    }
+}
+
+impl Related<super::microdevice_action::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::MicrodeviceAction.def()
+    }
+}
+
+impl Related<super::microdevice_lifecycle_event::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::MicrodeviceLifecycleEvent.def()
+    }
+}
+
+impl Related<super::microdevice_shadow::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::MicrodeviceShadow.def()
+    }
+}
+
 impl Related<super::telemetry_record::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::TelemetryRecord.def()
@@ -2,8 +2,17 @@
 
 pub mod prelude;
 
+pub mod action_job;
+pub mod action_job_device;
+pub mod action_schedule;
 pub mod cluster;
+pub mod device_group;
+pub mod device_group_member;
+pub mod idempotency_key;
 pub mod microdevice;
+pub mod microdevice_action;
+pub mod microdevice_lifecycle_event;
+pub mod microdevice_shadow;
 pub mod telemetry_record;
 pub mod user;
 pub mod user_cluster;
@@ -1,7 +1,16 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.0.1
 
+pub use super::action_job::Entity as ActionJob;
+pub use super::action_job_device::Entity as ActionJobDevice;
+pub use super::action_schedule::Entity as ActionSchedule;
 pub use super::cluster::Entity as Cluster;
+pub use super::device_group::Entity as DeviceGroup;
+pub use super::device_group_member::Entity as DeviceGroupMember;
+pub use super::idempotency_key::Entity as IdempotencyKey;
 pub use super::microdevice::Entity as Microdevice;
+pub use super::microdevice_action::Entity as MicrodeviceAction;
+pub use super::microdevice_lifecycle_event::Entity as MicrodeviceLifecycleEvent;
+pub use super::microdevice_shadow::Entity as MicrodeviceShadow;
 pub use super::telemetry_record::Entity as TelemetryRecord;
 pub use super::user::Entity as User;
 pub use super::user_cluster::Entity as UserCluster;
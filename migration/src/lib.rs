@@ -8,6 +8,16 @@ mod m20240910_224132_add_topics_to_microdevices;
 mod m20241007_224722_seed_tables;
 mod m20241009_032952_make_description_nullable;
 mod m20241116_234725_create_telemetry_table;
+mod m20241124_181502_create_microdevice_shadow_table;
+mod m20241127_203318_add_role_to_user_cluster;
+mod m20241127_204105_create_microdevice_action_table;
+mod m20241201_153012_add_lifecycle_to_microdevice;
+mod m20241203_094210_add_labels_to_microdevice;
+mod m20241203_101544_create_device_group_tables;
+mod m20241205_141907_create_action_job_tables;
+mod m20241207_083215_create_action_schedule_table;
+mod m20241209_112740_create_idempotency_key_table;
+mod m20241211_084512_add_lease_to_action_job;
 
 pub struct Migrator;
 
@@ -23,6 +33,16 @@ impl MigratorTrait for Migrator {
             Box::new(m20241007_224722_seed_tables::Migration),
             Box::new(m20241009_032952_make_description_nullable::Migration),
             Box::new(m20241116_234725_create_telemetry_table::Migration),
+            Box::new(m20241124_181502_create_microdevice_shadow_table::Migration),
+            Box::new(m20241127_203318_add_role_to_user_cluster::Migration),
+            Box::new(m20241127_204105_create_microdevice_action_table::Migration),
+            Box::new(m20241201_153012_add_lifecycle_to_microdevice::Migration),
+            Box::new(m20241203_094210_add_labels_to_microdevice::Migration),
+            Box::new(m20241203_101544_create_device_group_tables::Migration),
+            Box::new(m20241205_141907_create_action_job_tables::Migration),
+            Box::new(m20241207_083215_create_action_schedule_table::Migration),
+            Box::new(m20241209_112740_create_idempotency_key_table::Migration),
+            Box::new(m20241211_084512_add_lease_to_action_job::Migration),
         ]
     }
 }
@@ -87,18 +87,24 @@ impl MigrationTrait for Migration {
                     .clone()
                     .into_iter()
                     .map(|c| {
-                        user_cluster::ActiveModel {
+                        // Insert without `RETURNING` so columns added by later
+                        // migrations are not expected to exist yet
+                        user_cluster::Entity::insert(user_cluster::ActiveModel {
                             user_id: Set(user_uuid.clone()),
                             cluster_id: Set(c.id.clone().unwrap()),
                             ..Default::default()
-                        }
-                        .insert(db)
+                        })
+                        .exec_without_returning(db)
                     })
                     .collect();
 
                 let microdevices_fut: Vec<Vec<_>> = microdevices
                     .into_iter()
-                    .map(|m| m.into_iter().map(|m| m.insert(db)).collect())
+                    .map(|m| {
+                        m.into_iter()
+                            .map(|m| microdevice::Entity::insert(m).exec_without_returning(db))
+                            .collect()
+                    })
                     .collect();
 
                 (cluster_fut, user_cluster_fut, microdevices_fut)
@@ -136,17 +142,11 @@ impl MigrationTrait for Migration {
 
         futures::future::try_join_all(fut).await?;
 
-        let user_clusters = user_cluster::Entity::find().all(db).await?;
-
-        let fut: Vec<_> = user_clusters.into_iter().map(|uc| uc.delete(db)).collect();
-
-        futures::future::try_join_all(fut).await?;
-
-        let microdevices = microdevice::Entity::find().all(db).await?;
+        // Delete without selecting the rows so columns dropped by the `down`
+        // of later migrations are not expected to exist
+        user_cluster::Entity::delete_many().exec(db).await?;
 
-        let fut: Vec<_> = microdevices.into_iter().map(|m| m.delete(db)).collect();
-
-        futures::future::try_join_all(fut).await?;
+        microdevice::Entity::delete_many().exec(db).await?;
 
         Ok(())
     }
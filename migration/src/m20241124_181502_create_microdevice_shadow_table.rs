@@ -0,0 +1,74 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::m20240825_043411_create_microdevices_table::Microdevice;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MicrodeviceShadow::Table)
+                    .if_not_exists()
+                    .col(
+                        integer(MicrodeviceShadow::MicrodeviceId)
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        json_binary(MicrodeviceShadow::Desired)
+                            .not_null()
+                            .default(Expr::cust("'{}'::jsonb")),
+                    )
+                    .col(
+                        json_binary(MicrodeviceShadow::Reported)
+                            .not_null()
+                            .default(Expr::cust("'{}'::jsonb")),
+                    )
+                    .col(
+                        integer(MicrodeviceShadow::DesiredVersion)
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        integer(MicrodeviceShadow::ReportedVersion)
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        timestamp_with_time_zone(MicrodeviceShadow::UpdatedAt)
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_microdevice_shadow_microdevice_id")
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .from(MicrodeviceShadow::Table, MicrodeviceShadow::MicrodeviceId)
+                            .to(Microdevice::Table, Microdevice::Id),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(MicrodeviceShadow::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum MicrodeviceShadow {
+    Table,
+    MicrodeviceId,
+    Desired,
+    Reported,
+    DesiredVersion,
+    ReportedVersion,
+    UpdatedAt,
+}
@@ -0,0 +1,61 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::m20240825_043411_create_microdevices_table::Microdevice;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MicrodeviceAction::Table)
+                    .if_not_exists()
+                    .col(pk_auto(MicrodeviceAction::Id))
+                    .col(integer(MicrodeviceAction::MicrodeviceId).not_null())
+                    .col(string(MicrodeviceAction::Name).not_null())
+                    .col(string_null(MicrodeviceAction::Description))
+                    .col(json_binary_null(MicrodeviceAction::PayloadSchema))
+                    .col(
+                        json_binary(MicrodeviceAction::AllowedRoles)
+                            .not_null()
+                            .default(Expr::cust("'[]'::jsonb")),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_microdevice_action_microdevice_id")
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .from(MicrodeviceAction::Table, MicrodeviceAction::MicrodeviceId)
+                            .to(Microdevice::Table, Microdevice::Id),
+                    )
+                    .index(
+                        Index::create()
+                            .name("idx_microdevice_action_microdevice_id_name")
+                            .unique()
+                            .col(MicrodeviceAction::MicrodeviceId)
+                            .col(MicrodeviceAction::Name),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(MicrodeviceAction::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum MicrodeviceAction {
+    Table,
+    Id,
+    MicrodeviceId,
+    Name,
+    Description,
+    PayloadSchema,
+    AllowedRoles,
+}
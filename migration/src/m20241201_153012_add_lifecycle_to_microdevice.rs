@@ -0,0 +1,101 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::m20240825_043411_create_microdevices_table::Microdevice;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Existing microdevices are already in service
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Microdevice::Table)
+                    .add_column(
+                        string(MicrodeviceLifecycle::LifecycleState)
+                            .not_null()
+                            .default("active"),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(MicrodeviceLifecycleEvent::Table)
+                    .if_not_exists()
+                    .col(pk_auto(MicrodeviceLifecycleEvent::Id))
+                    .col(integer(MicrodeviceLifecycleEvent::MicrodeviceId).not_null())
+                    .col(string(MicrodeviceLifecycleEvent::FromState).not_null())
+                    .col(string(MicrodeviceLifecycleEvent::ToState).not_null())
+                    .col(string(MicrodeviceLifecycleEvent::Reason).not_null())
+                    .col(string_null(MicrodeviceLifecycleEvent::ChangedBy))
+                    .col(
+                        timestamp_with_time_zone(MicrodeviceLifecycleEvent::ChangedAt)
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_microdevice_lifecycle_event_microdevice_id")
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .from(
+                                MicrodeviceLifecycleEvent::Table,
+                                MicrodeviceLifecycleEvent::MicrodeviceId,
+                            )
+                            .to(Microdevice::Table, Microdevice::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_microdevice_lifecycle_event_microdevice_id")
+                    .table(MicrodeviceLifecycleEvent::Table)
+                    .col(MicrodeviceLifecycleEvent::MicrodeviceId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(MicrodeviceLifecycleEvent::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Microdevice::Table)
+                    .drop_column(MicrodeviceLifecycle::LifecycleState)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum MicrodeviceLifecycle {
+    LifecycleState,
+}
+
+#[derive(DeriveIden)]
+enum MicrodeviceLifecycleEvent {
+    Table,
+    Id,
+    MicrodeviceId,
+    FromState,
+    ToState,
+    Reason,
+    ChangedBy,
+    ChangedAt,
+}
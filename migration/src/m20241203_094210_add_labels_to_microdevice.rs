@@ -0,0 +1,40 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::m20240825_043411_create_microdevices_table::Microdevice;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Microdevice::Table)
+                    .add_column(
+                        json_binary(MicrodeviceLabels::Labels)
+                            .not_null()
+                            .default(Expr::cust("'{}'::jsonb")),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Microdevice::Table)
+                    .drop_column(MicrodeviceLabels::Labels)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum MicrodeviceLabels {
+    Labels,
+}
@@ -0,0 +1,99 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::m20240825_042151_create_clusters_table::Cluster;
+use crate::m20240825_043411_create_microdevices_table::Microdevice;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(DeviceGroup::Table)
+                    .if_not_exists()
+                    .col(pk_auto(DeviceGroup::Id))
+                    .col(uuid(DeviceGroup::ClusterId).not_null())
+                    .col(string(DeviceGroup::Name).not_null())
+                    .col(string_null(DeviceGroup::Description))
+                    .col(string(DeviceGroup::Kind).not_null())
+                    .col(json_binary_null(DeviceGroup::Filter))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_device_group_cluster_id")
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .from(DeviceGroup::Table, DeviceGroup::ClusterId)
+                            .to(Cluster::Table, Cluster::Id),
+                    )
+                    .index(
+                        Index::create()
+                            .name("idx_device_group_cluster_id_name")
+                            .unique()
+                            .col(DeviceGroup::ClusterId)
+                            .col(DeviceGroup::Name),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(DeviceGroupMember::Table)
+                    .if_not_exists()
+                    .col(integer(DeviceGroupMember::GroupId).not_null())
+                    .col(integer(DeviceGroupMember::MicrodeviceId).not_null())
+                    .primary_key(
+                        Index::create()
+                            .col(DeviceGroupMember::GroupId)
+                            .col(DeviceGroupMember::MicrodeviceId),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_device_group_member_group_id")
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .from(DeviceGroupMember::Table, DeviceGroupMember::GroupId)
+                            .to(DeviceGroup::Table, DeviceGroup::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_device_group_member_microdevice_id")
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .from(DeviceGroupMember::Table, DeviceGroupMember::MicrodeviceId)
+                            .to(Microdevice::Table, Microdevice::Id),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(DeviceGroupMember::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(DeviceGroup::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum DeviceGroup {
+    Table,
+    Id,
+    ClusterId,
+    Name,
+    Description,
+    Kind,
+    Filter,
+}
+
+#[derive(DeriveIden)]
+enum DeviceGroupMember {
+    Table,
+    GroupId,
+    MicrodeviceId,
+}
@@ -0,0 +1,131 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::m20240825_042151_create_clusters_table::Cluster;
+use crate::m20240825_043411_create_microdevices_table::Microdevice;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ActionJob::Table)
+                    .if_not_exists()
+                    .col(uuid(ActionJob::Id).not_null().primary_key())
+                    .col(uuid(ActionJob::ClusterId).not_null())
+                    .col(string(ActionJob::Action).not_null())
+                    .col(json_binary(ActionJob::Payload).not_null())
+                    .col(string(ActionJob::Status).not_null())
+                    .col(string_null(ActionJob::CreatedBy))
+                    .col(
+                        timestamp_with_time_zone(ActionJob::CreatedAt)
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(timestamp_with_time_zone_null(ActionJob::FinishedAt))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_action_job_cluster_id")
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .from(ActionJob::Table, ActionJob::ClusterId)
+                            .to(Cluster::Table, Cluster::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ActionJobDevice::Table)
+                    .if_not_exists()
+                    .col(pk_auto(ActionJobDevice::Id))
+                    .col(uuid(ActionJobDevice::JobId).not_null())
+                    .col(integer(ActionJobDevice::MicrodeviceId).not_null())
+                    .col(string(ActionJobDevice::Status).not_null())
+                    .col(json_binary_null(ActionJobDevice::Response))
+                    .col(string_null(ActionJobDevice::Error))
+                    .col(
+                        timestamp_with_time_zone(ActionJobDevice::QueuedAt)
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(timestamp_with_time_zone_null(ActionJobDevice::SentAt))
+                    .col(timestamp_with_time_zone_null(ActionJobDevice::CompletedAt))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_action_job_device_job_id")
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .from(ActionJobDevice::Table, ActionJobDevice::JobId)
+                            .to(ActionJob::Table, ActionJob::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_action_job_device_microdevice_id")
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .from(ActionJobDevice::Table, ActionJobDevice::MicrodeviceId)
+                            .to(Microdevice::Table, Microdevice::Id),
+                    )
+                    .index(
+                        Index::create()
+                            .name("idx_action_job_device_job_id_microdevice_id")
+                            .unique()
+                            .col(ActionJobDevice::JobId)
+                            .col(ActionJobDevice::MicrodeviceId),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_action_job_device_microdevice_id")
+                    .table(ActionJobDevice::Table)
+                    .col(ActionJobDevice::MicrodeviceId)
+                    .col(ActionJobDevice::QueuedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ActionJobDevice::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(ActionJob::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum ActionJob {
+    Table,
+    Id,
+    ClusterId,
+    Action,
+    Payload,
+    Status,
+    CreatedBy,
+    CreatedAt,
+    FinishedAt,
+}
+
+#[derive(DeriveIden)]
+enum ActionJobDevice {
+    Table,
+    Id,
+    JobId,
+    MicrodeviceId,
+    Status,
+    Response,
+    Error,
+    QueuedAt,
+    SentAt,
+    CompletedAt,
+}
@@ -0,0 +1,93 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::m20240825_042151_create_clusters_table::Cluster;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ActionSchedule::Table)
+                    .if_not_exists()
+                    .col(pk_auto(ActionSchedule::Id))
+                    .col(uuid(ActionSchedule::ClusterId).not_null())
+                    .col(string(ActionSchedule::Name).not_null())
+                    .col(string(ActionSchedule::Action).not_null())
+                    .col(json_binary(ActionSchedule::Payload).not_null())
+                    .col(json_binary_null(ActionSchedule::MicrodeviceIds))
+                    .col(string_null(ActionSchedule::GroupName))
+                    .col(string_null(ActionSchedule::Cron))
+                    .col(timestamp_with_time_zone_null(ActionSchedule::RunAt))
+                    .col(string(ActionSchedule::Timezone).not_null())
+                    .col(boolean(ActionSchedule::Paused).not_null().default(false))
+                    .col(timestamp_with_time_zone_null(ActionSchedule::NextRunAt))
+                    .col(timestamp_with_time_zone_null(ActionSchedule::LastRunAt))
+                    .col(string_null(ActionSchedule::LastStatus))
+                    .col(string_null(ActionSchedule::LastError))
+                    .col(string(ActionSchedule::CreatedBy).not_null())
+                    .col(
+                        timestamp_with_time_zone(ActionSchedule::CreatedAt)
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_action_schedule_cluster_id")
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .from(ActionSchedule::Table, ActionSchedule::ClusterId)
+                            .to(Cluster::Table, Cluster::Id),
+                    )
+                    .index(
+                        Index::create()
+                            .name("idx_action_schedule_cluster_id_name")
+                            .unique()
+                            .col(ActionSchedule::ClusterId)
+                            .col(ActionSchedule::Name),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_action_schedule_next_run_at")
+                    .table(ActionSchedule::Table)
+                    .col(ActionSchedule::NextRunAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ActionSchedule::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ActionSchedule {
+    Table,
+    Id,
+    ClusterId,
+    Name,
+    Action,
+    Payload,
+    MicrodeviceIds,
+    GroupName,
+    Cron,
+    RunAt,
+    Timezone,
+    Paused,
+    NextRunAt,
+    LastRunAt,
+    LastStatus,
+    LastError,
+    CreatedBy,
+    CreatedAt,
+}
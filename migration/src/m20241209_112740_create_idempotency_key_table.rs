@@ -0,0 +1,76 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::m20240825_042151_create_clusters_table::Cluster;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(IdempotencyKey::Table)
+                    .if_not_exists()
+                    .col(pk_auto(IdempotencyKey::Id))
+                    .col(uuid(IdempotencyKey::ClusterId).not_null())
+                    .col(string(IdempotencyKey::UserId).not_null())
+                    .col(string(IdempotencyKey::Key).not_null())
+                    .col(json_binary(IdempotencyKey::Request).not_null())
+                    .col(json_binary_null(IdempotencyKey::Response))
+                    .col(
+                        timestamp_with_time_zone(IdempotencyKey::CreatedAt)
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(timestamp_with_time_zone(IdempotencyKey::ExpiresAt).not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_idempotency_key_cluster_id")
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .from(IdempotencyKey::Table, IdempotencyKey::ClusterId)
+                            .to(Cluster::Table, Cluster::Id),
+                    )
+                    .index(
+                        Index::create()
+                            .name("idx_idempotency_key_cluster_id_user_id_key")
+                            .unique()
+                            .col(IdempotencyKey::ClusterId)
+                            .col(IdempotencyKey::UserId)
+                            .col(IdempotencyKey::Key),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_idempotency_key_expires_at")
+                    .table(IdempotencyKey::Table)
+                    .col(IdempotencyKey::ExpiresAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(IdempotencyKey::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum IdempotencyKey {
+    Table,
+    Id,
+    ClusterId,
+    UserId,
+    Key,
+    Request,
+    Response,
+    CreatedAt,
+    ExpiresAt,
+}
@@ -0,0 +1,61 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::m20241205_141907_create_action_job_tables::ActionJob;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ActionJob::Table)
+                    .add_column(string_null(ActionJobLease::Owner))
+                    .add_column(timestamp_with_time_zone_null(
+                        ActionJobLease::LeaseExpiresAt,
+                    ))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_action_job_status_lease_expires_at")
+                    .table(ActionJob::Table)
+                    .col(ActionJob::Status)
+                    .col(ActionJobLease::LeaseExpiresAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_action_job_status_lease_expires_at")
+                    .table(ActionJob::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ActionJob::Table)
+                    .drop_column(ActionJobLease::Owner)
+                    .drop_column(ActionJobLease::LeaseExpiresAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ActionJobLease {
+    Owner,
+    LeaseExpiresAt,
+}
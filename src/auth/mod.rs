@@ -129,7 +129,7 @@ pub mod jwt_auth {
     }
 
     /// Struct representing the claims of a JWT token
-    #[derive(Debug, Serialize, Deserialize)]
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct Claims {
         pub sub: String,
         pub exp: usize,
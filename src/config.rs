@@ -10,6 +10,66 @@ impl Default for ConfigStruct {
             port: "3000".to_string(),
             address: "localhost".to_string(),
             jwt: JwtConfig::default(),
+            actions: ActionConfig::default(),
+            rpc: RpcConfig::default(),
+            scheduler: SchedulerConfig::default(),
+            events: EventConfig::default(),
+            admin: AdminConfig::default(),
+            message_bus: MessageBusConfig::default(),
+        }
+    }
+}
+
+impl Default for ActionConfig {
+    fn default() -> Self {
+        ActionConfig {
+            max_concurrency: 16,
+            idempotency_window: 60 * 60 * 24,
+            idempotency_claim_timeout: 60 * 5,
+            max_per_device_per_minute: 30,
+            max_in_flight_per_device: 1,
+            device_timeout: 30,
+            job_lease: 60,
+        }
+    }
+}
+
+impl Default for RpcConfig {
+    fn default() -> Self {
+        RpcConfig {
+            max_batch_size: 100,
+            event_buffer: 1024,
+            max_subscriptions: 32,
+            max_pending_requests: 16,
+            access_check_interval: 60,
+        }
+    }
+}
+
+impl Default for EventConfig {
+    fn default() -> Self {
+        EventConfig {
+            registrar: ConsumerConfig {
+                prefetch: 16,
+                workers: 4,
+            },
+            telemetry: ConsumerConfig {
+                prefetch: 64,
+                workers: 16,
+            },
+            shadow: ConsumerConfig {
+                prefetch: 16,
+                workers: 4,
+            },
+            requeue_delay_ms: 1000,
+        }
+    }
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        SchedulerConfig {
+            poll_interval: 15,
         }
     }
 }
@@ -17,14 +77,55 @@ impl Default for ConfigStruct {
 impl Default for AmpqConfig {
     fn default() -> Self {
         AmpqConfig {
+            uri: None,
             host: "localhost".to_string(),
             port: 5672,
             user: "guest".to_string(),
             password: "guest".to_string(),
+            vhost: "/".to_string(),
+            heartbeat: 60,
+            connection_name: "iot-orchid-api".to_string(),
+            tls: AmpqTlsConfig::default(),
             mqtt_gateway_queue_name: "mqtt-gateway-wq".to_string(),
+            action_exchange: "iot-orchid.actions".to_string(),
+            action_routing_key: "cluster.{cluster_id}.device.{microdevice_id}.{action}".to_string(),
             telemetry_queue_name: "telemetry-wq".to_string(),
             registrar_queue_name: "registrar-wq".to_string(),
+            shadow_queue_name: "shadow-wq".to_string(),
             timeout: 10,
+            retry: RetryConfig::default(),
+            channel_pool_size: 8,
+            confirm_timeout: 5,
+            reconnect: ReconnectConfig::default(),
+            dead_letter: DeadLetterConfig::default(),
+        }
+    }
+}
+
+impl Default for DeadLetterConfig {
+    fn default() -> Self {
+        DeadLetterConfig {
+            exchange: "iot-orchid.dlx".to_string(),
+            queue_suffix: ".dlq".to_string(),
+        }
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_attempts: 3,
+            initial_backoff_ms: 200,
+            max_backoff_ms: 5000,
+        }
+    }
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        ReconnectConfig {
+            initial_backoff_ms: 500,
+            max_backoff_ms: 30000,
         }
     }
 }
@@ -99,18 +200,212 @@ pub struct ConfigStruct {
     pub port: String,
     pub address: String,
     pub jwt: JwtConfig,
+    pub actions: ActionConfig,
+    pub rpc: RpcConfig,
+    pub scheduler: SchedulerConfig,
+    pub events: EventConfig,
+    pub admin: AdminConfig,
+    pub message_bus: MessageBusConfig,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct MessageBusConfig {
+    pub kind: MessageBusKind,
+    /// Encoding of the messages sent to the gateways, replies use the
+    /// encoding of the request
+    pub encoding: crate::model::bus::Encoding,
+    /// Answers of the fake device used by the `in_process` bus
+    pub fake_device: FakeDeviceConfig,
+}
+
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageBusKind {
+    /// Talks to the microdevices through the broker configured in `ampq`
+    #[default]
+    Amqp,
+    /// Keeps every message within the process, actions are answered by a
+    /// fake device
+    InProcess,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct FakeDeviceConfig {
+    /// Checked in order, the first rule matching an action answers it
+    pub rules: Vec<FakeDeviceRule>,
+}
+
+/// How the fake device answers the actions it matches
+#[derive(Debug, Deserialize, Clone, utoipa::ToSchema)]
+pub struct FakeDeviceRule {
+    /// Action to match, every action when missing
+    #[schema(example = "calibrate")]
+    pub action: Option<String>,
+    /// Microdevice to match, every microdevice when missing
+    pub microdevice_id: Option<i32>,
+    /// Reply of the microdevice, `{"status": "ok"}` when missing
+    pub reply: Option<serde_json::Value>,
+    /// Failure returned instead of a reply
+    pub error: Option<FakeDeviceError>,
+    /// Milliseconds to wait before answering
+    pub delay_ms: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FakeDeviceError {
+    /// The microdevice does not reply
+    Timeout,
+    /// No queue is bound for the action
+    NotRouted,
+    /// The broker refuses the action
+    Nacked,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AdminConfig {
+    /// Users allowed to use the administration endpoints
+    pub usernames: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ActionConfig {
+    /// Maximum number of microdevices a single request transmits an action to at once
+    pub max_concurrency: usize,
+    /// Seconds a result is returned again for a repeated idempotency key
+    pub idempotency_window: i64,
+    /// Seconds after which a key whose request never completed, e.g. because
+    /// the API stopped while running it, can be used again
+    pub idempotency_claim_timeout: i64,
+    /// Actions a microdevice receives within a minute, 0 disables the limit
+    pub max_per_device_per_minute: usize,
+    /// Actions a microdevice handles at once, 0 disables the limit
+    pub max_in_flight_per_device: usize,
+    /// Seconds a microdevice has to answer an action, retries included
+    pub device_timeout: u64,
+    /// Seconds an instance keeps an action job it runs without renewing it,
+    /// other instances fail the remaining deliveries of the job afterwards
+    pub job_lease: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RpcConfig {
+    /// Maximum number of requests accepted in a single JSON-RPC batch
+    pub max_batch_size: usize,
+    /// Live events kept for WebSocket connections that fall behind
+    pub event_buffer: usize,
+    /// Maximum number of subscriptions of a single WebSocket connection
+    pub max_subscriptions: usize,
+    /// Requests of a single WebSocket connection handled at the same time,
+    /// further frames are read once one of them completes
+    pub max_pending_requests: usize,
+    /// Seconds between the checks that the user of a WebSocket connection can
+    /// still access its cluster
+    pub access_check_interval: u64,
+}
+
+/// How the registrar, telemetry and shadow queues are consumed
+#[derive(Debug, Deserialize)]
+pub struct EventConfig {
+    pub registrar: ConsumerConfig,
+    pub telemetry: ConsumerConfig,
+    pub shadow: ConsumerConfig,
+    /// Milliseconds to wait before requeueing a message that could not be
+    /// processed because the database is unavailable
+    pub requeue_delay_ms: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConsumerConfig {
+    /// Unacknowledged messages the broker delivers ahead, at least `workers`
+    /// to keep every worker busy
+    pub prefetch: u16,
+    /// Messages of the queue handled at the same time
+    pub workers: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SchedulerConfig {
+    /// Seconds between two checks for due schedules
+    pub poll_interval: u64,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct AmpqConfig {
+    /// `amqp://` or `amqps://` URI of the broker, used instead of `host`,
+    /// `port`, `user`, `password` and `vhost` when set
+    pub uri: Option<String>,
     pub host: String,
     pub port: u16,
     pub user: String,
     pub password: String,
+    pub vhost: String,
+    /// Heartbeat timeout negotiated with the broker in seconds, 0 disables it
+    pub heartbeat: u16,
+    /// Name of the connection shown in the broker management UI
+    pub connection_name: String,
+    pub tls: AmpqTlsConfig,
     pub mqtt_gateway_queue_name: String,
+    /// Topic exchange actions are published to, gateways bind their queues
+    /// for the clusters they serve, e.g. with `cluster.<id>.#`
+    pub action_exchange: String,
+    /// Routing key of an action, `{cluster_id}`, `{microdevice_id}` and
+    /// `{action}` are replaced
+    pub action_routing_key: String,
     pub telemetry_queue_name: String,
     pub registrar_queue_name: String,
+    pub shadow_queue_name: String,
     pub timeout: u64,
+    pub retry: RetryConfig,
+    /// Idle channels kept open for publishing
+    pub channel_pool_size: usize,
+    /// Seconds to wait for the broker to confirm a published message
+    pub confirm_timeout: u64,
+    pub reconnect: ReconnectConfig,
+    pub dead_letter: DeadLetterConfig,
+}
+
+/// TLS settings of the connection to the broker, always used with an
+/// `amqps://` URI
+#[derive(Debug, Deserialize, Default)]
+pub struct AmpqTlsConfig {
+    pub enabled: bool,
+    /// PEM file of the CA certificates to trust, the webpki roots otherwise
+    pub ca_cert: Option<String>,
+    /// PEM files of the client certificate and its private key, both are
+    /// required to authenticate with a certificate
+    pub client_cert: Option<String>,
+    pub client_key: Option<String>,
+    /// Name checked against the broker certificate, the host by default
+    pub domain: Option<String>,
+}
+
+/// Where registrar and telemetry messages that could not be processed end up
+#[derive(Debug, Deserialize)]
+pub struct DeadLetterConfig {
+    /// Direct exchange the failed messages are published to, keyed by the
+    /// name of the queue they were consumed from
+    pub exchange: String,
+    /// Appended to the name of a queue to name its dead-letter queue
+    pub queue_suffix: String,
+}
+
+/// Delays between attempts to reach the broker after the connection was lost
+#[derive(Debug, Deserialize)]
+pub struct ReconnectConfig {
+    /// Delay before the first attempt, doubled for every further attempt
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+}
+
+/// Retries of actions that failed before reaching the broker
+#[derive(Debug, Deserialize)]
+pub struct RetryConfig {
+    /// Attempts including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for every further retry
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
 }
 
 #[derive(Debug, Deserialize)]
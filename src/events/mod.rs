@@ -1,15 +1,26 @@
-use amqprs::channel::{
-    BasicAckArguments, BasicNackArguments, BasicPublishArguments, ConsumerMessage,
-};
+pub mod scheduler;
+
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use tokio::select;
-use tracing::{debug, error, info};
+use tokio::sync::{watch, Semaphore};
+use tokio::task::JoinSet;
+use tracing::{debug, error, info, warn};
 
 use crate::{
+    config::CONFIG,
     context::Ctx,
-    model::{microdevice::MicrodeviceBaseModelController as MicrodeviceBMC, ModelManager},
+    model::{
+        bus::{Consumer, DeadLetterSource, Delivery, InboundQueue},
+        health::{BrokerState, BrokerStatus},
+        lifecycle::LifecycleBaseModelController as LifecycleBMC,
+        microdevice::MicrodeviceBaseModelController as MicrodeviceBMC,
+        shadow::ShadowBaseModelController as ShadowBMC,
+        telemetry::TelemetryBaseModelController as TelemetryBMC, ModelManager,
+    },
 };
 
+#[derive(Clone)]
 pub struct EventManager {
     model_manager: ModelManager,
 }
@@ -19,6 +30,26 @@ struct RegistrarMessage {
     device_id: String,
 }
 
+#[derive(Deserialize, Debug)]
+struct ShadowReportMessage {
+    device_id: String,
+    reported: serde_json::Value,
+}
+
+#[derive(Deserialize, Debug)]
+struct TelemetryMessage {
+    device_id: String,
+    topic: String,
+    data: serde_json::Value,
+    timestamp: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Serialize, Debug)]
+struct RegistrarRefusal {
+    device_id: String,
+    error: String,
+}
+
 #[derive(Serialize, Debug)]
 struct RegistrarResponse {
     device_id: String,
@@ -32,11 +63,7 @@ impl EventManager {
         EventManager { model_manager: mm }
     }
 
-    pub async fn handle_registration(
-        &self,
-        ch: &amqprs::channel::Channel,
-        msg: Option<ConsumerMessage>,
-    ) {
+    pub async fn handle_registration(&self, msg: Option<Delivery>) {
         // Early return if no message is received
         let msg = match msg {
             Some(m) => m,
@@ -46,135 +73,324 @@ impl EventManager {
             }
         };
 
-        // Extract message content, return early on failure
-        let content = match msg.content.as_deref() {
-            Some(content) => content,
-            None => {
-                error!("Message content is missing.");
-                return;
-            }
-        };
+        debug!(envelope = ?msg.envelope, "Registration message received.");
 
-        debug!("Payload: {:}", std::str::from_utf8(content).unwrap());
-
-        // Deserialize the registration message
-        let registrar_msg: RegistrarMessage = match serde_json::from_slice(content) {
+        // Decode the registration message, unknown versions and encodings
+        // are dead-lettered as well
+        let registrar_msg: RegistrarMessage = match msg.decode() {
             Ok(parsed) => parsed,
             Err(e) => {
-                error!(error = %e, "Failed to deserialize registration message payload.");
-                if let Some(deliver) = &msg.deliver {
-                    let nack_args = BasicNackArguments::new(deliver.delivery_tag(), false, false);
-                    if let Err(e) = ch.basic_nack(nack_args).await {
-                        error!(error = %e, "Failed to nack message.");
-                    } else {
-                        info!("Message nacked and requeued successfully.");
-                    }
-                }
+                error!(error = %e, "Failed to decode registration message.");
+                let reason = format!("invalid registration message: {}", e);
+                msg.dead_letter(DeadLetterSource::Registrar, &reason).await;
                 return;
             }
         };
 
         // Construct the context
-        let ctx = Ctx::MicrodeviceCtx {
-            device_id: registrar_msg.device_id,
-            cluster_id: "idk".to_string(), // Placeholder
-        };
+        let ctx = microdevice_ctx(registrar_msg.device_id.clone());
+
+        // Refuse microdevices that were taken out of service
+        if let Err(e) = LifecycleBMC::register(&self.model_manager, &ctx).await {
+            if e.is_database_unavailable() {
+                error!(error = %e, "Database unavailable, registration requeued.");
+                requeue_later(&msg).await;
+                return;
+            }
+
+            warn!(error = %e, device_id = %registrar_msg.device_id, "Registration refused.");
+
+            let refusal = RegistrarRefusal {
+                device_id: registrar_msg.device_id,
+                error: e.message,
+            };
+
+            if let Ok(payload) = msg.encode_reply(&refusal) {
+                if let Err(e) = msg.reply(payload).await {
+                    error!(error = %e, "Failed to publish refusal message.");
+                }
+            }
+
+            msg.nack().await;
+            return;
+        }
 
         // Retrieve the microdevice record
         let record = match MicrodeviceBMC::get_microdevice(&ctx, &self.model_manager).await {
             Ok(rec) => rec,
+            Err(e) if e.is_database_unavailable() => {
+                error!(error = %e, "Database unavailable, registration requeued.");
+                requeue_later(&msg).await;
+                return;
+            }
             Err(e) => {
                 error!(error = %e, "Failed to retrieve microdevice record.");
+                let reason = format!("failed to retrieve microdevice: {}", e.message);
+                msg.dead_letter(DeadLetterSource::Registrar, &reason).await;
                 return;
             }
         };
 
         // Process and respond if `reply_to` is set in the message properties
-        if let Some(props) = &msg.basic_properties {
-            if let Some(reply_to) = props.reply_to() {
-                let payload = match serde_json::to_vec(&record) {
-                    Ok(data) => data,
-                    Err(e) => {
-                        error!(error = %e, "Failed to serialize response payload.");
-                        return;
-                    }
-                };
-
-                let publish_args = BasicPublishArguments::new("", reply_to).finish();
-                if let Err(e) = ch.basic_publish(props.clone(), payload, publish_args).await {
-                    error!(error = %e, "Failed to publish response message.");
+        if let Some(reply_to) = &msg.reply_to {
+            let payload = match msg.encode_reply(&record) {
+                Ok(data) => data,
+                Err(e) => {
+                    error!(error = %e, "Failed to serialize response payload.");
+                    let reason = format!("failed to encode registration reply: {}", e);
+                    msg.dead_letter(DeadLetterSource::Registrar, &reason).await;
                     return;
                 }
+            };
 
-                info!(reply_to = %reply_to, "Successfully published response message.");
+            // Every path settles the message, an unsettled one holds a
+            // prefetch slot until the channel closes
+            if let Err(e) = msg.reply(payload).await {
+                error!(error = %e, "Failed to publish response message.");
+                requeue_later(&msg).await;
+                return;
             }
+
+            info!(reply_to = %reply_to, "Successfully published response message.");
         }
 
-        // Acknowledge the message delivery if `deliver` is present
-        if let Some(deliver) = &msg.deliver {
-            let ack_args = BasicAckArguments::new(deliver.delivery_tag(), false);
-            if let Err(e) = ch.basic_ack(ack_args).await {
-                error!(error = %e, "Failed to acknowledge message.");
-            } else {
-                info!("Message acknowledged successfully.");
+        // Acknowledge the message delivery
+        msg.ack().await;
+    }
+
+    pub async fn handle_shadow_report(&self, msg: Option<Delivery>) {
+        let msg = match msg {
+            Some(m) => m,
+            None => {
+                error!("No message received for shadow report handling.");
+                return;
+            }
+        };
+
+        let report: ShadowReportMessage = match msg.decode() {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                error!(error = %e, "Failed to decode shadow report.");
+                msg.nack().await;
+                return;
+            }
+        };
+
+        let ctx = microdevice_ctx(report.device_id);
+
+        match ShadowBMC::update_reported(&self.model_manager, &ctx, report.reported).await {
+            Ok(shadow) => {
+                debug!(shadow = ?shadow, "Reported state stored.");
+                msg.ack().await;
+            }
+            Err(e) if e.is_database_unavailable() => {
+                error!(error = %e, "Database unavailable, shadow report requeued.");
+                requeue_later(&msg).await;
+            }
+            Err(e) => {
+                error!(error = %e, "Failed to store reported state.");
+                msg.nack().await;
             }
         }
     }
 
-    pub async fn handle_telemetry(
-        &self,
-        ch: &amqprs::channel::Channel,
-        msg: Option<ConsumerMessage>,
-    ) {
-        debug!("Recived telemetry message")
+    pub async fn handle_telemetry(&self, msg: Option<Delivery>) {
+        debug!("Recived telemetry message");
+
+        let msg = match msg {
+            Some(m) => m,
+            None => {
+                error!("No message received for telemetry handling.");
+                return;
+            }
+        };
+
+        let telemetry: TelemetryMessage = match msg.decode() {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                error!(error = %e, "Failed to decode telemetry message.");
+                let reason = format!("invalid telemetry message: {}", e);
+                msg.dead_letter(DeadLetterSource::Telemetry, &reason).await;
+                return;
+            }
+        };
+
+        let ctx = microdevice_ctx(telemetry.device_id);
+
+        match TelemetryBMC::ingest(
+            &self.model_manager,
+            &ctx,
+            telemetry.topic,
+            telemetry.data,
+            telemetry.timestamp,
+        )
+        .await
+        {
+            Ok(()) => msg.ack().await,
+            Err(e) if e.is_database_unavailable() => {
+                error!(error = %e, "Database unavailable, telemetry requeued.");
+                requeue_later(&msg).await;
+            }
+            Err(e) => {
+                warn!(error = %e, "Telemetry rejected.");
+                let reason = format!("telemetry rejected: {}", e.message);
+                msg.dead_letter(DeadLetterSource::Telemetry, &reason).await;
+            }
+        }
     }
 
+    /// Consumes the registrar, telemetry and shadow queues, declaring them
+    /// again every time the broker connection is re-established.
     pub async fn start(&self) {
         info!("Starting event manager");
 
+        let mut state = self.model_manager.bus.subscribe_state();
+
         loop {
-            if self.model_manager.ampq_bridge.is_connected() {
-                break;
+            if state
+                .wait_for(|status| status.state == BrokerState::Connected)
+                .await
+                .is_err()
+            {
+                error!("Broker state is no longer reported, stopping event manager");
+                return;
             }
+
+            self.consume(&mut state).await;
+
+            // Avoids spinning when consumers fail while the connection looks open
             tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
         }
+    }
 
-        let mut telemetry_consumer = match self.model_manager.ampq_bridge.telemetry_consumer().await
+    /// Handles messages until a consumer stops or the connection is lost.
+    async fn consume(&self, state: &mut watch::Receiver<BrokerStatus>) {
+        let telemetry_consumer = match self
+            .model_manager
+            .bus
+            .consume(InboundQueue::Telemetry)
+            .await
         {
             Ok(v) => v,
             Err(err) => {
-                info!("Error starting telemetry consumer: {}", err);
+                error!("Error starting telemetry consumer: {}", err);
                 return;
             }
         };
 
         info!("Telemetry consumer started");
 
-        let mut registrar_consumer = match self.model_manager.ampq_bridge.registrar_consume().await
+        let registrar_consumer = match self
+            .model_manager
+            .bus
+            .consume(InboundQueue::Registrar)
+            .await
         {
             Ok(v) => v,
             Err(err) => {
-                info!("Error starting registrar consumer: {}", err);
+                error!("Error starting registrar consumer: {}", err);
                 return;
             }
         };
 
         info!("Registrar consumer started");
 
+        let shadow_consumer = match self.model_manager.bus.consume(InboundQueue::Shadow).await {
+            Ok(v) => v,
+            Err(err) => {
+                error!("Error starting shadow consumer: {}", err);
+                return;
+            }
+        };
+
+        info!("Shadow consumer started");
+
+        let mut queues = JoinSet::new();
+        queues.spawn(
+            self.clone()
+                .process(InboundQueue::Telemetry, telemetry_consumer),
+        );
+        queues.spawn(
+            self.clone()
+                .process(InboundQueue::Registrar, registrar_consumer),
+        );
+        queues.spawn(self.clone().process(InboundQueue::Shadow, shadow_consumer));
+
+        // Dropping `queues` stops receiving, messages being handled still
+        // settle on their own
         loop {
             select! {
                 _ = tokio::time::sleep(tokio::time::Duration::from_secs(60 * 2)) => {
                     info!("Event manager running");
                 }
 
-                msg = telemetry_consumer.rx.recv() => {
-                    self.handle_telemetry(&telemetry_consumer.ch, msg).await;
+                res = state.changed() => {
+                    if res.is_err() || state.borrow().state != BrokerState::Connected {
+                        warn!("Broker connection lost, consumers will be restarted");
+                        return;
+                    }
                 }
 
-                msg = registrar_consumer.rx.recv() => {
-                    self.handle_registration(&registrar_consumer.ch, msg).await;
+                Some(res) = queues.join_next() => {
+                    match res {
+                        Ok(queue) => warn!("{:?} consumer stopped, restarting consumers", queue),
+                        Err(err) => error!("Consumer task failed: {}, restarting consumers", err),
+                    }
+                    return;
                 }
             }
         }
     }
+
+    /// Handles the messages of `queue` with up to `events.<queue>.workers` of
+    /// them at the same time, returns once the consumer stops.
+    async fn process(self, queue: InboundQueue, mut consumer: Consumer) -> InboundQueue {
+        let workers = Arc::new(Semaphore::new(queue.consumer_config().workers.max(1)));
+
+        loop {
+            // Waiting for a free worker before receiving leaves the next
+            // messages unacknowledged, so the broker stops delivering once
+            // the prefetch is reached
+            let Ok(permit) = workers.clone().acquire_owned().await else {
+                return queue;
+            };
+
+            let Some(msg) = consumer.rx.recv().await else {
+                return queue;
+            };
+
+            let manager = self.clone();
+            tokio::spawn(async move {
+                match queue {
+                    InboundQueue::Registrar => manager.handle_registration(Some(msg)).await,
+                    InboundQueue::Telemetry => manager.handle_telemetry(Some(msg)).await,
+                    InboundQueue::Shadow => manager.handle_shadow_report(Some(msg)).await,
+                }
+
+                drop(permit);
+            });
+        }
+    }
+}
+
+/// Puts a message back in its queue after `events.requeue_delay_ms`, keeping
+/// its worker busy meanwhile so the queue slows down while the database is
+/// unavailable.
+async fn requeue_later(msg: &Delivery) {
+    tokio::time::sleep(tokio::time::Duration::from_millis(
+        CONFIG.events.requeue_delay_ms,
+    ))
+    .await;
+    msg.requeue().await;
+}
+
+/// Context of a microdevice message
+///
+/// Messages only name the microdevice, the models resolve everything else
+/// from its id, so the cluster is left unknown.
+fn microdevice_ctx(device_id: String) -> Ctx {
+    Ctx::MicrodeviceCtx {
+        device_id,
+        cluster_id: "idk".to_string(), // Placeholder
+    }
 }
@@ -0,0 +1,88 @@
+use tracing::{debug, error, info};
+
+use crate::{
+    config::CONFIG,
+    model::{
+        action_job::ActionJobBaseModelController as ActionJobBMC,
+        idempotency::IdempotencyBaseModelController as IdempotencyBMC,
+        schedule::ScheduleBaseModelController as ScheduleBMC, ModelManager,
+    },
+};
+
+/// Sends the actions of due schedules
+///
+/// Every tick also completes the action jobs of instances that stopped and
+/// forgets expired idempotency keys.
+pub struct Scheduler {
+    model_manager: ModelManager,
+}
+
+impl Scheduler {
+    pub fn new(mm: ModelManager) -> Self {
+        Scheduler { model_manager: mm }
+    }
+
+    pub async fn start(&self) {
+        info!("Starting scheduler");
+
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(
+            CONFIG.scheduler.poll_interval.max(1),
+        ));
+
+        loop {
+            interval.tick().await;
+            self.run_due().await;
+            self.recover_jobs().await;
+            self.purge_idempotency_keys().await;
+        }
+    }
+
+    async fn recover_jobs(&self) {
+        match ActionJobBMC::recover_interrupted(&self.model_manager).await {
+            Ok(0) => {}
+            Ok(count) => info!("Completed {} interrupted action jobs", count),
+            Err(err) => error!("Error completing interrupted action jobs: {}", err),
+        }
+    }
+
+    async fn purge_idempotency_keys(&self) {
+        match IdempotencyBMC::purge_expired(&self.model_manager).await {
+            Ok(0) => {}
+            Ok(count) => debug!("Forgot {} expired idempotency keys", count),
+            Err(err) => error!("Error forgetting expired idempotency keys: {}", err),
+        }
+    }
+
+    async fn run_due(&self) {
+        let due = match ScheduleBMC::due(&self.model_manager).await {
+            Ok(due) => due,
+            Err(err) => {
+                error!("Error loading due schedules: {}", err);
+                return;
+            }
+        };
+
+        for schedule in due {
+            // Skip runs another scheduler instance already took over
+            match ScheduleBMC::claim(&self.model_manager, &schedule).await {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(err) => {
+                    error!("Error claiming schedule `{}`: {}", schedule.id, err);
+                    continue;
+                }
+            }
+
+            debug!("Running schedule `{}`", schedule.id);
+
+            // Runs can wait on the broker, so they must not hold up other schedules
+            let mm = self.model_manager.clone();
+            tokio::spawn(async move {
+                let id = schedule.id;
+                if let Err(err) = ScheduleBMC::run(&mm, schedule).await {
+                    error!("Error recording run of schedule `{}`: {}", id, err);
+                }
+            });
+        }
+    }
+}
@@ -22,10 +22,43 @@ use model::ModelManager;
         web::microdevice::create_device,
         web::microdevice::delete_device,
         web::microdevice::update_device,
+        web::bulk_import::import_devices,
+        web::action_catalog::list_actions,
+        web::action_catalog::create_action,
+        web::action_catalog::update_action,
+        web::action_catalog::delete_action,
+        web::device_group::list_groups,
+        web::device_group::create_group,
+        web::device_group::get_group,
+        web::device_group::update_group,
+        web::device_group::delete_group,
+        web::device_group::list_members,
+        web::action_job::get_job,
+        web::action_job::device_history,
+        web::schedule::list_schedules,
+        web::schedule::create_schedule,
+        web::schedule::get_schedule,
+        web::schedule::delete_schedule,
+        web::schedule::pause_schedule,
+        web::schedule::resume_schedule,
+        web::lifecycle::get_lifecycle,
+        web::lifecycle::transition,
+        web::shadow::get_shadow,
+        web::shadow::update_desired,
+        web::topic::list_topics,
+        web::topic::add_topic,
+        web::topic::remove_topic,
         web::session::login,
         web::session::status,
         web::session::logout,
+        web::health::health,
+        web::dead_letter::list_dead_letters,
+        web::dead_letter::replay_dead_letters,
+        web::dead_letter::purge_dead_letters,
+        web::fake_device::script_fake_device,
+        web::fake_device::send_fake_device_message,
         web::rpc::rpc_handler,
+        web::rpc::ws::ws_handler,
     ),
     components(
         schemas (
@@ -37,6 +70,47 @@ use model::ModelManager;
             model::microdevice::MicrodeviceUpdateParams,
             model::microdevice::MicrodeviceTopic,
             model::microdevice::DeviceStatus,
+            model::bulk_import::BulkImportMode,
+            model::bulk_import::BulkImportReport,
+            model::bulk_import::BulkImportRowError,
+            model::action_catalog::ActionDefinitionCreate,
+            model::action_catalog::ActionDefinitionUpdate,
+            model::action_catalog::ActionDefinitionRecord,
+            model::cluster::ClusterRole,
+            model::device_group::DeviceGroupKind,
+            model::device_group::DeviceGroupFilter,
+            model::device_group::DeviceGroupCreate,
+            model::device_group::DeviceGroupUpdate,
+            model::device_group::DeviceGroupRecord,
+            model::microdevice::MicrodeviceRecord,
+            model::microdevice::MicrodeviceActionResponse,
+            model::microdevice::ActionResultStatus,
+            model::microdevice::ActionSummary,
+            model::cluster::ClusterSummary,
+            model::telemetry::TelemetrySample,
+            model::action_job::ActionJobRecord,
+            model::action_job::ActionJobDeviceRecord,
+            model::action_job::ActionHistoryEntry,
+            model::schedule::ScheduleCreate,
+            model::schedule::ScheduleRecord,
+            model::lifecycle::LifecycleState,
+            model::lifecycle::LifecycleTransition,
+            model::lifecycle::LifecycleRecord,
+            model::lifecycle::LifecycleEventRecord,
+            model::shadow::ShadowRecord,
+            model::shadow::ShadowDesiredUpdate,
+            model::health::HealthRecord,
+            model::health::BrokerStatus,
+            model::health::BrokerState,
+            model::dead_letter::DeadLetter,
+            model::dead_letter::DeadLetterSource,
+            model::dead_letter::DeadLetterReplay,
+            model::dead_letter::DeadLetterReplayResult,
+            model::dead_letter::DeadLetterPurgeResult,
+            model::fake_device::InboundQueue,
+            model::fake_device::FakeDeviceMessageResult,
+            config::FakeDeviceRule,
+            config::FakeDeviceError,
             web::session::UserCredentials,
             web::session::LoginSuccess,
             web::rpc::JrpcExample,
@@ -45,7 +119,12 @@ use model::ModelManager;
     tags(
         (name = "Clusters", description = "Cluster operations"),
         (name = "Microdevices", description = "Microdevice operations"),
+        (name = "Device Groups", description = "Device group operations"),
+        (name = "Action Jobs", description = "Asynchronous action operations"),
+        (name = "Schedules", description = "Scheduled action operations"),
         (name = "Authentication", description = "Authentication operations"),
+        (name = "Health", description = "Service health"),
+        (name = "Administration", description = "Operations reserved to administrators"),
     ),
     servers(
         (url = "/api/v1", description = "API v1 base path")
@@ -75,13 +154,19 @@ async fn main() {
     let model_manager = ModelManager::new().await;
     let mm_api_ref = model_manager.clone();
     let mm_event_ref = model_manager.clone();
+    let mm_scheduler_ref = model_manager.clone();
 
     let event_manager = events::EventManager::new(mm_event_ref);
+    let scheduler = events::scheduler::Scheduler::new(mm_scheduler_ref);
 
     tokio::spawn(async move {
         let _ = event_manager.start().await;
     });
 
+    tokio::spawn(async move {
+        scheduler.start().await;
+    });
+
     let app = Router::new()
         .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
         .nest("/api/v1", web::app(mm_api_ref));
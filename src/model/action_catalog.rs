@@ -0,0 +1,353 @@
+use super::cluster::{ClusterBaseModelController as ClusterBMC, ClusterRole};
+use super::error::{Error, ErrorKind, Result};
+use super::microdevice::{MicrodeviceAction, MicrodeviceBaseModelController as MicrodeviceBMC};
+use super::ModelManager;
+use crate::context::Ctx;
+use entity::microdevice_action;
+use sea_orm::entity::prelude::*;
+use sea_orm::ActiveValue::Set;
+use sea_orm::SqlErr;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+const MAX_ACTION_NAME_LEN: usize = 64;
+
+#[derive(Deserialize, utoipa::ToSchema, Debug)]
+pub struct ActionDefinitionCreate {
+    #[schema(example = "calibrate")]
+    name: String,
+    #[schema(example = "Recalibrate the temperature sensor")]
+    description: Option<String>,
+    /// JSON Schema the action payload has to satisfy
+    #[schema(example = json!({
+        "type": "object",
+        "properties": { "offset": { "type": "number" } },
+        "required": ["offset"]
+    }))]
+    payload_schema: Option<Value>,
+    /// Roles allowed to trigger the action, every role is allowed when empty
+    #[schema(example = json!(["owner", "operator"]))]
+    allowed_roles: Option<Vec<ClusterRole>>,
+}
+
+/// Changes to an action definition, fields that are omitted are left untouched
+/// and `null` clears `description` or `payload_schema`.
+#[derive(Deserialize, utoipa::ToSchema, Debug)]
+pub struct ActionDefinitionUpdate {
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    #[schema(value_type = Option<String>, example = "Recalibrate the temperature sensor")]
+    description: Option<Option<String>>,
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    #[schema(value_type = Option<Object>)]
+    payload_schema: Option<Option<Value>>,
+    #[schema(example = json!(["owner"]))]
+    allowed_roles: Option<Vec<ClusterRole>>,
+}
+
+#[derive(Serialize, utoipa::ToSchema, Debug, Clone)]
+pub struct ActionDefinitionRecord {
+    microdevice_id: i32,
+    #[schema(example = "calibrate")]
+    name: String,
+    description: Option<String>,
+    payload_schema: Option<Value>,
+    allowed_roles: Vec<ClusterRole>,
+}
+
+impl From<microdevice_action::Model> for ActionDefinitionRecord {
+    fn from(model: microdevice_action::Model) -> Self {
+        Self {
+            microdevice_id: model.microdevice_id,
+            allowed_roles: allowed_roles(&model),
+            name: model.name,
+            description: model.description,
+            payload_schema: model.payload_schema,
+        }
+    }
+}
+
+pub struct ActionCatalogBaseModelController {}
+
+impl ActionCatalogBaseModelController {
+    pub async fn list_actions(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        microdevice_id: i32,
+    ) -> Result<Vec<ActionDefinitionRecord>> {
+        let device =
+            MicrodeviceBMC::find_in_cluster(mm, ctx, &cluster_uuid, microdevice_id).await?;
+
+        let actions = microdevice_action::Entity::find()
+            .filter(microdevice_action::Column::MicrodeviceId.eq(device.id))
+            .all(&mm.db)
+            .await?;
+
+        Ok(actions.into_iter().map(Into::into).collect())
+    }
+
+    pub async fn create_action(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        microdevice_id: i32,
+        definition: ActionDefinitionCreate,
+    ) -> Result<ActionDefinitionRecord> {
+        let device =
+            MicrodeviceBMC::find_in_cluster(mm, ctx, &cluster_uuid, microdevice_id).await?;
+        Self::require_owner(mm, ctx, &cluster_uuid).await?;
+
+        Self::validate_name(&definition.name)?;
+        if let Some(schema) = &definition.payload_schema {
+            Self::validate_schema(schema)?;
+        }
+
+        let res = microdevice_action::ActiveModel {
+            microdevice_id: Set(device.id),
+            name: Set(definition.name.clone()),
+            description: Set(definition.description),
+            payload_schema: Set(definition.payload_schema),
+            allowed_roles: Set(serde_json::to_value(
+                definition.allowed_roles.unwrap_or_default(),
+            )?),
+            ..Default::default()
+        }
+        .insert(&mm.db)
+        .await;
+
+        match res {
+            Ok(model) => Ok(model.into()),
+            Err(e) if matches!(e.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+                Err(Error {
+                    kind: ErrorKind::ActionAlreadyExists,
+                    message: format!(
+                        "action `{}` already exists on microdevice `{}`",
+                        definition.name, device.id
+                    ),
+                })
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn update_action(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        microdevice_id: i32,
+        name: String,
+        update: ActionDefinitionUpdate,
+    ) -> Result<ActionDefinitionRecord> {
+        let device =
+            MicrodeviceBMC::find_in_cluster(mm, ctx, &cluster_uuid, microdevice_id).await?;
+        Self::require_owner(mm, ctx, &cluster_uuid).await?;
+
+        let target = Self::find_action(mm, device.id, &name).await?;
+        let mut model = microdevice_action::ActiveModel::from(target);
+
+        if let Some(description) = update.description {
+            model.description = Set(description);
+        }
+
+        if let Some(schema) = update.payload_schema {
+            if let Some(schema) = &schema {
+                Self::validate_schema(schema)?;
+            }
+            model.payload_schema = Set(schema);
+        }
+
+        if let Some(roles) = update.allowed_roles {
+            model.allowed_roles = Set(serde_json::to_value(roles)?);
+        }
+
+        Ok(model.update(&mm.db).await?.into())
+    }
+
+    pub async fn delete_action(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        microdevice_id: i32,
+        name: String,
+    ) -> Result<()> {
+        let device =
+            MicrodeviceBMC::find_in_cluster(mm, ctx, &cluster_uuid, microdevice_id).await?;
+        Self::require_owner(mm, ctx, &cluster_uuid).await?;
+
+        Self::find_action(mm, device.id, &name)
+            .await?
+            .delete(&mm.db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Loads the definition of the user-defined action `name` for every given
+    /// microdevice that declares it, keyed by microdevice id.
+    pub(crate) async fn find_for_microdevices<I>(
+        mm: &ModelManager,
+        microdevice_ids: I,
+        name: &str,
+    ) -> Result<HashMap<i32, microdevice_action::Model>>
+    where
+        I: IntoIterator<Item = i32>,
+    {
+        let actions = microdevice_action::Entity::find()
+            .filter(microdevice_action::Column::MicrodeviceId.is_in(microdevice_ids))
+            .filter(microdevice_action::Column::Name.eq(name))
+            .all(&mm.db)
+            .await?;
+
+        Ok(actions.into_iter().map(|a| (a.microdevice_id, a)).collect())
+    }
+
+    /// Makes sure the user in `ctx` may invoke every given action definition
+    /// with `payload`.
+    ///
+    /// This runs before anything is published so an invalid payload never
+    /// reaches a single device.
+    pub(crate) async fn check_invocation<'a, I>(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: &String,
+        definitions: I,
+        payload: &Value,
+    ) -> Result<()>
+    where
+        I: IntoIterator<Item = &'a microdevice_action::Model>,
+    {
+        // The role is only looked up once a definition actually restricts it
+        let mut user_role: Option<ClusterRole> = None;
+        let mut errors: Vec<String> = vec![];
+
+        for definition in definitions {
+            let roles = allowed_roles(definition);
+
+            if !roles.is_empty() {
+                let role = match user_role {
+                    Some(role) => role,
+                    None => {
+                        let role = ClusterBMC::get_role(mm, ctx, cluster_uuid).await?;
+                        user_role = Some(role);
+                        role
+                    }
+                };
+
+                if !roles.contains(&role) {
+                    return Err(Error {
+                        kind: ErrorKind::InsufficientClusterRole,
+                        message: format!(
+                            "role `{}` is not allowed to trigger action `{}` on microdevice `{}`",
+                            role.as_ref(),
+                            definition.name,
+                            definition.microdevice_id
+                        ),
+                    });
+                }
+            }
+
+            if let Some(schema) = &definition.payload_schema {
+                let validator = jsonschema::validator_for(schema).map_err(|e| Error {
+                    kind: ErrorKind::InvalidActionDefinition,
+                    message: format!(
+                        "action `{}` of microdevice `{}` has an invalid payload schema: {}",
+                        definition.name, definition.microdevice_id, e
+                    ),
+                })?;
+
+                errors.extend(validator.iter_errors(payload).map(|e| {
+                    format!(
+                        "microdevice `{}`: `{}` {}",
+                        definition.microdevice_id, e.instance_path, e
+                    )
+                }));
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(Error {
+                kind: ErrorKind::InvalidActionPayload,
+                message: format!("invalid action payload: {}", errors.join("; ")),
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn find_action(
+        mm: &ModelManager,
+        microdevice_id: i32,
+        name: &str,
+    ) -> Result<microdevice_action::Model> {
+        microdevice_action::Entity::find()
+            .filter(microdevice_action::Column::MicrodeviceId.eq(microdevice_id))
+            .filter(microdevice_action::Column::Name.eq(name))
+            .one(&mm.db)
+            .await?
+            .ok_or(Error {
+                kind: ErrorKind::ActionNotFound,
+                message: format!(
+                    "action `{}` not found on microdevice `{}`",
+                    name, microdevice_id
+                ),
+            })
+    }
+
+    async fn require_owner(mm: &ModelManager, ctx: &Ctx, cluster_uuid: &String) -> Result<()> {
+        ClusterBMC::require_role(
+            mm,
+            ctx,
+            cluster_uuid,
+            &[ClusterRole::Owner],
+            "manage the action catalog",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    fn validate_name(name: &str) -> Result<()> {
+        let invalid = |message: String| Error {
+            kind: ErrorKind::InvalidActionDefinition,
+            message,
+        };
+
+        if name.is_empty() || name.len() > MAX_ACTION_NAME_LEN {
+            return Err(invalid(format!(
+                "action name must be between 1 and {} characters",
+                MAX_ACTION_NAME_LEN
+            )));
+        }
+
+        if name.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return Err(invalid(format!(
+                "action name `{}` must not contain whitespace",
+                name
+            )));
+        }
+
+        if !matches!(
+            MicrodeviceAction::from(name.to_string()),
+            MicrodeviceAction::UserDefined(_)
+        ) {
+            return Err(invalid(format!("`{}` is a built-in action", name)));
+        }
+
+        Ok(())
+    }
+
+    fn validate_schema(schema: &Value) -> Result<()> {
+        jsonschema::validator_for(schema)
+            .map(|_| ())
+            .map_err(|e| Error {
+                kind: ErrorKind::InvalidActionDefinition,
+                message: format!("invalid payload schema: {}", e),
+            })
+    }
+}
+
+/// Roles allowed to trigger the action, malformed entries restrict it to owners.
+fn allowed_roles(model: &microdevice_action::Model) -> Vec<ClusterRole> {
+    serde_json::from_value(model.allowed_roles.clone()).unwrap_or(vec![ClusterRole::Owner])
+}
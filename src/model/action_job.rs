@@ -0,0 +1,832 @@
+use super::ampq;
+use super::cluster::ClusterBaseModelController as ClusterBMC;
+use super::common::parse_cluster_id;
+use super::error::{Error, ErrorKind, Result};
+use super::event_bus::LiveEvent;
+use super::microdevice::{
+    MicrodeviceAction, MicrodeviceBaseModelController as MicrodeviceBMC, MicrodeviceId,
+    MicrodeviceRecord, PreparedAction,
+};
+use super::ModelManager;
+use crate::config::CONFIG;
+use crate::context::Ctx;
+use entity::{action_job, action_job_device};
+use futures::StreamExt;
+use sea_orm::ActiveValue::Set;
+use sea_orm::{entity::prelude::*, Condition, QueryOrder, QuerySelect, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+use tracing::{error, warn};
+
+/// Largest number of history entries returned at once
+const MAX_HISTORY_ENTRIES: u64 = 200;
+
+#[derive(Clone, Copy, Debug, PartialEq, strum::EnumString, strum::AsRefStr)]
+#[strum(serialize_all = "kebab-case")]
+pub enum ActionJobStatus {
+    /// Some microdevices have not answered yet
+    Running,
+    Completed,
+}
+
+/// Delivery status of an action to a single microdevice
+#[derive(Clone, Copy, Debug, PartialEq, strum::EnumString, strum::AsRefStr)]
+#[strum(serialize_all = "kebab-case")]
+pub enum ActionDeliveryStatus {
+    Queued,
+    Sent,
+    Acknowledged,
+    Failed,
+    TimedOut,
+    /// Refused because the microdevice exceeded its action limits
+    RateLimited,
+}
+
+#[derive(Serialize, utoipa::ToSchema, Debug)]
+pub struct ActionJobDeviceRecord {
+    #[schema(example = 1)]
+    microdevice_id: i32,
+    #[schema(value_type = String, example = "acknowledged")]
+    status: String,
+    /// Reply of the microdevice once acknowledged
+    response: Option<Value>,
+    #[schema(example = "microdevice `sensor-1` does not support action `calibrate`")]
+    error: Option<String>,
+    #[schema(value_type = String, format = DateTime)]
+    queued_at: DateTimeWithTimeZone,
+    #[schema(value_type = Option<String>, format = DateTime)]
+    sent_at: Option<DateTimeWithTimeZone>,
+    #[schema(value_type = Option<String>, format = DateTime)]
+    completed_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Serialize, utoipa::ToSchema, Debug)]
+pub struct ActionJobRecord {
+    #[schema(example = "5f0c2d56-6b7b-4b43-9a7c-0f3f1d0bd7e1")]
+    id: Uuid,
+    #[schema(example = "restart")]
+    action: String,
+    #[schema(example = json!({}))]
+    payload: Value,
+    #[schema(value_type = String, example = "running")]
+    status: String,
+    /// User that submitted the job
+    created_by: Option<String>,
+    #[schema(value_type = String, format = DateTime)]
+    created_at: DateTimeWithTimeZone,
+    #[schema(value_type = Option<String>, format = DateTime)]
+    finished_at: Option<DateTimeWithTimeZone>,
+    devices: Vec<ActionJobDeviceRecord>,
+}
+
+/// An action a microdevice was targeted by
+#[derive(Serialize, utoipa::ToSchema, Debug)]
+pub struct ActionHistoryEntry {
+    job_id: Uuid,
+    #[schema(example = "restart")]
+    action: String,
+    #[schema(example = json!({}))]
+    payload: Value,
+    #[schema(value_type = String, example = "acknowledged")]
+    status: String,
+    response: Option<Value>,
+    error: Option<String>,
+    #[schema(value_type = String, format = DateTime)]
+    queued_at: DateTimeWithTimeZone,
+    #[schema(value_type = Option<String>, format = DateTime)]
+    sent_at: Option<DateTimeWithTimeZone>,
+    #[schema(value_type = Option<String>, format = DateTime)]
+    completed_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ActionHistoryParams {
+    pub limit: Option<u64>,
+}
+
+impl From<action_job_device::Model> for ActionJobDeviceRecord {
+    fn from(device: action_job_device::Model) -> Self {
+        Self {
+            microdevice_id: device.microdevice_id,
+            status: device.status,
+            response: device.response,
+            error: device.error,
+            queued_at: device.queued_at,
+            sent_at: device.sent_at,
+            completed_at: device.completed_at,
+        }
+    }
+}
+
+pub struct ActionJobBaseModelController {}
+
+impl ActionJobBaseModelController {
+    /// Records an action job and transmits it in the background.
+    ///
+    /// Targets and payload are checked before returning, so a job is only
+    /// created for an action that can be sent.
+    pub async fn submit(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        microdevice_ids: Vec<MicrodeviceId>,
+        action: MicrodeviceAction,
+        payload: Value,
+    ) -> Result<ActionJobRecord> {
+        let cluster_id = parse_cluster_id(&cluster_uuid)?;
+
+        let (targets, rejected, action) = if microdevice_ids.is_empty() {
+            ClusterBMC::exists(mm, ctx, cluster_uuid.clone()).await?;
+            (vec![], vec![], action)
+        } else {
+            let PreparedAction {
+                targets,
+                rejected,
+                action,
+            } = MicrodeviceBMC::prepare_action(
+                mm,
+                ctx,
+                &cluster_uuid,
+                microdevice_ids,
+                action,
+                &payload,
+            )
+            .await?;
+            (targets, rejected, action)
+        };
+
+        let now = chrono::Utc::now().fixed_offset();
+        let status = match targets.is_empty() {
+            true => ActionJobStatus::Completed,
+            false => ActionJobStatus::Running,
+        };
+
+        let txn = mm.db.begin().await?;
+
+        let job = action_job::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            cluster_id: Set(cluster_id),
+            action: Set(action.name().to_string()),
+            payload: Set(payload.clone()),
+            status: Set(status.as_ref().to_string()),
+            created_by: Set(ctx.get_user_id().cloned()),
+            created_at: Set(now),
+            finished_at: Set(targets.is_empty().then_some(now)),
+            owner: Set(Some(mm.instance_id.clone())),
+            lease_expires_at: Set((!targets.is_empty()).then(Self::lease_expiry)),
+        }
+        .insert(&txn)
+        .await?;
+
+        let queued = targets
+            .iter()
+            .filter_map(|rec| rec.id)
+            .map(|microdevice_id| action_job_device::ActiveModel {
+                job_id: Set(job.id),
+                microdevice_id: Set(microdevice_id),
+                status: Set(ActionDeliveryStatus::Queued.as_ref().to_string()),
+                queued_at: Set(now),
+                ..Default::default()
+            });
+
+        // Microdevices the action cannot be sent to fail right away
+        let failed = rejected
+            .into_iter()
+            .filter_map(|res| match res.microdevice_id {
+                MicrodeviceId::Id(microdevice_id) => Some(action_job_device::ActiveModel {
+                    job_id: Set(job.id),
+                    microdevice_id: Set(microdevice_id),
+                    status: Set(ActionDeliveryStatus::Failed.as_ref().to_string()),
+                    error: Set(Some(res.message)),
+                    queued_at: Set(now),
+                    completed_at: Set(Some(now)),
+                    ..Default::default()
+                }),
+                MicrodeviceId::Name(_) => None,
+            });
+
+        let devices: Vec<_> = queued.chain(failed).collect();
+        if !devices.is_empty() {
+            action_job_device::Entity::insert_many(devices)
+                .exec_without_returning(&txn)
+                .await?;
+        }
+
+        txn.commit().await?;
+
+        if !targets.is_empty() {
+            tokio::spawn(Self::run(
+                mm.clone(),
+                cluster_id,
+                job.id,
+                targets,
+                action,
+                payload,
+            ));
+        }
+
+        Self::record(mm, job).await
+    }
+
+    pub async fn get_job(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        job_id: Uuid,
+    ) -> Result<ActionJobRecord> {
+        ClusterBMC::exists(mm, ctx, cluster_uuid.clone()).await?;
+
+        let job = action_job::Entity::find_by_id(job_id)
+            .filter(action_job::Column::ClusterId.eq(parse_cluster_id(&cluster_uuid)?))
+            .one(&mm.db)
+            .await?
+            .ok_or(Error {
+                kind: ErrorKind::ActionJobNotFound,
+                message: format!("action job `{}` not found", job_id),
+            })?;
+
+        Self::record(mm, job).await
+    }
+
+    /// Returns the actions a microdevice was targeted by, most recent first.
+    pub async fn device_history(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        microdevice_id: i32,
+        limit: Option<u64>,
+    ) -> Result<Vec<ActionHistoryEntry>> {
+        let device =
+            MicrodeviceBMC::find_in_cluster(mm, ctx, &cluster_uuid, microdevice_id).await?;
+
+        let entries = action_job_device::Entity::find()
+            .filter(action_job_device::Column::MicrodeviceId.eq(device.id))
+            .order_by_desc(action_job_device::Column::QueuedAt)
+            .order_by_desc(action_job_device::Column::Id)
+            .limit(limit.unwrap_or(50).clamp(1, MAX_HISTORY_ENTRIES))
+            .find_also_related(action_job::Entity)
+            .all(&mm.db)
+            .await?;
+
+        Ok(entries
+            .into_iter()
+            .filter_map(|(device, job)| {
+                job.map(|job| ActionHistoryEntry {
+                    job_id: job.id,
+                    action: job.action,
+                    payload: job.payload,
+                    status: device.status,
+                    response: device.response,
+                    error: device.error,
+                    queued_at: device.queued_at,
+                    sent_at: device.sent_at,
+                    completed_at: device.completed_at,
+                })
+            })
+            .collect())
+    }
+
+    /// Completes the jobs whose instance stopped transmitting them.
+    ///
+    /// Jobs are only transmitted by the instance that submitted them, which
+    /// renews their lease until they complete. Once the lease expired, e.g.
+    /// after a restart or a crash, their queued and sent deliveries would never
+    /// be settled. They are failed rather than sent again, as a sent action may
+    /// already have run on its microdevice. Returns the number of jobs completed.
+    pub async fn recover_interrupted(mm: &ModelManager) -> Result<u64> {
+        let now = chrono::Utc::now().fixed_offset();
+        let txn = mm.db.begin().await?;
+
+        // Locked so their owner cannot renew the lease in the meantime
+        let interrupted: Vec<Uuid> = action_job::Entity::find()
+            .select_only()
+            .column(action_job::Column::Id)
+            .filter(action_job::Column::Status.eq(ActionJobStatus::Running.as_ref()))
+            .filter(
+                Condition::any()
+                    .add(action_job::Column::LeaseExpiresAt.is_null())
+                    .add(action_job::Column::LeaseExpiresAt.lte(now)),
+            )
+            .lock_exclusive()
+            .into_tuple()
+            .all(&txn)
+            .await?;
+
+        if interrupted.is_empty() {
+            return Ok(0);
+        }
+
+        action_job_device::Entity::update_many()
+            .set(action_job_device::ActiveModel {
+                status: Set(ActionDeliveryStatus::Failed.as_ref().to_string()),
+                error: Set(Some(
+                    "interrupted before the microdevice answered".to_string(),
+                )),
+                completed_at: Set(Some(now)),
+                ..Default::default()
+            })
+            .filter(action_job_device::Column::JobId.is_in(interrupted.clone()))
+            .filter(action_job_device::Column::Status.is_in([
+                ActionDeliveryStatus::Queued.as_ref(),
+                ActionDeliveryStatus::Sent.as_ref(),
+            ]))
+            .exec(&txn)
+            .await?;
+
+        let res = action_job::Entity::update_many()
+            .set(action_job::ActiveModel {
+                status: Set(ActionJobStatus::Completed.as_ref().to_string()),
+                finished_at: Set(Some(now)),
+                lease_expires_at: Set(None),
+                ..Default::default()
+            })
+            .filter(action_job::Column::Id.is_in(interrupted))
+            .exec(&txn)
+            .await?;
+
+        txn.commit().await?;
+
+        Ok(res.rows_affected)
+    }
+
+    /// Transmits the action of a job to its queued microdevices and completes the job.
+    async fn run(
+        mm: ModelManager,
+        cluster_id: Uuid,
+        job_id: Uuid,
+        targets: Vec<MicrodeviceRecord>,
+        action: MicrodeviceAction,
+        payload: Value,
+    ) {
+        let fut: Vec<_> = targets
+            .into_iter()
+            .map(|rec| {
+                Self::deliver(
+                    &mm,
+                    cluster_id,
+                    job_id,
+                    rec,
+                    action.clone(),
+                    payload.clone(),
+                )
+            })
+            .collect();
+
+        let heartbeat = tokio::spawn(Self::renew_lease(mm.clone(), job_id));
+
+        futures::stream::iter(fut)
+            .buffer_unordered(CONFIG.actions.max_concurrency.max(1))
+            .collect::<Vec<_>>()
+            .await;
+
+        heartbeat.abort();
+
+        let res = action_job::Entity::update_many()
+            .set(action_job::ActiveModel {
+                status: Set(ActionJobStatus::Completed.as_ref().to_string()),
+                finished_at: Set(Some(chrono::Utc::now().fixed_offset())),
+                lease_expires_at: Set(None),
+                ..Default::default()
+            })
+            .filter(action_job::Column::Id.eq(job_id))
+            .filter(action_job::Column::Owner.eq(&mm.instance_id))
+            .filter(action_job::Column::Status.eq(ActionJobStatus::Running.as_ref()))
+            .exec(&mm.db)
+            .await;
+
+        match res {
+            Ok(res) if res.rows_affected == 0 => {
+                warn!(
+                    "Action job `{}` was completed after its lease expired",
+                    job_id
+                );
+                return;
+            }
+            Ok(_) => {}
+            Err(e) => error!("Failed to complete action job `{}`: {}", job_id, e),
+        }
+
+        Self::publish(
+            &mm,
+            cluster_id,
+            job_id,
+            None,
+            ActionJobStatus::Completed.as_ref(),
+        );
+    }
+
+    async fn deliver(
+        mm: &ModelManager,
+        cluster_id: Uuid,
+        job_id: Uuid,
+        microdevice: MicrodeviceRecord,
+        action: MicrodeviceAction,
+        payload: Value,
+    ) {
+        let Some(microdevice_id) = microdevice.id else {
+            return;
+        };
+
+        let mut update = action_job_device::ActiveModel {
+            status: Set(ActionDeliveryStatus::Sent.as_ref().to_string()),
+            sent_at: Set(Some(chrono::Utc::now().fixed_offset())),
+            ..Default::default()
+        };
+
+        // Deliveries failed by the recovery of the job are not sent anymore
+        let queued = ActionDeliveryStatus::Queued;
+        if !Self::update_device(mm, job_id, microdevice_id, queued, update.clone()).await {
+            return;
+        }
+        Self::publish(
+            mm,
+            cluster_id,
+            job_id,
+            Some(microdevice_id),
+            ActionDeliveryStatus::Sent.as_ref(),
+        );
+
+        let res = MicrodeviceBMC::send_action(mm, &microdevice, action, payload).await;
+
+        let status = match &res {
+            Ok(response) => {
+                update.response = Set(Some(response.clone()));
+                ActionDeliveryStatus::Acknowledged
+            }
+            Err(Error {
+                kind: ErrorKind::AmpqError(ampq::error::Error::ResponseTimeout),
+                message,
+            }) => {
+                update.error = Set(Some(message.clone()));
+                ActionDeliveryStatus::TimedOut
+            }
+            Err(Error {
+                kind: ErrorKind::RateLimited,
+                message,
+            }) => {
+                update.error = Set(Some(message.clone()));
+                ActionDeliveryStatus::RateLimited
+            }
+            Err(e) => {
+                update.error = Set(Some(e.message.clone()));
+                ActionDeliveryStatus::Failed
+            }
+        };
+
+        update.status = Set(status.as_ref().to_string());
+        update.completed_at = Set(Some(chrono::Utc::now().fixed_offset()));
+        let sent = ActionDeliveryStatus::Sent;
+        if !Self::update_device(mm, job_id, microdevice_id, sent, update).await {
+            return;
+        }
+        Self::publish(
+            mm,
+            cluster_id,
+            job_id,
+            Some(microdevice_id),
+            status.as_ref(),
+        );
+    }
+
+    /// Notifies live subscribers about the progress of a job.
+    fn publish(
+        mm: &ModelManager,
+        cluster_id: Uuid,
+        job_id: Uuid,
+        microdevice_id: Option<i32>,
+        status: &str,
+    ) {
+        mm.events.publish(LiveEvent::JobProgress {
+            cluster_id,
+            job_id,
+            microdevice_id,
+            status: status.to_string(),
+        });
+    }
+
+    /// Moves the delivery to a microdevice on from `current`, returns whether
+    /// the delivery was still in that status.
+    async fn update_device(
+        mm: &ModelManager,
+        job_id: Uuid,
+        microdevice_id: i32,
+        current: ActionDeliveryStatus,
+        update: action_job_device::ActiveModel,
+    ) -> bool {
+        let res = action_job_device::Entity::update_many()
+            .set(update)
+            .filter(action_job_device::Column::JobId.eq(job_id))
+            .filter(action_job_device::Column::MicrodeviceId.eq(microdevice_id))
+            .filter(action_job_device::Column::Status.eq(current.as_ref()))
+            .exec(&mm.db)
+            .await;
+
+        match res {
+            Ok(res) => res.rows_affected > 0,
+            Err(e) => {
+                error!(
+                    "Failed to update microdevice `{}` of action job `{}`: {}",
+                    microdevice_id, job_id, e
+                );
+                false
+            }
+        }
+    }
+
+    /// Extends the lease of a running job until the task is aborted.
+    async fn renew_lease(mm: ModelManager, job_id: Uuid) {
+        let period = Duration::from_secs((CONFIG.actions.job_lease / 3).max(1));
+        let mut interval = tokio::time::interval(period);
+
+        // The lease was just taken by `submit`
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+
+            let res = action_job::Entity::update_many()
+                .set(action_job::ActiveModel {
+                    lease_expires_at: Set(Some(Self::lease_expiry())),
+                    ..Default::default()
+                })
+                .filter(action_job::Column::Id.eq(job_id))
+                .filter(action_job::Column::Owner.eq(&mm.instance_id))
+                .filter(action_job::Column::Status.eq(ActionJobStatus::Running.as_ref()))
+                .exec(&mm.db)
+                .await;
+
+            if let Err(e) = res {
+                warn!(
+                    "Failed to renew the lease of action job `{}`: {}",
+                    job_id, e
+                );
+            }
+        }
+    }
+
+    fn lease_expiry() -> DateTimeWithTimeZone {
+        (chrono::Utc::now() + Duration::from_secs(CONFIG.actions.job_lease)).fixed_offset()
+    }
+
+    async fn record(mm: &ModelManager, job: action_job::Model) -> Result<ActionJobRecord> {
+        let devices = action_job_device::Entity::find()
+            .filter(action_job_device::Column::JobId.eq(job.id))
+            .order_by_asc(action_job_device::Column::MicrodeviceId)
+            .all(&mm.db)
+            .await?;
+
+        Ok(ActionJobRecord {
+            id: job.id,
+            action: job.action,
+            payload: job.payload,
+            status: job.status,
+            created_by: job.created_by,
+            created_at: job.created_at,
+            finished_at: job.finished_at,
+            devices: devices.into_iter().map(Into::into).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{FakeDeviceError, FakeDeviceRule};
+    use crate::model::bus::in_process::{FakeDeviceResponder, InProcessBus};
+    use entity::{cluster, microdevice, microdevice_action, user, user_cluster};
+    use sea_orm::{ConnectionTrait, Database, DbBackend, Schema};
+    use serde_json::json;
+
+    /// Model manager on an in-memory database with a cluster of the user in
+    /// `Ctx` holding microdevices 1 and 2.
+    async fn setup() -> (ModelManager, Ctx, Uuid) {
+        let mut mm = ModelManager::in_process(InProcessBus::new(FakeDeviceResponder::default()));
+        mm.db = Database::connect("sqlite::memory:").await.unwrap();
+
+        let schema = Schema::new(DbBackend::Sqlite);
+        for table in [
+            schema.create_table_from_entity(user::Entity),
+            schema.create_table_from_entity(cluster::Entity),
+            schema.create_table_from_entity(user_cluster::Entity),
+            schema.create_table_from_entity(microdevice::Entity),
+            schema.create_table_from_entity(microdevice_action::Entity),
+            schema.create_table_from_entity(action_job::Entity),
+            schema.create_table_from_entity(action_job_device::Entity),
+        ] {
+            mm.db
+                .execute(mm.db.get_database_backend().build(&table))
+                .await
+                .unwrap();
+        }
+
+        let now = chrono::Utc::now().fixed_offset();
+        let user_id = Uuid::new_v4();
+        let cluster_id = Uuid::new_v4();
+
+        user::ActiveModel {
+            id: Set(user_id),
+            username: Set("operator".to_string()),
+            password_hash: Set(String::new()),
+        }
+        .insert(&mm.db)
+        .await
+        .unwrap();
+
+        cluster::ActiveModel {
+            id: Set(cluster_id),
+            name: Set("factory".to_string()),
+            created_at: Set(now),
+            updated_at: Set(now),
+        }
+        .insert(&mm.db)
+        .await
+        .unwrap();
+
+        user_cluster::ActiveModel {
+            user_id: Set(user_id),
+            cluster_id: Set(cluster_id),
+            role: Set("owner".to_string()),
+        }
+        .insert(&mm.db)
+        .await
+        .unwrap();
+
+        for id in [1, 2] {
+            microdevice::ActiveModel {
+                id: Set(id),
+                cluster_id: Set(cluster_id),
+                name: Set(format!("sensor-{}", id)),
+                description: Set(None),
+                topics: Set(None),
+                lifecycle_state: Set("active".to_string()),
+                labels: Set(json!({})),
+            }
+            .insert(&mm.db)
+            .await
+            .unwrap();
+        }
+
+        (mm, Ctx::new_user(user_id.to_string()), cluster_id)
+    }
+
+    async fn job(mm: &ModelManager, job_id: Uuid) -> action_job::Model {
+        action_job::Entity::find_by_id(job_id)
+            .one(&mm.db)
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    async fn deliveries(mm: &ModelManager, job_id: Uuid) -> Vec<(i32, String)> {
+        action_job_device::Entity::find()
+            .filter(action_job_device::Column::JobId.eq(job_id))
+            .order_by_asc(action_job_device::Column::MicrodeviceId)
+            .all(&mm.db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|device| (device.microdevice_id, device.status))
+            .collect()
+    }
+
+    /// Inserts a running job with a delivery to microdevice 1 and 2.
+    async fn running_job(
+        mm: &ModelManager,
+        cluster_id: Uuid,
+        lease_expires_at: DateTimeWithTimeZone,
+    ) -> Uuid {
+        let now = chrono::Utc::now().fixed_offset();
+        let job_id = Uuid::new_v4();
+
+        action_job::ActiveModel {
+            id: Set(job_id),
+            cluster_id: Set(cluster_id),
+            action: Set("restart".to_string()),
+            payload: Set(json!({})),
+            status: Set(ActionJobStatus::Running.as_ref().to_string()),
+            created_by: Set(None),
+            created_at: Set(now),
+            finished_at: Set(None),
+            owner: Set(Some("another-instance".to_string())),
+            lease_expires_at: Set(Some(lease_expires_at)),
+        }
+        .insert(&mm.db)
+        .await
+        .unwrap();
+
+        for (microdevice_id, status) in [
+            (1, ActionDeliveryStatus::Sent),
+            (2, ActionDeliveryStatus::Acknowledged),
+        ] {
+            action_job_device::ActiveModel {
+                job_id: Set(job_id),
+                microdevice_id: Set(microdevice_id),
+                status: Set(status.as_ref().to_string()),
+                queued_at: Set(now),
+                ..Default::default()
+            }
+            .insert(&mm.db)
+            .await
+            .unwrap();
+        }
+
+        job_id
+    }
+
+    #[tokio::test]
+    async fn submitted_jobs_settle_every_delivery() {
+        let (mm, ctx, cluster_id) = setup().await;
+
+        mm.in_process_bus
+            .as_ref()
+            .unwrap()
+            .responder()
+            .script(FakeDeviceRule {
+                action: None,
+                microdevice_id: Some(2),
+                reply: None,
+                error: Some(FakeDeviceError::Timeout),
+                delay_ms: None,
+            });
+
+        let record = ActionJobBaseModelController::submit(
+            &mm,
+            &ctx,
+            cluster_id.to_string(),
+            vec![MicrodeviceId::Id(1), MicrodeviceId::Id(2)],
+            MicrodeviceAction::Restart,
+            json!({}),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(record.status, "running");
+        assert_eq!(
+            job(&mm, record.id).await.owner,
+            Some(mm.instance_id.clone())
+        );
+
+        let mut finished = job(&mm, record.id).await;
+        for _ in 0..100 {
+            if finished.status == ActionJobStatus::Completed.as_ref() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            finished = job(&mm, record.id).await;
+        }
+
+        assert_eq!(finished.status, "completed");
+        assert!(finished.finished_at.is_some());
+        assert_eq!(finished.lease_expires_at, None);
+        assert_eq!(
+            deliveries(&mm, record.id).await,
+            vec![
+                (1, "acknowledged".to_string()),
+                (2, "timed-out".to_string())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn only_jobs_with_an_expired_lease_are_recovered() {
+        let (mm, _, cluster_id) = setup().await;
+        let now = chrono::Utc::now().fixed_offset();
+
+        let expired = running_job(&mm, cluster_id, now - Duration::from_secs(1)).await;
+        let leased = running_job(&mm, cluster_id, now + Duration::from_secs(60)).await;
+
+        let recovered = ActionJobBaseModelController::recover_interrupted(&mm)
+            .await
+            .unwrap();
+
+        assert_eq!(recovered, 1);
+        assert_eq!(job(&mm, expired).await.status, "completed");
+        assert_eq!(
+            deliveries(&mm, expired).await,
+            vec![(1, "failed".to_string()), (2, "acknowledged".to_string())]
+        );
+
+        assert_eq!(job(&mm, leased).await.status, "running");
+        assert_eq!(
+            deliveries(&mm, leased).await,
+            vec![(1, "sent".to_string()), (2, "acknowledged".to_string())]
+        );
+
+        // The late answer of the former owner keeps the delivery failed
+        let updated = ActionJobBaseModelController::update_device(
+            &mm,
+            expired,
+            1,
+            ActionDeliveryStatus::Sent,
+            action_job_device::ActiveModel {
+                status: Set(ActionDeliveryStatus::Acknowledged.as_ref().to_string()),
+                ..Default::default()
+            },
+        )
+        .await;
+
+        assert!(!updated);
+        assert_eq!(deliveries(&mm, expired).await[0].1, "failed");
+    }
+}
@@ -0,0 +1,166 @@
+use super::error::{Error, Result};
+use super::{envelope, BrokerStatus, ConsumerHandle, DeadLetter, DeadLetterSource, MessageBroker};
+use crate::model::bus::{
+    ActionRoute, Consumer, Delivery, DeliveryHandle, InboundQueue, MessageBus,
+};
+use amqprs::channel::{BasicAckArguments, BasicNackArguments, BasicPublishArguments, Channel};
+use amqprs::BasicProperties;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use tokio::sync::{mpsc, watch};
+use tracing::debug;
+
+#[async_trait]
+impl MessageBus for MessageBroker {
+    fn status(&self) -> BrokerStatus {
+        MessageBroker::status(self)
+    }
+
+    fn subscribe_state(&self) -> watch::Receiver<BrokerStatus> {
+        MessageBroker::subscribe_state(self)
+    }
+
+    async fn transmit_action(
+        &self,
+        route: &ActionRoute,
+        payload: Value,
+        deadline: DateTime<Utc>,
+    ) -> Result<Value> {
+        MessageBroker::transmit_action(self, route, payload, deadline).await
+    }
+
+    async fn publish_event(&self, message_type: &str, payload: Value) -> Result<()> {
+        self.publish_to_gateway(message_type, payload).await
+    }
+
+    async fn consume(&self, queue: InboundQueue) -> Result<Consumer> {
+        let handle = match queue {
+            InboundQueue::Registrar => self.registrar_consume().await?,
+            InboundQueue::Telemetry => self.telemetry_consumer().await?,
+            InboundQueue::Shadow => self.shadow_consumer().await?,
+        };
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(forward_deliveries(self.clone(), handle, tx));
+
+        Ok(Consumer { rx })
+    }
+
+    async fn dead_letters(
+        &self,
+        source: DeadLetterSource,
+        limit: usize,
+    ) -> Result<Vec<DeadLetter>> {
+        MessageBroker::dead_letters(self, source, limit).await
+    }
+
+    async fn replay_dead_letters(&self, source: DeadLetterSource, limit: usize) -> Result<usize> {
+        MessageBroker::replay_dead_letters(self, source, limit).await
+    }
+
+    async fn purge_dead_letters(&self, source: DeadLetterSource) -> Result<u32> {
+        MessageBroker::purge_dead_letters(self, source).await
+    }
+}
+
+/// Hands the messages of an amqprs consumer to the bus consumer until either
+/// side stops, the channel is closed afterwards.
+async fn forward_deliveries(
+    broker: MessageBroker,
+    handle: ConsumerHandle,
+    tx: mpsc::UnboundedSender<Delivery>,
+) {
+    let ConsumerHandle { tag, mut rx, ch } = handle;
+
+    loop {
+        let msg = tokio::select! {
+            msg = rx.recv() => msg,
+            _ = tx.closed() => None,
+        };
+
+        let Some(msg) = msg else {
+            break;
+        };
+
+        let props = msg.basic_properties.unwrap_or_default();
+        let reply_to = props.reply_to().cloned();
+
+        let delivery = Delivery::new(
+            msg.content.clone(),
+            reply_to,
+            envelope::envelope(&props),
+            Box::new(AmqpDelivery {
+                broker: broker.clone(),
+                chan: ch.clone(),
+                delivery_tag: msg.deliver.map(|deliver| deliver.delivery_tag()),
+                props,
+                content: msg.content.unwrap_or_default(),
+            }),
+        );
+
+        if tx.send(delivery).is_err() {
+            break;
+        }
+    }
+
+    debug!("Consumer `{}` stopped", tag);
+}
+
+/// Message received from the broker, settled on the channel it came from
+struct AmqpDelivery {
+    broker: MessageBroker,
+    chan: Channel,
+    delivery_tag: Option<u64>,
+    props: BasicProperties,
+    content: Vec<u8>,
+}
+
+#[async_trait]
+impl DeliveryHandle for AmqpDelivery {
+    async fn ack(&self) -> Result<()> {
+        if let Some(tag) = self.delivery_tag {
+            self.chan
+                .basic_ack(BasicAckArguments::new(tag, false))
+                .await
+                .map_err(Error::ChannelError)?;
+        }
+
+        Ok(())
+    }
+
+    async fn reject(&self, requeue: bool) -> Result<()> {
+        if let Some(tag) = self.delivery_tag {
+            self.chan
+                .basic_nack(BasicNackArguments::new(tag, false, requeue))
+                .await
+                .map_err(Error::ChannelError)?;
+        }
+
+        Ok(())
+    }
+
+    async fn reply(&self, payload: Vec<u8>) -> Result<()> {
+        let Some(reply_to) = self.props.reply_to() else {
+            return Ok(());
+        };
+
+        // The reply keeps the correlation id and the encoding of the request
+        let props = envelope::properties(&envelope::envelope(&self.props).reply());
+
+        self.chan
+            .basic_publish(
+                props,
+                payload,
+                BasicPublishArguments::new("", reply_to).finish(),
+            )
+            .await
+            .map_err(Error::PublishError)
+    }
+
+    async fn dead_letter(&self, source: DeadLetterSource, reason: &str) -> Result<()> {
+        self.broker
+            .dead_letter(source, &self.props, self.content.clone(), reason)
+            .await
+    }
+}
@@ -0,0 +1,131 @@
+use amqprs::callbacks::ChannelCallback;
+use amqprs::channel::Channel;
+use amqprs::{Ack, BasicProperties, Cancel, CloseChannel, Nack, Return};
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+use tracing::{debug, warn};
+
+/// Answer of the broker to a message published in confirm mode
+#[derive(Debug)]
+pub(super) struct Confirm {
+    pub(super) acked: bool,
+    /// Reason given by the broker when the message could not be routed
+    pub(super) returned: Option<String>,
+}
+
+#[derive(Default)]
+struct ConfirmState {
+    /// Delivery tag of the last published message
+    last_tag: u64,
+    waiting: BTreeMap<u64, oneshot::Sender<Confirm>>,
+    /// Returned message whose confirmation has not arrived yet
+    returned: Option<String>,
+}
+
+/// Matches the confirmations of a channel to the published messages.
+///
+/// The broker sends `basic.return` for an unroutable mandatory message right
+/// before its `basic.ack`, so the return is attached to the next confirmation.
+#[derive(Default)]
+pub(super) struct ConfirmTracker {
+    state: Mutex<ConfirmState>,
+}
+
+impl ConfirmTracker {
+    /// Reserves the delivery tag of the next published message.
+    pub(super) fn expect(&self) -> oneshot::Receiver<Confirm> {
+        let (tx, rx) = oneshot::channel();
+
+        let mut state = self.lock_state();
+        state.last_tag += 1;
+        let tag = state.last_tag;
+        state.waiting.insert(tag, tx);
+
+        rx
+    }
+
+    fn settle(&self, delivery_tag: u64, multiple: bool, acked: bool) {
+        let mut state = self.lock_state();
+
+        let tags: Vec<u64> = match multiple {
+            true => state
+                .waiting
+                .range(..=delivery_tag)
+                .map(|(t, _)| *t)
+                .collect(),
+            false => vec![delivery_tag],
+        };
+
+        for tag in tags {
+            if let Some(tx) = state.waiting.remove(&tag) {
+                let returned = state.returned.take();
+                let _ = tx.send(Confirm { acked, returned });
+            }
+        }
+    }
+
+    fn lock_state(&self) -> std::sync::MutexGuard<'_, ConfirmState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Forwards the confirmations and returns of a channel to its tracker
+pub(super) struct ConfirmCallback {
+    pub(super) tracker: Arc<ConfirmTracker>,
+}
+
+#[async_trait]
+impl ChannelCallback for ConfirmCallback {
+    async fn close(
+        &mut self,
+        channel: &Channel,
+        close: CloseChannel,
+    ) -> Result<(), amqprs::error::Error> {
+        warn!("Publish channel {} closed by broker: {}", channel, close);
+        Ok(())
+    }
+
+    async fn cancel(
+        &mut self,
+        _channel: &Channel,
+        _cancel: Cancel,
+    ) -> Result<(), amqprs::error::Error> {
+        Ok(())
+    }
+
+    async fn flow(
+        &mut self,
+        _channel: &Channel,
+        active: bool,
+    ) -> Result<bool, amqprs::error::Error> {
+        Ok(active)
+    }
+
+    async fn publish_ack(&mut self, _channel: &Channel, ack: Ack) {
+        self.tracker.settle(ack.delivery_tag(), ack.mutiple(), true);
+    }
+
+    async fn publish_nack(&mut self, _channel: &Channel, nack: Nack) {
+        debug!("Message {} nacked by broker", nack.delivery_tag());
+        self.tracker
+            .settle(nack.delivery_tag(), nack.multiple(), false);
+    }
+
+    async fn publish_return(
+        &mut self,
+        _channel: &Channel,
+        ret: Return,
+        _basic_properties: BasicProperties,
+        _content: Vec<u8>,
+    ) {
+        debug!("Message returned by broker: {}", ret);
+        self.tracker.lock_state().returned = Some(format!(
+            "{} ({}) for routing key `{}`",
+            ret.reply_text(),
+            ret.reply_code(),
+            ret.routing_key()
+        ));
+    }
+}
@@ -0,0 +1,108 @@
+use super::error::{Error, Result};
+use crate::config::AmpqConfig;
+use amqprs::connection::OpenConnectionArguments;
+use amqprs::tls::TlsAdaptor;
+use std::path::Path;
+
+/// Builds the arguments used to open the connection to the broker.
+///
+/// `uri` takes the place of the host, port, credentials and virtual host when
+/// it is set. A heartbeat given in its query is kept over `heartbeat`.
+pub(super) fn connection_arguments(config: &AmpqConfig) -> Result<OpenConnectionArguments> {
+    let mut args = match &config.uri {
+        Some(uri) => OpenConnectionArguments::try_from(uri.as_str())
+            .map_err(|err| Error::InvalidConfig(err.to_string()))?,
+        None => {
+            let mut args = OpenConnectionArguments::new(
+                &config.host,
+                config.port,
+                &config.user,
+                &config.password,
+            );
+            args.virtual_host(&config.vhost);
+            args
+        }
+    };
+
+    if !config
+        .uri
+        .as_deref()
+        .is_some_and(|uri| uri.contains("heartbeat="))
+    {
+        args.heartbeat(config.heartbeat);
+    }
+
+    args.connection_name(&config.connection_name);
+
+    let secure = match &config.uri {
+        Some(uri) => uri.starts_with("amqps://"),
+        None => config.tls.enabled,
+    };
+
+    if secure {
+        args.tls_adaptor(tls_adaptor(config)?);
+    }
+
+    Ok(args.finish())
+}
+
+fn tls_adaptor(config: &AmpqConfig) -> Result<TlsAdaptor> {
+    let tls = &config.tls;
+    let domain = match (&tls.domain, &config.uri) {
+        (Some(domain), _) => domain.clone(),
+        (None, Some(uri)) => uri_host(uri)
+            .ok_or_else(|| Error::InvalidConfig(format!("no host in `{}`", uri)))?
+            .to_string(),
+        (None, None) => config.host.clone(),
+    };
+    let ca_cert = tls.ca_cert.as_deref().map(Path::new);
+
+    let adaptor = match (&tls.client_cert, &tls.client_key) {
+        (Some(cert), Some(key)) => {
+            TlsAdaptor::with_client_auth(ca_cert, Path::new(cert), Path::new(key), domain)
+        }
+        (None, None) => TlsAdaptor::without_client_auth(ca_cert, domain),
+        _ => {
+            return Err(Error::InvalidConfig(
+                "`tls.client_cert` and `tls.client_key` must be set together".to_string(),
+            ))
+        }
+    };
+
+    adaptor.map_err(|err| Error::InvalidConfig(format!("cannot load TLS files: {}", err)))
+}
+
+/// Host of the broker, for logs since the URI may hold credentials
+pub(super) fn broker_host(config: &AmpqConfig) -> &str {
+    match &config.uri {
+        Some(uri) => uri_host(uri).unwrap_or_default(),
+        None => &config.host,
+    }
+}
+
+/// Host of an `amqp(s)://[user[:password]@]host[:port][/vhost]` URI
+fn uri_host(uri: &str) -> Option<&str> {
+    let authority = uri.split_once("://")?.1.split(['/', '?']).next()?;
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    let host = host.split(':').next()?;
+
+    (!host.is_empty()).then_some(host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::uri_host;
+
+    #[test]
+    fn uri_host_skips_credentials_port_and_vhost() {
+        assert_eq!(
+            uri_host("amqps://user:p@ss@broker.example.com:5671/prod?heartbeat=10"),
+            Some("broker.example.com")
+        );
+        assert_eq!(uri_host("amqp://localhost"), Some("localhost"));
+        assert_eq!(uri_host("amqp://user@/vhost"), None);
+        assert_eq!(uri_host("localhost:5672"), None);
+    }
+}
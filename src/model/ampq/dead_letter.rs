@@ -0,0 +1,270 @@
+use super::error::{Error, Result};
+use super::{envelope, MessageBroker};
+use crate::config::CONFIG;
+use amqprs::channel::{
+    BasicAckArguments, BasicGetArguments, Channel, ExchangeDeclareArguments, ExchangeType,
+    QueueBindArguments, QueueDeclareArguments, QueuePurgeArguments,
+};
+use amqprs::{BasicProperties, FieldTable, FieldValue, DELIVERY_MODE_PERSISTENT};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+/// Header carrying why a message was dead-lettered
+const FAILURE_REASON_HEADER: &str = "x-failure-reason";
+/// Header carrying when a message was dead-lettered, in RFC 3339
+const FAILED_AT_HEADER: &str = "x-failed-at";
+/// Header carrying the queue a message was consumed from
+const SOURCE_QUEUE_HEADER: &str = "x-source-queue";
+
+/// Queue whose failed messages are dead-lettered
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeadLetterSource {
+    Registrar,
+    Telemetry,
+}
+
+impl DeadLetterSource {
+    pub fn queue_name(self) -> &'static str {
+        match self {
+            Self::Registrar => &CONFIG.ampq.registrar_queue_name,
+            Self::Telemetry => &CONFIG.ampq.telemetry_queue_name,
+        }
+    }
+
+    pub fn dead_letter_queue_name(self) -> String {
+        format!(
+            "{}{}",
+            self.queue_name(),
+            CONFIG.ampq.dead_letter.queue_suffix
+        )
+    }
+}
+
+/// Dead-lettered message as returned by the administration endpoints
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct DeadLetter {
+    #[schema(example = "registrar-wq")]
+    pub source_queue: Option<String>,
+    #[schema(example = "invalid payload: missing field `device_id`")]
+    pub failure_reason: Option<String>,
+    #[schema(example = "2026-01-01T12:00:00+00:00")]
+    pub failed_at: Option<String>,
+    pub content_type: Option<String>,
+    /// Content of the message, as a string when it cannot be decoded
+    pub payload: serde_json::Value,
+}
+
+impl MessageBroker {
+    /// Declares the dead-letter exchange and the dead-letter queue of `source`.
+    pub(super) async fn declare_dead_letters(&self, source: DeadLetterSource) -> Result<()> {
+        let chan = self.create_channel().await?;
+        let exchange = &CONFIG.ampq.dead_letter.exchange;
+        let queue = source.dead_letter_queue_name();
+
+        chan.exchange_declare(
+            ExchangeDeclareArguments::of_type(exchange, ExchangeType::Direct)
+                .durable(true)
+                .finish(),
+        )
+        .await
+        .map_err(Error::ChannelError)?;
+
+        chan.queue_declare(QueueDeclareArguments::durable_client_named(&queue))
+            .await
+            .map_err(Error::QueueDeclareError)?;
+
+        chan.queue_bind(QueueBindArguments::new(
+            &queue,
+            exchange,
+            source.queue_name(),
+        ))
+        .await
+        .map_err(Error::QueueDeclareError)?;
+
+        debug!("Dead-letter queue `{}` declared", queue);
+
+        let _ = chan.close().await;
+
+        Ok(())
+    }
+
+    /// Publishes a message that could not be processed to the dead-letter
+    /// queue of `source`, recording `reason` in its headers.
+    ///
+    /// The message has to be acknowledged on its own channel afterwards.
+    pub(super) async fn dead_letter(
+        &self,
+        source: DeadLetterSource,
+        props: &BasicProperties,
+        content: Vec<u8>,
+        reason: &str,
+    ) -> Result<()> {
+        let mut props = props.clone();
+        let mut headers = props.headers().cloned().unwrap_or_default();
+
+        insert_header(&mut headers, FAILURE_REASON_HEADER, reason);
+        insert_header(
+            &mut headers,
+            FAILED_AT_HEADER,
+            &chrono::Utc::now().to_rfc3339(),
+        );
+        insert_header(&mut headers, SOURCE_QUEUE_HEADER, source.queue_name());
+
+        let props = props
+            .with_headers(headers)
+            .with_delivery_mode(DELIVERY_MODE_PERSISTENT)
+            .finish();
+
+        self.channels
+            .publish(
+                &self.connection()?,
+                &CONFIG.ampq.dead_letter.exchange,
+                source.queue_name(),
+                props,
+                content,
+            )
+            .await?;
+
+        warn!(
+            "Message from `{}` dead-lettered: {}",
+            source.queue_name(),
+            reason
+        );
+
+        Ok(())
+    }
+
+    /// Returns up to `limit` dead-lettered messages of `source`, oldest first,
+    /// without removing them.
+    pub(super) async fn dead_letters(
+        &self,
+        source: DeadLetterSource,
+        limit: usize,
+    ) -> Result<Vec<DeadLetter>> {
+        let chan = self.create_channel().await?;
+        let queue = source.dead_letter_queue_name();
+        let mut letters = Vec::new();
+
+        while letters.len() < limit {
+            let Some((_, props, content)) = get(&chan, &queue).await? else {
+                break;
+            };
+
+            letters.push(DeadLetter {
+                source_queue: header(&props, SOURCE_QUEUE_HEADER),
+                failure_reason: header(&props, FAILURE_REASON_HEADER),
+                failed_at: header(&props, FAILED_AT_HEADER),
+                content_type: props.content_type().cloned(),
+                payload: envelope::envelope(&props)
+                    .decode(&content)
+                    .unwrap_or_else(|_| {
+                        serde_json::Value::String(String::from_utf8_lossy(&content).into_owned())
+                    }),
+            });
+        }
+
+        // Closing the channel puts the unacknowledged messages back in the queue
+        let _ = chan.close().await;
+
+        Ok(letters)
+    }
+
+    /// Moves up to `limit` dead-lettered messages of `source` back to the
+    /// queue they failed in, returns how many were moved.
+    pub(super) async fn replay_dead_letters(
+        &self,
+        source: DeadLetterSource,
+        limit: usize,
+    ) -> Result<usize> {
+        let connection = self.connection()?;
+        let chan = self.create_channel().await?;
+        let queue = source.dead_letter_queue_name();
+        let mut replayed = 0;
+
+        while replayed < limit {
+            let Some((get_ok, mut props, content)) = get(&chan, &queue).await? else {
+                break;
+            };
+
+            if let Some(headers) = props.headers() {
+                let mut headers = headers.clone();
+                for name in [FAILURE_REASON_HEADER, FAILED_AT_HEADER, SOURCE_QUEUE_HEADER] {
+                    if let Ok(name) = name.try_into() {
+                        headers.remove(&name);
+                    }
+                }
+                props.with_headers(headers);
+            }
+
+            // Messages not acknowledged yet go back to the dead-letter queue
+            // when the channel is closed
+            if let Err(err) = self
+                .channels
+                .publish(&connection, "", source.queue_name(), props, content)
+                .await
+            {
+                let _ = chan.close().await;
+                return Err(err);
+            }
+
+            chan.basic_ack(BasicAckArguments::new(get_ok.delivery_tag(), false))
+                .await
+                .map_err(Error::ChannelError)?;
+
+            replayed += 1;
+        }
+
+        let _ = chan.close().await;
+
+        info!(
+            "Replayed {} dead-lettered message(s) to `{}`",
+            replayed,
+            source.queue_name()
+        );
+
+        Ok(replayed)
+    }
+
+    /// Drops every dead-lettered message of `source`, returns how many were dropped.
+    pub(super) async fn purge_dead_letters(&self, source: DeadLetterSource) -> Result<u32> {
+        let chan = self.create_channel().await?;
+        let queue = source.dead_letter_queue_name();
+
+        let purged = chan
+            .queue_purge(QueuePurgeArguments::new(&queue))
+            .await
+            .map_err(Error::ChannelError)?
+            .unwrap_or_default();
+
+        let _ = chan.close().await;
+
+        info!(
+            "Purged {} dead-lettered message(s) from `{}`",
+            purged, queue
+        );
+
+        Ok(purged)
+    }
+}
+
+async fn get(chan: &Channel, queue: &str) -> Result<Option<amqprs::channel::GetMessage>> {
+    chan.basic_get(BasicGetArguments::new(queue))
+        .await
+        .map_err(Error::ChannelError)
+}
+
+pub(super) fn insert_header(headers: &mut FieldTable, name: &str, value: &str) {
+    if let Ok(name) = name.try_into() {
+        headers.insert(name, FieldValue::from(value));
+    }
+}
+
+pub(super) fn header(props: &BasicProperties, name: &str) -> Option<String> {
+    let name = name.try_into().ok()?;
+
+    match props.headers()?.get(&name)? {
+        FieldValue::S(value) => Some(value.as_ref().clone()),
+        _ => None,
+    }
+}
@@ -0,0 +1,87 @@
+use super::dead_letter::{header, insert_header};
+use crate::model::bus::Envelope;
+use amqprs::{BasicProperties, FieldValue};
+use chrono::{DateTime, TimeZone, Utc};
+
+/// Header carrying the schema version of a message
+const SCHEMA_VERSION_HEADER: &str = "x-schema-version";
+/// Header carrying when a message expires, in RFC 3339
+const EXPIRES_AT_HEADER: &str = "x-expires-at";
+
+/// Properties carrying `envelope`.
+///
+/// The message type, content type, send time and correlation id map to the
+/// AMQP properties of the same meaning, the version and expiry go to headers.
+/// An expiring message is also given an AMQP expiration so the broker drops it
+/// once it is stale.
+pub(super) fn properties(envelope: &Envelope) -> BasicProperties {
+    let mut props = BasicProperties::default();
+    let mut headers = amqprs::FieldTable::default();
+
+    if let Some(version) = envelope.schema_version {
+        if let Ok(name) = SCHEMA_VERSION_HEADER.try_into() {
+            headers.insert(name, FieldValue::I(version.into()));
+        }
+    }
+
+    if let Some(message_type) = &envelope.message_type {
+        props.with_message_type(message_type);
+    }
+
+    if let Some(content_type) = &envelope.content_type {
+        props.with_content_type(content_type);
+    }
+
+    if let Some(sent_at) = envelope.sent_at {
+        props.with_timestamp(sent_at.timestamp().max(0) as u64);
+    }
+
+    if let Some(expires_at) = envelope.expires_at {
+        insert_header(&mut headers, EXPIRES_AT_HEADER, &expires_at.to_rfc3339());
+
+        let ttl = (expires_at - Utc::now()).num_milliseconds().max(1);
+        props.with_expiration(&ttl.to_string());
+    }
+
+    if let Some(correlation_id) = &envelope.correlation_id {
+        props.with_correlation_id(correlation_id);
+    }
+
+    props.with_headers(headers).finish()
+}
+
+/// Envelope carried by the properties of a received message.
+pub(super) fn envelope(props: &BasicProperties) -> Envelope {
+    Envelope {
+        schema_version: schema_version(props),
+        message_type: props.message_type().cloned(),
+        content_type: props.content_type().cloned(),
+        sent_at: props
+            .timestamp()
+            .and_then(|secs| Utc.timestamp_opt(secs as i64, 0).single()),
+        expires_at: header(props, EXPIRES_AT_HEADER)
+            .and_then(|value| DateTime::parse_from_rfc3339(&value).ok())
+            .map(|expires_at| expires_at.with_timezone(&Utc)),
+        correlation_id: props.correlation_id().cloned(),
+    }
+}
+
+/// Version header, whichever integer type the sender picked
+fn schema_version(props: &BasicProperties) -> Option<u16> {
+    let name = SCHEMA_VERSION_HEADER.try_into().ok()?;
+
+    let version = match props.headers()?.get(&name)? {
+        FieldValue::b(v) => i64::from(*v),
+        FieldValue::B(v) => i64::from(*v),
+        FieldValue::s(v) => i64::from(*v),
+        FieldValue::u(v) => i64::from(*v),
+        FieldValue::I(v) => i64::from(*v),
+        FieldValue::i(v) => i64::from(*v),
+        FieldValue::l(v) => *v,
+        FieldValue::S(v) => v.as_ref().parse().unwrap_or(-1),
+        _ => return None,
+    };
+
+    // Out of range versions are unknown as well
+    Some(u16::try_from(version).unwrap_or(u16::MAX))
+}
@@ -1,3 +1,5 @@
+use crate::model::bus::envelope::SCHEMA_VERSION;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug)]
@@ -15,6 +17,43 @@ pub enum Error {
     CommunicationError(std::sync::mpsc::RecvError),
     ResponseTimeout,
     FailedToDeclareQueue,
+    BrokerUnavailable,
+    /// No queue is bound for the routing key of a mandatory message
+    NotRouted(String),
+    /// The broker refused to take responsibility for the message
+    Nacked,
+    /// The broker did not confirm the message in time
+    Unconfirmed,
+    /// The connection settings cannot be used, e.g. a TLS file is missing
+    InvalidConfig(String),
+    /// The message is of a schema version this process does not understand
+    UnsupportedSchemaVersion(u16),
+    /// The content type of the message has no known encoding
+    UnsupportedContentType(String),
+    EncodingFailed(String),
+    DecodingFailed(String),
+}
+
+impl Error {
+    /// Whether the failure happened before the message could reach the broker,
+    /// so sending it again cannot deliver it twice.
+    ///
+    /// Channel errors are only raised while opening a channel, failures once a
+    /// message is published are reported as `Unconfirmed`, `NotRouted` or
+    /// `ResponseTimeout` and are never retried.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Error::ConnectionError(_)
+                | Error::CreateChannelError(_)
+                | Error::ChannelError(_)
+                | Error::QueueDeclareError(_)
+                | Error::FailedToDeclareQueue
+                | Error::ConsumerDeclareError(_)
+                | Error::PublishError(_)
+                | Error::Nacked
+        )
+    }
 }
 
 impl std::error::Error for Error {}
@@ -26,13 +65,47 @@ impl std::fmt::Display for Error {
             Error::ConnectionError(e) => write!(f, "Connection error: {}", e),
             Error::ChannelError(e) => write!(f, "Channel error: {}", e),
             Error::SerdeError(e) => write!(f, "Serde error: {}", e),
-            Error::ResponseTimeout => write!(f, "AMQP broker failed to respond in time"),
+            Error::ResponseTimeout => write!(f, "Microdevice did not reply in time"),
             Error::CommunicationError(e) => write!(f, "Communication error: {}", e),
             Error::CloseConsumerError(e) => write!(f, "Close consumer error: {}", e),
             Error::FailedToDeclareQueue => write!(f, "Failed to declare queue"),
+            Error::BrokerUnavailable => write!(f, "AMQP broker is unavailable"),
             Error::CreateChannelError(e) => write!(f, "Create channel error: {}", e),
             Error::ConsumerDeclareError(e) => write!(f, "Consumer declare error: {}", e),
             Error::PublishError(e) => write!(f, "Publish error: {}", e),
+            Error::NotRouted(reason) => write!(f, "Message could not be routed: {}", reason),
+            Error::Nacked => write!(f, "Message was nacked by the AMQP broker"),
+            Error::Unconfirmed => write!(f, "AMQP broker did not confirm the message in time"),
+            Error::InvalidConfig(reason) => write!(f, "Invalid AMQP configuration: {}", reason),
+            Error::UnsupportedSchemaVersion(version) => write!(
+                f,
+                "Unsupported message schema version {}, the supported version is {}",
+                version, SCHEMA_VERSION
+            ),
+            Error::UnsupportedContentType(content_type) => {
+                write!(f, "Unsupported message content type `{}`", content_type)
+            }
+            Error::EncodingFailed(e) => write!(f, "Encode error: {}", e),
+            Error::DecodingFailed(e) => write!(f, "Decode error: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failures_after_publishing_are_not_retried() {
+        assert!(Error::Nacked.is_retryable());
+        assert!(Error::FailedToDeclareQueue.is_retryable());
+
+        for err in [
+            Error::Unconfirmed,
+            Error::NotRouted("NO_ROUTE (312)".to_string()),
+            Error::ResponseTimeout,
+        ] {
+            assert!(!err.is_retryable(), "{} must not be retried", err);
         }
     }
 }
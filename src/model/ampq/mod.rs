@@ -1,65 +1,197 @@
-use super::config;
 use crate::config::CONFIG;
-use amqprs::{
-    channel::{
-        BasicCancelArguments, BasicConsumeArguments, BasicPublishArguments, QueueDeclareArguments,
-    },
-    BasicProperties,
-};
+use crate::model::bus::{ActionRoute, Envelope, InboundQueue};
+use amqprs::channel::{BasicConsumeArguments, BasicQosArguments, QueueDeclareArguments};
 #[allow(unused_imports)]
 use error::{Error, Result};
-use tracing::{debug, error, info};
+use serde::Serialize;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::{debug, error, info, warn};
+
+pub use dead_letter::{DeadLetter, DeadLetterSource};
+mod bus;
+mod confirm;
+mod connection;
+mod dead_letter;
+mod envelope;
 pub mod error;
+mod pool;
+mod reply;
+mod routing;
+
+/// Message type of the actions sent to the microdevices
+const ACTION_MESSAGE_TYPE: &str = "action";
+
+/// How often an established connection is checked for a silent close
+const CONNECTION_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// State of the connection to the broker
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BrokerState {
+    Connecting,
+    Connected,
+    Disconnected,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, utoipa::ToSchema)]
+pub struct BrokerStatus {
+    pub state: BrokerState,
+    /// When the broker entered `state`
+    #[schema(value_type = String, format = DateTime)]
+    pub since: chrono::DateTime<chrono::Utc>,
+}
 
 #[derive(Clone)]
 pub struct MessageBroker {
-    pub connection: amqprs::connection::Connection,
+    /// Current connection, empty while disconnected
+    connection: Arc<RwLock<Option<amqprs::connection::Connection>>>,
+    state: Arc<watch::Sender<BrokerStatus>>,
+    /// Channels used to publish
+    channels: Arc<pool::ChannelPool>,
+    /// Replies to actions, shared by every action of the process
+    replies: Arc<reply::ReplyRouter>,
 }
 
-pub struct ConsumerHandle {
-    pub tag: String,
-    pub rx: tokio::sync::mpsc::UnboundedReceiver<amqprs::channel::ConsumerMessage>,
-    pub ch: amqprs::channel::Channel,
+struct ConsumerHandle {
+    tag: String,
+    rx: tokio::sync::mpsc::UnboundedReceiver<amqprs::channel::ConsumerMessage>,
+    ch: amqprs::channel::Channel,
 }
 
 impl MessageBroker {
-    pub async fn new() -> Self {
-        let args = amqprs::connection::OpenConnectionArguments::new(
-            &config::CONFIG.ampq.host,
-            config::CONFIG.ampq.port,
-            &config::CONFIG.ampq.user,
-            &config::CONFIG.ampq.password,
-        )
-        .finish();
-
-        let fut = amqprs::connection::Connection::open(&args);
-
-        let amqp_conn = match fut.await {
-            Ok(conn) => {
-                info!("Connected to amqp: {}", config::CONFIG.ampq.host);
-                conn
-            }
-            Err(err) => {
-                error!("Error connecting to amqp: {}", err);
-                panic!("Error connecting to amqp: {}", err);
-            }
+    /// Creates the broker and connects to it in the background, reconnecting
+    /// with exponential backoff whenever the connection is lost.
+    pub fn new() -> Self {
+        let (state, _) = watch::channel(BrokerStatus {
+            state: BrokerState::Connecting,
+            since: chrono::Utc::now(),
+        });
+
+        let broker = Self {
+            connection: Arc::new(RwLock::new(None)),
+            state: Arc::new(state),
+            channels: Arc::new(pool::ChannelPool::new(CONFIG.ampq.channel_pool_size)),
+            replies: reply::ReplyRouter::new(),
         };
 
-        debug!("AMQP connection established successfully");
+        tokio::spawn(broker.clone().supervise());
+
+        broker
+    }
+
+    async fn supervise(self) {
+        let reconnect = &CONFIG.ampq.reconnect;
+        let mut backoff = reconnect.initial_backoff_ms;
+
+        loop {
+            self.set_state(BrokerState::Connecting);
+
+            // The settings are read on every attempt so a bad TLS file can be
+            // fixed without restarting
+            let opened = match connection::connection_arguments(&CONFIG.ampq) {
+                Ok(args) => amqprs::connection::Connection::open(&args)
+                    .await
+                    .map_err(Error::ConnectionError),
+                Err(err) => Err(err),
+            };
+
+            match opened {
+                Ok(conn) => {
+                    info!(
+                        "Connected to amqp: {}",
+                        connection::broker_host(&CONFIG.ampq)
+                    );
+                    backoff = reconnect.initial_backoff_ms;
+
+                    if let Err(err) = routing::declare_action_exchange(&conn).await {
+                        error!("Error declaring the action exchange: {}", err);
+                    }
 
-        Self {
-            connection: amqp_conn,
+                    *self.write_connection() = Some(conn.clone());
+                    self.set_state(BrokerState::Connected);
+
+                    Self::wait_closed(&conn).await;
+
+                    *self.write_connection() = None;
+                    self.set_state(BrokerState::Disconnected);
+                    warn!("Lost connection to amqp, reconnecting");
+                }
+                Err(err) => {
+                    self.set_state(BrokerState::Disconnected);
+                    error!(
+                        "Error connecting to amqp: {}, retrying in {} ms",
+                        err, backoff
+                    );
+
+                    tokio::time::sleep(Duration::from_millis(backoff)).await;
+                    backoff = (backoff * 2).min(reconnect.max_backoff_ms);
+                }
+            }
         }
     }
 
-    pub fn is_connected(&self) -> bool {
-        debug!("Checking if AMQP connection is open");
-        self.connection.is_open()
+    async fn wait_closed(conn: &amqprs::connection::Connection) {
+        let mut check = tokio::time::interval(CONNECTION_CHECK_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = conn.listen_network_io_failure() => return,
+                _ = check.tick() => {
+                    if !conn.is_open() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    fn set_state(&self, state: BrokerState) {
+        self.state.send_if_modified(|status| {
+            if status.state == state {
+                return false;
+            }
+
+            debug!("AMQP connection state changed to {:?}", state);
+            *status = BrokerStatus {
+                state,
+                since: chrono::Utc::now(),
+            };
+            true
+        });
+    }
+
+    fn write_connection(
+        &self,
+    ) -> std::sync::RwLockWriteGuard<'_, Option<amqprs::connection::Connection>> {
+        self.connection.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The open connection, fails fast with `BrokerUnavailable` while disconnected.
+    fn connection(&self) -> Result<amqprs::connection::Connection> {
+        self.connection
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .filter(|conn| conn.is_open())
+            .cloned()
+            .ok_or(Error::BrokerUnavailable)
+    }
+
+    fn status(&self) -> BrokerStatus {
+        *self.state.borrow()
+    }
+
+    /// Notifies about every change of the connection state.
+    fn subscribe_state(&self) -> watch::Receiver<BrokerStatus> {
+        self.state.subscribe()
     }
 
     async fn create_channel(&self) -> Result<amqprs::channel::Channel> {
         debug!("Creating a new channel");
-        let fut = self.connection.open_channel(None);
+        let connection = self.connection()?;
+        let fut = connection.open_channel(None);
 
         let channel = match fut.await {
             Ok(channel) => {
@@ -75,65 +207,49 @@ impl MessageBroker {
         Ok(channel)
     }
 
-    pub async fn telemetry_consumer(&self) -> Result<ConsumerHandle> {
+    async fn telemetry_consumer(&self) -> Result<ConsumerHandle> {
         debug!("Starting telemetry consumer setup");
-        let chan = self.create_channel().await?;
-
-        let args = QueueDeclareArguments::default()
-            .queue(CONFIG.ampq.telemetry_queue_name.clone())
-            .finish();
-
-        debug!("Declaring telemetry queue with args: {:?}", args);
-
-        match chan.queue_declare(args).await {
-            Ok(Some(v)) => {
-                let (queue_name, _m, _) = v;
-                debug!("Telemetry queue declared successfully: {}", queue_name);
-
-                let args = BasicConsumeArguments::new(&queue_name, "");
-                debug!("Starting basic consume with args: {:?}", args);
-
-                let (consumer_tag, messages_rx) = chan
-                    .basic_consume_rx(args)
-                    .await
-                    .map_err(|e| Error::ConsumerDeclareError(e))?;
+        self.declare_dead_letters(DeadLetterSource::Telemetry)
+            .await?;
+        self.queue_consumer(InboundQueue::Telemetry).await
+    }
 
-                debug!(
-                    "Telemetry consumer created successfully with tag: {}",
-                    consumer_tag
-                );
+    async fn registrar_consume(&self) -> Result<ConsumerHandle> {
+        debug!("Starting registrar consumer setup");
+        self.declare_dead_letters(DeadLetterSource::Registrar)
+            .await?;
+        self.queue_consumer(InboundQueue::Registrar).await
+    }
 
-                Ok(ConsumerHandle {
-                    tag: consumer_tag,
-                    rx: messages_rx,
-                    ch: chan,
-                })
-            }
-            Ok(None) => {
-                debug!("Failed to declare telemetry queue");
-                Err(Error::FailedToDeclareQueue)
-            }
-            Err(err) => {
-                debug!("Error declaring telemetry queue: {}", err);
-                Err(Error::QueueDeclareError(err))
-            }
-        }
+    async fn shadow_consumer(&self) -> Result<ConsumerHandle> {
+        debug!("Starting shadow consumer setup");
+        self.queue_consumer(InboundQueue::Shadow).await
     }
 
-    pub async fn registrar_consume(&self) -> Result<ConsumerHandle> {
-        debug!("Starting registrar consumer setup");
+    /// Declares `queue` on a fresh channel and starts consuming from it, with
+    /// the prefetch configured in `events`.
+    async fn queue_consumer(&self, queue: InboundQueue) -> Result<ConsumerHandle> {
+        let queue_name = queue.queue_name();
         let chan = self.create_channel().await?;
 
+        chan.basic_qos(BasicQosArguments::new(
+            0,
+            queue.consumer_config().prefetch,
+            false,
+        ))
+        .await
+        .map_err(Error::ChannelError)?;
+
         let args = QueueDeclareArguments::default()
-            .queue(CONFIG.ampq.registrar_queue_name.clone())
+            .queue(queue_name.to_string())
             .finish();
 
-        debug!("Declaring registrar queue with args: {:?}", args);
+        debug!("Declaring queue with args: {:?}", args);
 
         match chan.queue_declare(args).await {
             Ok(Some(v)) => {
                 let (queue_name, _m, _) = v;
-                debug!("Registrar queue declared successfully: {}", queue_name);
+                debug!("Queue declared successfully: {}", queue_name);
 
                 let args = BasicConsumeArguments::new(&queue_name, "");
                 debug!("Starting basic consume with args: {:?}", args);
@@ -141,11 +257,11 @@ impl MessageBroker {
                 let (consumer_tag, messages_rx) = chan
                     .basic_consume_rx(args)
                     .await
-                    .map_err(|e| Error::ConsumerDeclareError(e))?;
+                    .map_err(Error::ConsumerDeclareError)?;
 
                 debug!(
-                    "Registrar consumer created successfully with tag: {}",
-                    consumer_tag
+                    "Consumer for `{}` created successfully with tag: {}",
+                    queue_name, consumer_tag
                 );
 
                 Ok(ConsumerHandle {
@@ -155,148 +271,145 @@ impl MessageBroker {
                 })
             }
             Ok(None) => {
-                debug!("Failed to declare registrar queue");
+                debug!("Failed to declare queue `{}`", queue_name);
                 Err(Error::FailedToDeclareQueue)
             }
             Err(err) => {
-                debug!("Error declaring registrar queue: {}", err);
+                debug!("Error declaring queue `{}`: {}", queue_name, err);
                 Err(Error::QueueDeclareError(err))
             }
         }
     }
 
-    pub async fn transmit_action(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
-        debug!("Starting action transmission with payload: {}", payload);
-
-        let chan = self.create_channel().await?;
+    /// Publishes a fire-and-forget message to the gateway queue.
+    ///
+    /// `message_type` is set as the AMQP `type` property so the gateway can
+    /// tell these apart from action requests, which always expect a reply.
+    async fn publish_to_gateway(
+        &self,
+        message_type: &str,
+        payload: serde_json::Value,
+    ) -> Result<()> {
+        debug!("Publishing `{}` message to gateway: {}", message_type, payload);
+
+        let connection = self.connection()?;
+
+        let envelope = Envelope::new(message_type);
+        let payload_bytes = envelope.encode(&payload)?;
+        let props = envelope::properties(&envelope);
+
+        self.channels
+            .publish(
+                &connection,
+                "",
+                &CONFIG.ampq.mqtt_gateway_queue_name,
+                props,
+                payload_bytes,
+            )
+            .await
+    }
 
-        let args = QueueDeclareArguments::default()
-            .exclusive(true)
-            .auto_delete(true)
-            .finish();
+    /// Sends an action and waits for its reply, retrying failures that happened
+    /// before the action reached the broker as configured in `ampq.retry`.
+    ///
+    /// Every attempt expires at `deadline` at the latest, a retry that would
+    /// start after it is not made.
+    async fn transmit_action(
+        &self,
+        route: &ActionRoute,
+        payload: serde_json::Value,
+        deadline: chrono::DateTime<chrono::Utc>,
+    ) -> Result<serde_json::Value> {
+        let retry = &CONFIG.ampq.retry;
+        let mut backoff = retry.initial_backoff_ms;
+        let mut attempt = 1;
+
+        loop {
+            match self
+                .transmit_action_once(route, payload.clone(), deadline)
+                .await
+            {
+                Err(err)
+                    if err.is_retryable()
+                        && attempt < retry.max_attempts
+                        && chrono::Utc::now() + Duration::from_millis(backoff) < deadline =>
+                {
+                    warn!(
+                        "Action transmission attempt {} of {} failed, retrying in {} ms: {}",
+                        attempt, retry.max_attempts, backoff, err
+                    );
+
+                    tokio::time::sleep(std::time::Duration::from_millis(backoff)).await;
+                    backoff = (backoff * 2).min(retry.max_backoff_ms);
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
 
-        debug!("Declaring response queue with args: {:?}", args);
+    /// Publishes an action to the action exchange with the shared reply queue
+    /// as `reply_to` and waits for the reply carrying its correlation id.
+    async fn transmit_action_once(
+        &self,
+        route: &ActionRoute,
+        payload: serde_json::Value,
+        deadline: chrono::DateTime<chrono::Utc>,
+    ) -> Result<serde_json::Value> {
+        debug!("Starting action transmission with payload: {}", payload);
 
-        let (response_queue_name, _m, _) = match chan.queue_declare(args).await {
-            Ok(Some(v)) => {
-                debug!("Response queue declared successfully: {}", v.0);
-                v
-            }
-            Ok(None) => {
-                debug!("Failed to declare response queue");
-                return Err(Error::FailedToDeclareQueue);
-            }
-            Err(err) => {
-                debug!("Error declaring response queue: {}", err);
-                return Err(Error::QueueDeclareError(err));
-            }
-        };
+        let connection = self.connection()?;
+        let reply_to = self.replies.queue_name(&connection).await?;
 
         let correlation_id = uuid::Uuid::new_v4().to_string();
         debug!("Generated correlation ID: {}", correlation_id);
 
-        let (tx, rx) = tokio::sync::oneshot::channel::<serde_json::Value>();
-        let (cancel_tx, cancel_rx) = tokio::sync::oneshot::channel::<()>();
+        // Nobody waits for the reply after the caller's deadline or
+        // `ampq.timeout`, the action expires then so it is not run late
+        let expires_at =
+            deadline.min(chrono::Utc::now() + Duration::from_secs(CONFIG.ampq.timeout));
+        let timeout = (expires_at - chrono::Utc::now()).to_std().unwrap_or_default();
 
-        let args = BasicConsumeArguments::new(&response_queue_name, "");
-        debug!("Starting basic consume with args: {:?}", args);
-
-        let (consumer_tag, mut messages_rx) = chan
-            .basic_consume_rx(args)
-            .await
-            .map_err(|e| Error::ConsumerDeclareError(e))?;
-
-        debug!("Consumer started with tag: {}", consumer_tag);
-
-        tokio::spawn(async move {
-            debug!("Spawned task to listen for messages");
-            tokio::select! {
-                _ = cancel_rx => {
-                    debug!("Cancelled consumer task");
-                    return
-                }
-                Some(msg) = messages_rx.recv() => {
-                    if let Some(content) = msg.content {
-                        match serde_json::from_slice::<serde_json::Value>(&content) {
-                            Ok(json) => {
-                                debug!("Message content deserialized successfully");
-                                let _ = tx.send(json);
-                            }
-                            Err(err) => {
-                                debug!("Failed to deserialize message content: {}", err);
-                            }
-                        }
-                    }
-                }
-            }
-        });
-
-        let props = BasicProperties::default()
+        let envelope = Envelope::new(ACTION_MESSAGE_TYPE)
             .with_correlation_id(&correlation_id)
-            .with_reply_to(&response_queue_name)
-            .finish();
+            .with_expires_at(expires_at);
+        let payload_bytes = envelope.encode(&payload)?;
 
-        debug!("Publishing message with properties: {:?}", props);
+        let rx = self.replies.register(correlation_id.clone(), timeout);
 
-        let args = BasicPublishArguments::default()
-            .routing_key(CONFIG.ampq.mqtt_gateway_queue_name.clone())
+        let props = envelope::properties(&envelope)
+            .with_reply_to(&reply_to)
             .finish();
 
-        let payload_bytes = serde_json::to_vec(&payload).map_err(Error::SerdeError)?;
+        debug!("Publishing message with properties: {:?}", props);
 
-        chan.basic_publish(props, payload_bytes, args)
+        if let Err(err) = self
+            .channels
+            .publish(
+                &connection,
+                &CONFIG.ampq.action_exchange,
+                &routing::action_routing_key(route),
+                props,
+                payload_bytes,
+            )
             .await
-            .map_err(|e| Error::PublishError(e))?;
-
-        debug!("Message published, waiting for response");
-
-        let response =
-            match tokio::time::timeout(std::time::Duration::from_secs(CONFIG.ampq.timeout), rx)
-                .await
-            {
-                Ok(v) => match v {
-                    Ok(v) => {
-                        debug!("Response received successfully");
-                        v
-                    }
-                    Err(_) => {
-                        debug!("Failed to receive response");
-                        self.close_consumer(&chan, consumer_tag).await?;
-                        return Err(Error::ResponseTimeout);
-                    }
-                },
-                Err(_) => {
-                    debug!("Response timed out");
-                    self.close_consumer(&chan, consumer_tag).await?;
-                    return Err(Error::ResponseTimeout);
-                }
-            };
-
-        debug!("Cancelling response consumer");
-        let _ = cancel_tx.send(());
-
-        Ok(response)
-    }
-
-    async fn close_consumer<S>(
-        &self,
-        chan: &amqprs::channel::Channel,
-        consumer_tag: S,
-    ) -> Result<()>
-    where
-        S: Into<String>,
-    {
-        let args = BasicCancelArguments::default()
-            .consumer_tag(consumer_tag.into())
-            .finish();
-
-        debug!("Closing consumer with args: {:?}", args);
+        {
+            self.replies.forget(&correlation_id);
+            return Err(err);
+        }
 
-        chan.basic_cancel(args)
-            .await
-            .map_err(|e| Error::ChannelError(e))?;
+        debug!("Message confirmed by broker, waiting for response");
 
-        debug!("Consumer closed successfully");
-        Ok(())
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(response)) => {
+                debug!("Response received successfully");
+                response
+            }
+            _ => {
+                debug!("Response timed out");
+                self.replies.forget(&correlation_id);
+                Err(Error::ResponseTimeout)
+            }
+        }
     }
 }
@@ -0,0 +1,120 @@
+use super::confirm::{ConfirmCallback, ConfirmTracker};
+use super::error::{Error, Result};
+use crate::config::CONFIG;
+use amqprs::channel::{BasicPublishArguments, Channel, ConfirmSelectArguments};
+use amqprs::connection::Connection;
+use amqprs::BasicProperties;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Channel in confirm mode together with the tracker of its confirmations
+struct PooledChannel {
+    chan: Channel,
+    confirms: Arc<ConfirmTracker>,
+}
+
+/// Keeps open channels around so publishing does not open one per message
+pub(super) struct ChannelPool {
+    idle: Mutex<Vec<PooledChannel>>,
+    size: usize,
+}
+
+impl ChannelPool {
+    /// At most `size` idle channels are kept, any further channel is closed
+    /// when released.
+    pub(super) fn new(size: usize) -> Self {
+        Self {
+            idle: Mutex::new(Vec::with_capacity(size)),
+            size,
+        }
+    }
+
+    /// Publishes a mandatory message and waits until the broker confirmed it.
+    ///
+    /// Messages no queue is bound for fail with `Error::NotRouted`, messages
+    /// the broker refused with `Error::Nacked`.
+    pub(super) async fn publish(
+        &self,
+        connection: &Connection,
+        exchange: &str,
+        routing_key: &str,
+        props: BasicProperties,
+        payload: Vec<u8>,
+    ) -> Result<()> {
+        let pooled = self.acquire(connection).await?;
+
+        let args = BasicPublishArguments::new(exchange, routing_key)
+            .mandatory(true)
+            .finish();
+
+        let confirm = pooled.confirms.expect();
+
+        if let Err(err) = pooled.chan.basic_publish(props, payload, args).await {
+            return Err(Error::PublishError(err));
+        }
+
+        let timeout = Duration::from_secs(CONFIG.ampq.confirm_timeout);
+
+        let confirm = match tokio::time::timeout(timeout, confirm).await {
+            Ok(Ok(confirm)) => confirm,
+            // The channel is dropped as later confirmations could not be matched
+            _ => return Err(Error::Unconfirmed),
+        };
+
+        self.release(pooled);
+
+        match (confirm.acked, confirm.returned) {
+            (_, Some(reason)) => Err(Error::NotRouted(reason)),
+            (true, None) => Ok(()),
+            (false, None) => Err(Error::Nacked),
+        }
+    }
+
+    async fn acquire(&self, connection: &Connection) -> Result<PooledChannel> {
+        loop {
+            let pooled = self.lock_idle().pop();
+
+            match pooled {
+                Some(pooled) if pooled.chan.is_open() => return Ok(pooled),
+                // Closed by the broker while idle
+                Some(_) => continue,
+                None => break,
+            }
+        }
+
+        let chan = connection
+            .open_channel(None)
+            .await
+            .map_err(Error::CreateChannelError)?;
+
+        let confirms = Arc::new(ConfirmTracker::default());
+
+        chan.register_callback(ConfirmCallback {
+            tracker: confirms.clone(),
+        })
+        .await
+        .map_err(Error::ChannelError)?;
+
+        chan.confirm_select(ConfirmSelectArguments::default())
+            .await
+            .map_err(Error::ChannelError)?;
+
+        Ok(PooledChannel { chan, confirms })
+    }
+
+    /// Returns a channel that was used without error.
+    fn release(&self, pooled: PooledChannel) {
+        if !pooled.chan.is_open() {
+            return;
+        }
+
+        let mut idle = self.lock_idle();
+        if idle.len() < self.size {
+            idle.push(pooled);
+        }
+    }
+
+    fn lock_idle(&self) -> std::sync::MutexGuard<'_, Vec<PooledChannel>> {
+        self.idle.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,170 @@
+use super::envelope;
+use super::error::{Error, Result};
+use amqprs::channel::{BasicConsumeArguments, Channel, ConsumerMessage, QueueDeclareArguments};
+use amqprs::connection::Connection;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot};
+use tracing::debug;
+
+/// Interval at which requests nobody waits for anymore are forgotten
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(30);
+
+struct PendingReply {
+    tx: oneshot::Sender<Result<serde_json::Value>>,
+    expires_at: Instant,
+}
+
+struct ReplyQueue {
+    name: String,
+    chan: Channel,
+}
+
+/// Routes the replies of the process wide reply queue to the waiting requests
+/// by correlation id.
+pub(super) struct ReplyRouter {
+    pending: Mutex<HashMap<String, PendingReply>>,
+    queue: tokio::sync::Mutex<Option<ReplyQueue>>,
+}
+
+impl ReplyRouter {
+    pub(super) fn new() -> Arc<Self> {
+        Arc::new(Self {
+            pending: Mutex::new(HashMap::new()),
+            queue: tokio::sync::Mutex::new(None),
+        })
+    }
+
+    /// Name of the reply queue, declared with its consumer on first use and
+    /// again once its channel was closed.
+    pub(super) async fn queue_name(self: &Arc<Self>, connection: &Connection) -> Result<String> {
+        let mut queue = self.queue.lock().await;
+
+        if let Some(queue) = queue.as_ref().filter(|q| q.chan.is_open()) {
+            return Ok(queue.name.clone());
+        }
+
+        let chan = connection
+            .open_channel(None)
+            .await
+            .map_err(Error::CreateChannelError)?;
+
+        let args = QueueDeclareArguments::default()
+            .exclusive(true)
+            .auto_delete(true)
+            .finish();
+
+        let (name, _, _) = chan
+            .queue_declare(args)
+            .await
+            .map_err(Error::QueueDeclareError)?
+            .ok_or(Error::FailedToDeclareQueue)?;
+
+        let args = BasicConsumeArguments::new(&name, "")
+            .manual_ack(false)
+            .finish();
+
+        let (consumer_tag, messages_rx) = chan
+            .basic_consume_rx(args)
+            .await
+            .map_err(Error::ConsumerDeclareError)?;
+
+        debug!("Reply queue `{}` consumed with tag: {}", name, consumer_tag);
+
+        tokio::spawn(self.clone().listen(messages_rx));
+
+        *queue = Some(ReplyQueue {
+            name: name.clone(),
+            chan,
+        });
+
+        Ok(name)
+    }
+
+    /// Registers a request, the receiver resolves with the reply carrying
+    /// `correlation_id`.
+    pub(super) fn register(
+        &self,
+        correlation_id: String,
+        timeout: Duration,
+    ) -> oneshot::Receiver<Result<serde_json::Value>> {
+        let (tx, rx) = oneshot::channel();
+
+        self.lock_pending().insert(
+            correlation_id,
+            PendingReply {
+                tx,
+                expires_at: Instant::now() + timeout,
+            },
+        );
+
+        rx
+    }
+
+    /// Stops waiting for the reply of a request that failed or timed out.
+    pub(super) fn forget(&self, correlation_id: &str) {
+        self.lock_pending().remove(correlation_id);
+    }
+
+    async fn listen(self: Arc<Self>, mut messages_rx: mpsc::UnboundedReceiver<ConsumerMessage>) {
+        let mut cleanup = tokio::time::interval(CLEANUP_INTERVAL);
+
+        loop {
+            tokio::select! {
+                msg = messages_rx.recv() => match msg {
+                    Some(msg) => self.dispatch(msg),
+                    None => {
+                        debug!("Reply consumer stopped");
+                        return;
+                    }
+                },
+                _ = cleanup.tick() => self.purge_expired(),
+            }
+        }
+    }
+
+    fn dispatch(&self, msg: ConsumerMessage) {
+        let Some(correlation_id) = msg
+            .basic_properties
+            .as_ref()
+            .and_then(|props| props.correlation_id())
+        else {
+            debug!("Dropping reply without correlation id");
+            return;
+        };
+
+        let Some(pending) = self.lock_pending().remove(correlation_id) else {
+            debug!("Dropping late reply for correlation id: {}", correlation_id);
+            return;
+        };
+
+        let Some(content) = msg.content.as_deref() else {
+            debug!("Dropping reply without content");
+            return;
+        };
+
+        // Replies of an unknown version or encoding fail the request right
+        // away instead of letting it time out
+        let props = msg.basic_properties.as_ref().cloned().unwrap_or_default();
+        let reply = envelope::envelope(&props).decode::<serde_json::Value>(content);
+
+        if let Err(err) = &reply {
+            debug!("Failed to decode reply content: {}", err);
+        }
+
+        let _ = pending.tx.send(reply);
+    }
+
+    /// Forgets requests whose caller stopped waiting without cleaning up.
+    fn purge_expired(&self) {
+        let now = Instant::now();
+
+        self.lock_pending()
+            .retain(|_, pending| pending.expires_at > now && !pending.tx.is_closed());
+    }
+
+    fn lock_pending(&self) -> std::sync::MutexGuard<'_, HashMap<String, PendingReply>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,88 @@
+use super::error::{Error, Result};
+use crate::config::CONFIG;
+use crate::model::bus::ActionRoute;
+use amqprs::channel::{ExchangeDeclareArguments, ExchangeType};
+use amqprs::connection::Connection;
+use tracing::debug;
+
+/// Declares the topic exchange actions are published to.
+pub(super) async fn declare_action_exchange(connection: &Connection) -> Result<()> {
+    let chan = connection
+        .open_channel(None)
+        .await
+        .map_err(Error::CreateChannelError)?;
+
+    chan.exchange_declare(
+        ExchangeDeclareArguments::of_type(&CONFIG.ampq.action_exchange, ExchangeType::Topic)
+            .durable(true)
+            .finish(),
+    )
+    .await
+    .map_err(Error::ChannelError)?;
+
+    debug!("Action exchange `{}` declared", CONFIG.ampq.action_exchange);
+
+    let _ = chan.close().await;
+
+    Ok(())
+}
+
+/// Renders `ampq.action_routing_key` for an action.
+///
+/// Dots and wildcards within the values are replaced so every value stays a
+/// single word of the routing key.
+pub(super) fn action_routing_key(route: &ActionRoute) -> String {
+    render_routing_key(&CONFIG.ampq.action_routing_key, route)
+}
+
+fn render_routing_key(template: &str, route: &ActionRoute) -> String {
+    let word = |value: &str| value.replace(['.', '*', '#'], "_");
+
+    template
+        .replace("{cluster_id}", &word(&route.cluster_id))
+        .replace("{microdevice_id}", &route.microdevice_id.to_string())
+        .replace("{action}", &word(&route.action))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(cluster_id: &str, action: &str) -> ActionRoute {
+        ActionRoute {
+            cluster_id: cluster_id.to_string(),
+            microdevice_id: 7,
+            action: action.to_string(),
+        }
+    }
+
+    #[test]
+    fn default_template_routes_by_cluster_device_and_action() {
+        let key = render_routing_key(
+            "cluster.{cluster_id}.device.{microdevice_id}.{action}",
+            &route("5f0c2d56-6b7b-4b43-9a7c-0f3f1d0bd7e1", "power-on"),
+        );
+
+        assert_eq!(
+            key,
+            "cluster.5f0c2d56-6b7b-4b43-9a7c-0f3f1d0bd7e1.device.7.power-on"
+        );
+    }
+
+    #[test]
+    fn values_stay_single_words() {
+        let key = render_routing_key(
+            "{cluster_id}.{microdevice_id}.{action}",
+            &route("a.b", "set.*.#"),
+        );
+
+        assert_eq!(key, "a_b.7.set____");
+    }
+
+    #[test]
+    fn templates_may_leave_out_placeholders() {
+        let key = render_routing_key("actions.{action}", &route("c1", "restart"));
+
+        assert_eq!(key, "actions.restart");
+    }
+}
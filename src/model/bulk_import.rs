@@ -0,0 +1,345 @@
+use super::cluster::ClusterBaseModelController as ClusterBMC;
+use super::common::parse_cluster_id;
+use super::error::{Error, ErrorKind, Result};
+use super::microdevice::{
+    validate_labels, MicrodeviceCreate, MicrodeviceRecord, MicrodeviceTopic,
+};
+use super::topic::validate_topics;
+use super::ModelManager;
+use crate::context::Ctx;
+use entity::microdevice;
+use sea_orm::{entity::prelude::*, QuerySelect, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// Maximum number of rows accepted in a single import
+const MAX_IMPORT_ROWS: usize = 5000;
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkImportMode {
+    /// Nothing is inserted unless every row is valid
+    #[default]
+    Atomic,
+    /// Valid rows are inserted, invalid rows are reported
+    BestEffort,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum BulkImportFormat {
+    Json,
+    Csv,
+}
+
+#[derive(Deserialize, utoipa::ToSchema, Debug)]
+pub struct BulkImportParams {
+    pub mode: Option<BulkImportMode>,
+}
+
+#[derive(Serialize, utoipa::ToSchema, Debug)]
+pub struct BulkImportRowError {
+    /// 1-based index of the row in the uploaded document
+    #[schema(example = 3)]
+    row: usize,
+    #[schema(example = "sensor-3")]
+    name: Option<String>,
+    #[schema(example = "a microdevice named `sensor-3` already exists")]
+    message: String,
+}
+
+#[derive(Serialize, utoipa::ToSchema, Debug)]
+pub struct BulkImportReport {
+    mode: BulkImportMode,
+    total: usize,
+    created: Vec<MicrodeviceRecord>,
+    errors: Vec<BulkImportRowError>,
+}
+
+impl BulkImportReport {
+    /// Whether the import was rejected as a whole
+    pub fn is_rejected(&self) -> bool {
+        self.mode == BulkImportMode::Atomic && !self.errors.is_empty()
+    }
+}
+
+/// A row of the uploaded document, either parsed or with the reason it could not be
+pub struct BulkImportRow {
+    row: usize,
+    parsed: std::result::Result<MicrodeviceCreate, String>,
+}
+
+#[derive(Deserialize)]
+struct CsvRow {
+    name: String,
+    description: Option<String>,
+    topics: Option<String>,
+}
+
+impl BulkImportFormat {
+    pub fn from_content_type(content_type: Option<&str>) -> Option<Self> {
+        let mime = content_type
+            .and_then(|v| v.split(';').next())
+            .map(|v| v.trim().to_ascii_lowercase());
+
+        match mime.as_deref() {
+            None | Some("application/json") => Some(Self::Json),
+            Some("text/csv") => Some(Self::Csv),
+            _ => None,
+        }
+    }
+}
+
+pub struct BulkImportBaseModelController {}
+
+impl BulkImportBaseModelController {
+    /// Splits the uploaded document into rows.
+    ///
+    /// Only a malformed document as a whole is an error, rows that cannot be
+    /// parsed are kept so they can be reported individually.
+    pub fn parse_rows(format: BulkImportFormat, body: &[u8]) -> Result<Vec<BulkImportRow>> {
+        let rows = match format {
+            BulkImportFormat::Json => Self::parse_json(body)?,
+            BulkImportFormat::Csv => Self::parse_csv(body)?,
+        };
+
+        if rows.len() > MAX_IMPORT_ROWS {
+            return Err(Error {
+                kind: ErrorKind::InvalidBulkImport,
+                message: format!("at most {} rows can be imported at once", MAX_IMPORT_ROWS),
+            });
+        }
+
+        Ok(rows)
+    }
+
+    pub async fn import(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        rows: Vec<BulkImportRow>,
+        mode: BulkImportMode,
+    ) -> Result<BulkImportReport> {
+        ClusterBMC::exists(mm, ctx, cluster_uuid.clone()).await?;
+        let cluster_id = parse_cluster_id(&cluster_uuid)?;
+
+        let existing: HashSet<String> = microdevice::Entity::find()
+            .select_only()
+            .column(microdevice::Column::Name)
+            .filter(microdevice::Column::ClusterId.eq(cluster_id))
+            .into_tuple::<String>()
+            .all(&mm.db)
+            .await?
+            .into_iter()
+            .collect();
+
+        let total = rows.len();
+        let (valid, mut errors) = Self::validate_rows(rows, existing);
+
+        let mut report = BulkImportReport {
+            mode,
+            total,
+            created: vec![],
+            errors: vec![],
+        };
+
+        match mode {
+            BulkImportMode::Atomic => {
+                if errors.is_empty() {
+                    let txn = mm.db.begin().await?;
+                    let mut created = Vec::with_capacity(valid.len());
+
+                    for (row, create) in valid {
+                        let name = create.name.clone();
+                        match create.into_active_model(cluster_id)?.insert(&txn).await {
+                            Ok(model) => created.push(model.into()),
+                            Err(e) => {
+                                // Dropping the transaction rolls back the rows inserted so far
+                                errors.push(BulkImportRowError {
+                                    row,
+                                    name: Some(name),
+                                    message: e.to_string(),
+                                });
+                                break;
+                            }
+                        }
+                    }
+
+                    if errors.is_empty() {
+                        txn.commit().await?;
+                        report.created = created;
+                    }
+                }
+            }
+            BulkImportMode::BestEffort => {
+                for (row, create) in valid {
+                    let name = create.name.clone();
+                    match create.into_active_model(cluster_id)?.insert(&mm.db).await {
+                        Ok(model) => report.created.push(model.into()),
+                        Err(e) => errors.push(BulkImportRowError {
+                            row,
+                            name: Some(name),
+                            message: e.to_string(),
+                        }),
+                    }
+                }
+            }
+        }
+
+        errors.sort_by_key(|e| e.row);
+        report.errors = errors;
+
+        Ok(report)
+    }
+
+    fn validate_rows(
+        rows: Vec<BulkImportRow>,
+        mut taken_names: HashSet<String>,
+    ) -> (Vec<(usize, MicrodeviceCreate)>, Vec<BulkImportRowError>) {
+        let mut valid = vec![];
+        let mut errors = vec![];
+
+        for BulkImportRow { row, parsed } in rows {
+            let mut create = match parsed {
+                Ok(create) => create,
+                Err(message) => {
+                    errors.push(BulkImportRowError {
+                        row,
+                        name: None,
+                        message,
+                    });
+                    continue;
+                }
+            };
+
+            create.name = create.name.trim().to_string();
+
+            let error = |message: String| BulkImportRowError {
+                row,
+                name: Some(create.name.clone()),
+                message,
+            };
+
+            if create.name.is_empty() {
+                errors.push(error("name must not be empty".to_string()));
+                continue;
+            }
+
+            if let Some(topics) = &create.topics {
+                if let Err(e) = validate_topics(topics) {
+                    errors.push(error(e.message));
+                    continue;
+                }
+            }
+
+            if let Some(labels) = &create.labels {
+                if let Err(e) = validate_labels(labels) {
+                    errors.push(error(e.message));
+                    continue;
+                }
+            }
+
+            if !taken_names.insert(create.name.clone()) {
+                errors.push(error(format!(
+                    "a microdevice named `{}` already exists",
+                    create.name
+                )));
+                continue;
+            }
+
+            valid.push((row, create));
+        }
+
+        (valid, errors)
+    }
+
+    fn parse_json(body: &[u8]) -> Result<Vec<BulkImportRow>> {
+        let values: Vec<Value> = serde_json::from_slice(body).map_err(|e| Error {
+            kind: ErrorKind::InvalidBulkImport,
+            message: format!("expected a JSON array of microdevices: {}", e),
+        })?;
+
+        Ok(values
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| BulkImportRow {
+                row: i + 1,
+                parsed: serde_json::from_value(value).map_err(|e| e.to_string()),
+            })
+            .collect())
+    }
+
+    /// Parses a CSV document with a `name` column and optional `description`
+    /// and `topics` columns.
+    fn parse_csv(body: &[u8]) -> Result<Vec<BulkImportRow>> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(body);
+
+        let headers = reader.headers().map_err(|e| Error {
+            kind: ErrorKind::InvalidBulkImport,
+            message: format!("failed to read CSV header: {}", e),
+        })?;
+
+        if !headers.iter().any(|h| h == "name") {
+            return Err(Error {
+                kind: ErrorKind::InvalidBulkImport,
+                message: "CSV header must contain a `name` column".to_string(),
+            });
+        }
+
+        Ok(reader
+            .deserialize::<CsvRow>()
+            .enumerate()
+            .map(|(i, record)| BulkImportRow {
+                row: i + 1,
+                parsed: record.map_err(|e| e.to_string()).and_then(|record| {
+                    Ok(MicrodeviceCreate {
+                        name: record.name,
+                        description: record.description,
+                        topics: match record.topics {
+                            Some(topics) => Some(parse_csv_topics(&topics)?),
+                            None => None,
+                        },
+                        labels: None,
+                    })
+                }),
+            })
+            .collect())
+    }
+}
+
+/// Parses the `topics` cell of a CSV row.
+///
+/// The cell either holds a JSON array of topics or `;` separated entries in the
+/// form `topic|qos|name`, where `qos` defaults to 0 and `name` to the topic.
+fn parse_csv_topics(cell: &str) -> std::result::Result<Vec<MicrodeviceTopic>, String> {
+    let cell = cell.trim();
+
+    if cell.starts_with('[') {
+        return serde_json::from_str(cell).map_err(|e| format!("invalid topics: {}", e));
+    }
+
+    cell.split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let mut parts = entry.splitn(3, '|').map(str::trim);
+            let topic = parts.next().unwrap_or_default().to_string();
+
+            let qos = match parts.next() {
+                None | Some("") => 0,
+                Some(qos) => qos
+                    .parse::<u8>()
+                    .map_err(|_| format!("invalid qos `{}` for topic `{}`", qos, topic))?,
+            };
+
+            let name = match parts.next() {
+                Some(name) if !name.is_empty() => name.to_string(),
+                _ => topic.clone(),
+            };
+
+            Ok(MicrodeviceTopic { topic, qos, name })
+        })
+        .collect()
+}
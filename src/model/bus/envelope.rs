@@ -0,0 +1,229 @@
+use super::{Error, Result};
+use crate::config::CONFIG;
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Version of the messages exchanged with the gateways
+pub const SCHEMA_VERSION: u16 = 1;
+
+/// Encoding of a message body, told by its content type
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    #[default]
+    Json,
+    Cbor,
+    #[serde(rename = "msgpack")]
+    MessagePack,
+}
+
+impl Encoding {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Cbor => "application/cbor",
+            Self::MessagePack => "application/msgpack",
+        }
+    }
+
+    /// Encoding of `content_type`, parameters such as `charset` are ignored.
+    pub fn from_content_type(content_type: &str) -> Result<Self> {
+        let essence = content_type.split(';').next().unwrap_or_default().trim();
+
+        match essence.to_ascii_lowercase().as_str() {
+            "application/json" => Ok(Self::Json),
+            "application/cbor" => Ok(Self::Cbor),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Ok(Self::MessagePack)
+            }
+            _ => Err(Error::UnsupportedContentType(content_type.to_string())),
+        }
+    }
+
+    pub fn encode<T: Serialize>(self, body: &T) -> Result<Vec<u8>> {
+        match self {
+            Self::Json => serde_json::to_vec(body).map_err(Error::SerdeError),
+            Self::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(body, &mut bytes)
+                    .map_err(|err| Error::EncodingFailed(err.to_string()))?;
+                Ok(bytes)
+            }
+            Self::MessagePack => {
+                rmp_serde::to_vec_named(body).map_err(|err| Error::EncodingFailed(err.to_string()))
+            }
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T> {
+        match self {
+            Self::Json => serde_json::from_slice(bytes).map_err(Error::SerdeError),
+            Self::Cbor => {
+                ciborium::from_reader(bytes).map_err(|err| Error::DecodingFailed(err.to_string()))
+            }
+            Self::MessagePack => {
+                rmp_serde::from_slice(bytes).map_err(|err| Error::DecodingFailed(err.to_string()))
+            }
+        }
+    }
+}
+
+/// Metadata sent along every message body
+///
+/// The AMQP bus carries it in the message properties and headers, so the body
+/// stays the plain message. Messages of gateways predating the envelope have
+/// no version nor content type, they are read as version 1 JSON.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Envelope {
+    /// Missing for messages sent before versions were introduced
+    pub schema_version: Option<u16>,
+    /// e.g. `action` or `registration`
+    pub message_type: Option<String>,
+    /// Missing for messages sent before content types were introduced
+    pub content_type: Option<String>,
+    pub sent_at: Option<DateTime<Utc>>,
+    /// After which the message is not worth handling anymore
+    pub expires_at: Option<DateTime<Utc>>,
+    pub correlation_id: Option<String>,
+}
+
+impl Envelope {
+    /// Envelope of a message sent now, encoded as set in `message_bus.encoding`.
+    pub fn new(message_type: &str) -> Self {
+        Self {
+            schema_version: Some(SCHEMA_VERSION),
+            message_type: Some(message_type.to_string()),
+            content_type: Some(CONFIG.message_bus.encoding.content_type().to_string()),
+            sent_at: Some(Utc::now()),
+            expires_at: None,
+            correlation_id: None,
+        }
+    }
+
+    pub fn with_correlation_id(mut self, correlation_id: &str) -> Self {
+        self.correlation_id = Some(correlation_id.to_string());
+        self
+    }
+
+    pub fn with_expires_at(mut self, expires_at: DateTime<Utc>) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// Envelope of the answer to this message, in the same encoding when it
+    /// is supported.
+    pub fn reply(&self) -> Self {
+        let encoding = self.encoding().unwrap_or_default();
+
+        Self {
+            schema_version: Some(SCHEMA_VERSION),
+            message_type: self.message_type.as_ref().map(|t| format!("{}.reply", t)),
+            content_type: Some(encoding.content_type().to_string()),
+            sent_at: Some(Utc::now()),
+            expires_at: None,
+            correlation_id: self.correlation_id.clone(),
+        }
+    }
+
+    pub fn encoding(&self) -> Result<Encoding> {
+        self.content_type
+            .as_deref()
+            .map_or(Ok(Encoding::Json), Encoding::from_content_type)
+    }
+
+    /// Fails with `UnsupportedSchemaVersion` unless the message is of a version
+    /// this process understands.
+    pub fn check_version(&self) -> Result<()> {
+        match self.schema_version.unwrap_or(SCHEMA_VERSION) {
+            SCHEMA_VERSION => Ok(()),
+            version => Err(Error::UnsupportedSchemaVersion(version)),
+        }
+    }
+
+    pub fn encode<T: Serialize>(&self, body: &T) -> Result<Vec<u8>> {
+        self.encoding()?.encode(body)
+    }
+
+    /// Decodes a body after checking its version and content type.
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T> {
+        self.check_version()?;
+        self.encoding()?.decode(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn envelope(schema_version: Option<u16>, content_type: Option<&str>) -> Envelope {
+        Envelope {
+            schema_version,
+            content_type: content_type.map(str::to_string),
+            ..Envelope::default()
+        }
+    }
+
+    #[test]
+    fn bodies_round_trip_in_every_encoding() {
+        let body = json!({ "device_id": "d1", "data": { "temperature": 21.5, "ok": true } });
+
+        for encoding in [Encoding::Json, Encoding::Cbor, Encoding::MessagePack] {
+            let envelope = envelope(Some(SCHEMA_VERSION), Some(encoding.content_type()));
+            let bytes = envelope.encode(&body).unwrap();
+
+            assert_eq!(envelope.decode::<serde_json::Value>(&bytes).unwrap(), body);
+        }
+    }
+
+    #[test]
+    fn legacy_messages_are_read_as_json() {
+        let body = envelope(None, None)
+            .decode::<serde_json::Value>(br#"{"device_id":"d1"}"#)
+            .unwrap();
+
+        assert_eq!(body, json!({ "device_id": "d1" }));
+    }
+
+    #[test]
+    fn content_type_parameters_are_ignored() {
+        assert_eq!(
+            Encoding::from_content_type("Application/JSON; charset=utf-8").unwrap(),
+            Encoding::Json
+        );
+        assert_eq!(
+            Encoding::from_content_type("application/x-msgpack").unwrap(),
+            Encoding::MessagePack
+        );
+    }
+
+    #[test]
+    fn unknown_versions_and_content_types_are_rejected() {
+        let err = envelope(Some(SCHEMA_VERSION + 1), None)
+            .decode::<serde_json::Value>(b"{}")
+            .unwrap_err();
+        assert!(matches!(err, Error::UnsupportedSchemaVersion(v) if v == SCHEMA_VERSION + 1));
+        assert!(err.to_string().contains("supported version is 1"));
+
+        let err = envelope(Some(SCHEMA_VERSION), Some("text/xml"))
+            .decode::<serde_json::Value>(b"<a/>")
+            .unwrap_err();
+        assert!(matches!(err, Error::UnsupportedContentType(_)));
+    }
+
+    #[test]
+    fn replies_keep_the_encoding_and_correlation_id() {
+        let request = Envelope {
+            message_type: Some("registration".to_string()),
+            correlation_id: Some("c1".to_string()),
+            ..envelope(Some(SCHEMA_VERSION), Some("application/cbor"))
+        };
+
+        let reply = request.reply();
+
+        assert_eq!(reply.encoding().unwrap(), Encoding::Cbor);
+        assert_eq!(reply.correlation_id.as_deref(), Some("c1"));
+        assert_eq!(reply.message_type.as_deref(), Some("registration.reply"));
+    }
+}
@@ -0,0 +1,478 @@
+use super::{
+    ActionRoute, BrokerState, BrokerStatus, Consumer, DeadLetter, DeadLetterSource, Delivery,
+    DeliveryHandle, Envelope, Error, InboundQueue, MessageBus, Result,
+};
+use crate::config::{FakeDeviceError, FakeDeviceRule};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, watch};
+use tracing::debug;
+
+/// Answers actions in place of the microdevices
+///
+/// Rules are checked in order and the first one matching the action and the
+/// microdevice decides the answer. Actions no rule matches are echoed back
+/// with `status: ok`.
+#[derive(Clone, Default)]
+pub struct FakeDeviceResponder {
+    rules: Arc<Mutex<Vec<FakeDeviceRule>>>,
+}
+
+impl FakeDeviceResponder {
+    pub fn new(rules: Vec<FakeDeviceRule>) -> Self {
+        Self {
+            rules: Arc::new(Mutex::new(rules)),
+        }
+    }
+
+    /// Adds a rule taking precedence over the existing ones.
+    pub fn script(&self, rule: FakeDeviceRule) {
+        self.lock_rules().insert(0, rule);
+    }
+
+    async fn respond(&self, route: &ActionRoute, message: &Value) -> Result<Value> {
+        let rule = self
+            .lock_rules()
+            .iter()
+            .find(|rule| {
+                rule.action.as_deref().is_none_or(|a| a == route.action)
+                    && rule
+                        .microdevice_id
+                        .is_none_or(|id| id == route.microdevice_id)
+            })
+            .cloned();
+
+        let Some(rule) = rule else {
+            return Ok(json!({
+                "status": "ok",
+                "action": route.action,
+                "payload": message.get("payload"),
+            }));
+        };
+
+        if let Some(delay_ms) = rule.delay_ms {
+            tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+        }
+
+        match rule.error {
+            Some(FakeDeviceError::Timeout) => Err(Error::ResponseTimeout),
+            Some(FakeDeviceError::NotRouted) => Err(Error::NotRouted(format!(
+                "no fake device handles action `{}`",
+                route.action
+            ))),
+            Some(FakeDeviceError::Nacked) => Err(Error::Nacked),
+            None => Ok(rule.reply.unwrap_or_else(|| json!({ "status": "ok" }))),
+        }
+    }
+
+    fn lock_rules(&self) -> std::sync::MutexGuard<'_, Vec<FakeDeviceRule>> {
+        self.rules.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Dead-lettered message together with its content for replays
+struct StoredDeadLetter {
+    letter: DeadLetter,
+    content: Vec<u8>,
+}
+
+#[derive(Default)]
+struct Queues {
+    consumers: HashMap<InboundQueue, mpsc::UnboundedSender<Delivery>>,
+    dead_letters: HashMap<DeadLetterSource, VecDeque<StoredDeadLetter>>,
+}
+
+/// Message bus keeping every message within the process
+///
+/// Actions are answered by a `FakeDeviceResponder` and messages for the
+/// consumers are handed in with `inject`, so the API runs without a broker.
+#[derive(Clone)]
+pub struct InProcessBus {
+    state: Arc<watch::Sender<BrokerStatus>>,
+    responder: FakeDeviceResponder,
+    queues: Arc<Mutex<Queues>>,
+}
+
+impl InProcessBus {
+    pub fn new(responder: FakeDeviceResponder) -> Self {
+        let (state, _) = watch::channel(BrokerStatus {
+            state: BrokerState::Connected,
+            since: chrono::Utc::now(),
+        });
+
+        Self {
+            state: Arc::new(state),
+            responder,
+            queues: Arc::new(Mutex::new(Queues::default())),
+        }
+    }
+
+    /// Fake device answering the actions, rules can be scripted at runtime.
+    pub fn responder(&self) -> &FakeDeviceResponder {
+        &self.responder
+    }
+
+    /// Hands a message to the consumer of `queue` as a microdevice would,
+    /// the receiver gets the reply of the consumer if it sends one.
+    pub fn inject(
+        &self,
+        queue: InboundQueue,
+        content: Vec<u8>,
+    ) -> Result<oneshot::Receiver<Vec<u8>>> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+
+        let delivery = Delivery::new(
+            Some(content.clone()),
+            Some(format!("in-process.reply.{}", uuid::Uuid::new_v4())),
+            Envelope::default(),
+            Box::new(InProcessDelivery {
+                bus: self.clone(),
+                queue,
+                content,
+                reply: Mutex::new(Some(reply_tx)),
+            }),
+        );
+
+        let queues = self.lock_queues();
+
+        match queues.consumers.get(&queue) {
+            Some(consumer) if consumer.send(delivery).is_ok() => Ok(reply_rx),
+            _ => Err(Error::NotRouted(format!(
+                "no consumer for queue `{}`",
+                queue.queue_name()
+            ))),
+        }
+    }
+
+    fn lock_queues(&self) -> std::sync::MutexGuard<'_, Queues> {
+        self.queues.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl MessageBus for InProcessBus {
+    fn status(&self) -> BrokerStatus {
+        *self.state.borrow()
+    }
+
+    fn subscribe_state(&self) -> watch::Receiver<BrokerStatus> {
+        self.state.subscribe()
+    }
+
+    async fn transmit_action(
+        &self,
+        route: &ActionRoute,
+        payload: Value,
+        deadline: DateTime<Utc>,
+    ) -> Result<Value> {
+        debug!("Answering action with the fake device: {}", payload);
+
+        let timeout = (deadline - Utc::now()).to_std().unwrap_or_default();
+
+        tokio::time::timeout(timeout, self.responder.respond(route, &payload))
+            .await
+            .map_err(|_| Error::ResponseTimeout)?
+    }
+
+    async fn publish_event(&self, message_type: &str, payload: Value) -> Result<()> {
+        debug!(
+            "Dropping `{}` message without microdevices: {}",
+            message_type, payload
+        );
+        Ok(())
+    }
+
+    async fn consume(&self, queue: InboundQueue) -> Result<Consumer> {
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        // Replaces a previous consumer, like a restarted event manager would
+        self.lock_queues().consumers.insert(queue, tx);
+
+        Ok(Consumer { rx })
+    }
+
+    async fn dead_letters(
+        &self,
+        source: DeadLetterSource,
+        limit: usize,
+    ) -> Result<Vec<DeadLetter>> {
+        let queues = self.lock_queues();
+
+        Ok(queues
+            .dead_letters
+            .get(&source)
+            .map(|letters| {
+                letters
+                    .iter()
+                    .take(limit)
+                    .map(|stored| DeadLetter {
+                        source_queue: stored.letter.source_queue.clone(),
+                        failure_reason: stored.letter.failure_reason.clone(),
+                        failed_at: stored.letter.failed_at.clone(),
+                        content_type: stored.letter.content_type.clone(),
+                        payload: stored.letter.payload.clone(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn replay_dead_letters(&self, source: DeadLetterSource, limit: usize) -> Result<usize> {
+        let mut replayed = 0;
+
+        while replayed < limit {
+            let stored = self
+                .lock_queues()
+                .dead_letters
+                .get_mut(&source)
+                .and_then(VecDeque::pop_front);
+
+            let Some(stored) = stored else {
+                break;
+            };
+
+            if let Err(err) = self.inject(source.into(), stored.content.clone()) {
+                // Keeps the message for a later replay
+                self.lock_queues()
+                    .dead_letters
+                    .entry(source)
+                    .or_default()
+                    .push_front(stored);
+                return Err(err);
+            }
+
+            replayed += 1;
+        }
+
+        Ok(replayed)
+    }
+
+    async fn purge_dead_letters(&self, source: DeadLetterSource) -> Result<u32> {
+        let purged = self
+            .lock_queues()
+            .dead_letters
+            .remove(&source)
+            .map_or(0, |letters| letters.len());
+
+        Ok(purged as u32)
+    }
+}
+
+/// Delivery handed to a consumer of the in-process bus
+struct InProcessDelivery {
+    bus: InProcessBus,
+    queue: InboundQueue,
+    content: Vec<u8>,
+    reply: Mutex<Option<oneshot::Sender<Vec<u8>>>>,
+}
+
+#[async_trait]
+impl DeliveryHandle for InProcessDelivery {
+    async fn ack(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn reject(&self, requeue: bool) -> Result<()> {
+        if requeue {
+            self.bus.inject(self.queue, self.content.clone())?;
+        }
+
+        Ok(())
+    }
+
+    async fn reply(&self, payload: Vec<u8>) -> Result<()> {
+        let reply = self.reply.lock().unwrap_or_else(|e| e.into_inner()).take();
+
+        if let Some(reply) = reply {
+            // The sender may have stopped waiting
+            let _ = reply.send(payload);
+        }
+
+        Ok(())
+    }
+
+    async fn dead_letter(&self, source: DeadLetterSource, reason: &str) -> Result<()> {
+        let letter = DeadLetter {
+            source_queue: Some(self.queue.queue_name().to_string()),
+            failure_reason: Some(reason.to_string()),
+            failed_at: Some(chrono::Utc::now().to_rfc3339()),
+            content_type: None,
+            payload: serde_json::from_slice(&self.content).unwrap_or_else(|_| {
+                Value::String(String::from_utf8_lossy(&self.content).into_owned())
+            }),
+        };
+
+        self.bus
+            .lock_queues()
+            .dead_letters
+            .entry(source)
+            .or_default()
+            .push_back(StoredDeadLetter {
+                letter,
+                content: self.content.clone(),
+            });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(action: Option<&str>, microdevice_id: Option<i32>) -> FakeDeviceRule {
+        FakeDeviceRule {
+            action: action.map(str::to_string),
+            microdevice_id,
+            reply: None,
+            error: None,
+            delay_ms: None,
+        }
+    }
+
+    fn action(action: &str, microdevice_id: i32) -> (ActionRoute, Value) {
+        let route = ActionRoute {
+            cluster_id: "00000000-0000-0000-0000-000000000000".to_string(),
+            microdevice_id,
+            action: action.to_string(),
+        };
+
+        let payload = json!({
+            "cluster_id": route.cluster_id,
+            "microdevice_id": microdevice_id,
+            "action": action,
+            "payload": { "level": 3 },
+        });
+
+        (route, payload)
+    }
+
+    async fn transmit(bus: &InProcessBus, action: (ActionRoute, Value)) -> Result<Value> {
+        let deadline = Utc::now() + Duration::from_secs(5);
+        bus.transmit_action(&action.0, action.1, deadline).await
+    }
+
+    #[tokio::test]
+    async fn unscripted_actions_are_echoed() {
+        let bus = InProcessBus::new(FakeDeviceResponder::default());
+
+        let reply = transmit(&bus, action("restart", 1)).await.unwrap();
+
+        assert_eq!(
+            reply,
+            json!({ "status": "ok", "action": "restart", "payload": { "level": 3 } })
+        );
+    }
+
+    #[tokio::test]
+    async fn first_matching_rule_decides() {
+        let bus = InProcessBus::new(FakeDeviceResponder::new(vec![FakeDeviceRule {
+            reply: Some(json!({ "status": "calibrated" })),
+            ..rule(Some("calibrate"), None)
+        }]));
+
+        bus.responder().script(FakeDeviceRule {
+            error: Some(FakeDeviceError::Timeout),
+            ..rule(Some("calibrate"), Some(7))
+        });
+
+        assert!(matches!(
+            transmit(&bus, action("calibrate", 7)).await,
+            Err(Error::ResponseTimeout)
+        ));
+        assert_eq!(
+            transmit(&bus, action("calibrate", 8)).await.unwrap(),
+            json!({ "status": "calibrated" })
+        );
+    }
+
+    #[tokio::test]
+    async fn scripted_errors_match_the_broker() {
+        let bus = InProcessBus::new(FakeDeviceResponder::new(vec![
+            FakeDeviceRule {
+                error: Some(FakeDeviceError::NotRouted),
+                ..rule(Some("start"), None)
+            },
+            FakeDeviceRule {
+                error: Some(FakeDeviceError::Nacked),
+                ..rule(None, None)
+            },
+        ]));
+
+        assert!(matches!(
+            transmit(&bus, action("start", 1)).await,
+            Err(Error::NotRouted(_))
+        ));
+        assert!(matches!(
+            transmit(&bus, action("stop", 1)).await,
+            Err(Error::Nacked)
+        ));
+    }
+
+    #[tokio::test]
+    async fn consumers_reply_to_injected_messages() {
+        let bus = InProcessBus::new(FakeDeviceResponder::default());
+        let mut consumer = bus.consume(InboundQueue::Registrar).await.unwrap();
+
+        let reply = bus
+            .inject(InboundQueue::Registrar, b"{\"device_id\":\"d1\"}".to_vec())
+            .unwrap();
+
+        let delivery = consumer.rx.recv().await.unwrap();
+        delivery.reply(b"registered".to_vec()).await.unwrap();
+        delivery.ack().await;
+
+        assert_eq!(reply.await.unwrap(), b"registered".to_vec());
+    }
+
+    #[tokio::test]
+    async fn dead_letters_can_be_inspected_replayed_and_purged() {
+        let bus = InProcessBus::new(FakeDeviceResponder::default());
+        let mut consumer = bus.consume(InboundQueue::Telemetry).await.unwrap();
+
+        for content in [&b"not json"[..], &b"{\"device_id\":\"d1\"}"[..]] {
+            bus.inject(InboundQueue::Telemetry, content.to_vec())
+                .unwrap();
+            let delivery = consumer.rx.recv().await.unwrap();
+            delivery
+                .dead_letter(DeadLetterSource::Telemetry, "invalid payload")
+                .await;
+        }
+
+        let letters = bus
+            .dead_letters(DeadLetterSource::Telemetry, 10)
+            .await
+            .unwrap();
+        assert_eq!(letters.len(), 2);
+        assert_eq!(letters[0].payload, json!("not json"));
+        assert_eq!(
+            letters[1].failure_reason.as_deref(),
+            Some("invalid payload")
+        );
+
+        let replayed = bus
+            .replay_dead_letters(DeadLetterSource::Telemetry, 1)
+            .await
+            .unwrap();
+        assert_eq!(replayed, 1);
+        assert_eq!(
+            consumer.rx.recv().await.unwrap().content,
+            Some(b"not json".to_vec())
+        );
+
+        let purged = bus
+            .purge_dead_letters(DeadLetterSource::Telemetry)
+            .await
+            .unwrap();
+        assert_eq!(purged, 1);
+        assert!(bus
+            .dead_letters(DeadLetterSource::Telemetry, 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,219 @@
+pub use super::ampq::error::{Error, Result};
+pub use super::ampq::{BrokerState, BrokerStatus, DeadLetter, DeadLetterSource};
+use crate::config::{ConsumerConfig, MessageBusKind, CONFIG};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+pub use envelope::{Encoding, Envelope};
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch};
+use tracing::{error, info};
+pub mod envelope;
+pub mod in_process;
+
+/// Queue the microdevices send messages to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum InboundQueue {
+    Registrar,
+    Telemetry,
+    Shadow,
+}
+
+impl InboundQueue {
+    pub fn queue_name(self) -> &'static str {
+        match self {
+            Self::Registrar => &CONFIG.ampq.registrar_queue_name,
+            Self::Telemetry => &CONFIG.ampq.telemetry_queue_name,
+            Self::Shadow => &CONFIG.ampq.shadow_queue_name,
+        }
+    }
+
+    /// Prefetch and worker count of the queue, from `events`
+    pub fn consumer_config(self) -> &'static ConsumerConfig {
+        match self {
+            Self::Registrar => &CONFIG.events.registrar,
+            Self::Telemetry => &CONFIG.events.telemetry,
+            Self::Shadow => &CONFIG.events.shadow,
+        }
+    }
+}
+
+impl From<DeadLetterSource> for InboundQueue {
+    fn from(source: DeadLetterSource) -> Self {
+        match source {
+            DeadLetterSource::Registrar => Self::Registrar,
+            DeadLetterSource::Telemetry => Self::Telemetry,
+        }
+    }
+}
+
+/// Microdevice an action is meant for
+#[derive(Clone, Debug)]
+pub struct ActionRoute {
+    pub cluster_id: String,
+    pub microdevice_id: i32,
+    /// Name of the action, e.g. `power-on`
+    pub action: String,
+}
+
+/// Transport between the API and the microdevices
+///
+/// `ampq::MessageBroker` talks to RabbitMQ, `in_process::InProcessBus` keeps
+/// every message within the process. The implementation is picked with
+/// `message_bus.kind`.
+#[async_trait]
+pub trait MessageBus: Send + Sync {
+    fn status(&self) -> BrokerStatus;
+
+    /// Notifies about every change of the connection state.
+    fn subscribe_state(&self) -> watch::Receiver<BrokerStatus>;
+
+    /// Sends an action to a microdevice and waits for its reply until
+    /// `deadline`, after which the action is not delivered anymore.
+    async fn transmit_action(
+        &self,
+        route: &ActionRoute,
+        payload: Value,
+        deadline: DateTime<Utc>,
+    ) -> Result<Value>;
+
+    /// Publishes a fire-and-forget message to the microdevices, `message_type`
+    /// tells it apart from action requests.
+    async fn publish_event(&self, message_type: &str, payload: Value) -> Result<()>;
+
+    /// Starts consuming `queue`, the consumer stops when the bus disconnects.
+    async fn consume(&self, queue: InboundQueue) -> Result<Consumer>;
+
+    /// Returns up to `limit` dead-lettered messages of `source`, oldest first,
+    /// without removing them.
+    async fn dead_letters(&self, source: DeadLetterSource, limit: usize)
+        -> Result<Vec<DeadLetter>>;
+
+    /// Moves up to `limit` dead-lettered messages of `source` back to the
+    /// queue they failed in, returns how many were moved.
+    async fn replay_dead_letters(&self, source: DeadLetterSource, limit: usize) -> Result<usize>;
+
+    /// Drops every dead-lettered message of `source`, returns how many were dropped.
+    async fn purge_dead_letters(&self, source: DeadLetterSource) -> Result<u32>;
+}
+
+/// Creates the bus selected in `message_bus.kind`.
+///
+/// The in-process bus is returned a second time as is, so the fake device can
+/// be scripted and messages injected while the API runs.
+pub fn from_config() -> (Arc<dyn MessageBus>, Option<in_process::InProcessBus>) {
+    match CONFIG.message_bus.kind {
+        MessageBusKind::Amqp => (Arc::new(super::ampq::MessageBroker::new()), None),
+        MessageBusKind::InProcess => {
+            info!("Using the in-process message bus, actions are answered by a fake device");
+            let bus = in_process::InProcessBus::new(in_process::FakeDeviceResponder::new(
+                CONFIG.message_bus.fake_device.rules.clone(),
+            ));
+            (Arc::new(bus.clone()), Some(bus))
+        }
+    }
+}
+
+/// Messages received from a queue
+pub struct Consumer {
+    pub rx: mpsc::UnboundedReceiver<Delivery>,
+}
+
+/// Settles a delivery with the bus it came from
+#[async_trait]
+pub trait DeliveryHandle: Send + Sync {
+    async fn ack(&self) -> Result<()>;
+
+    async fn reject(&self, requeue: bool) -> Result<()>;
+
+    /// Answers to the `reply_to` of the delivery.
+    async fn reply(&self, payload: Vec<u8>) -> Result<()>;
+
+    /// Moves the delivery to the dead-letter queue of `source`, recording `reason`.
+    async fn dead_letter(&self, source: DeadLetterSource, reason: &str) -> Result<()>;
+}
+
+/// Message received from a queue, it has to be settled exactly once
+pub struct Delivery {
+    pub content: Option<Vec<u8>>,
+    /// Queue the sender expects an answer on
+    pub reply_to: Option<String>,
+    pub envelope: Envelope,
+    handle: Box<dyn DeliveryHandle>,
+}
+
+impl Delivery {
+    pub fn new(
+        content: Option<Vec<u8>>,
+        reply_to: Option<String>,
+        envelope: Envelope,
+        handle: Box<dyn DeliveryHandle>,
+    ) -> Self {
+        Self {
+            content,
+            reply_to,
+            envelope,
+            handle,
+        }
+    }
+
+    /// Decodes the content as told by the envelope, failing on a missing
+    /// content or an unknown version or content type.
+    pub fn decode<T: serde::de::DeserializeOwned>(&self) -> Result<T> {
+        let content = self
+            .content
+            .as_deref()
+            .ok_or_else(|| Error::DecodingFailed("message content is missing".to_string()))?;
+
+        self.envelope.decode(content)
+    }
+
+    /// Encodes an answer in the encoding of the message.
+    pub fn encode_reply<T: serde::Serialize>(&self, body: &T) -> Result<Vec<u8>> {
+        self.envelope.reply().encode(body)
+    }
+
+    pub async fn ack(&self) {
+        match self.handle.ack().await {
+            Ok(()) => info!("Message acknowledged successfully."),
+            Err(e) => error!(error = %e, "Failed to acknowledge message."),
+        }
+    }
+
+    /// Rejects the message without requeueing it.
+    pub async fn nack(&self) {
+        match self.handle.reject(false).await {
+            Ok(()) => info!("Message nacked successfully."),
+            Err(e) => error!(error = %e, "Failed to nack message."),
+        }
+    }
+
+    /// Rejects the message and puts it back in its queue.
+    pub async fn requeue(&self) {
+        if let Err(e) = self.handle.reject(true).await {
+            error!(error = %e, "Failed to requeue message.");
+        }
+    }
+
+    /// Answers to the `reply_to` of the message, does nothing when it has none.
+    pub async fn reply(&self, payload: Vec<u8>) -> Result<()> {
+        match self.reply_to {
+            Some(_) => self.handle.reply(payload).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Moves a message that could not be processed to the dead-letter queue of
+    /// `source`, the message is requeued when that fails.
+    pub async fn dead_letter(&self, source: DeadLetterSource, reason: &str) {
+        match self.handle.dead_letter(source, reason).await {
+            Ok(()) => self.ack().await,
+            Err(e) => {
+                error!(error = %e, "Failed to dead-letter message.");
+                self.requeue().await;
+            }
+        }
+    }
+}
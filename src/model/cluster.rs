@@ -4,12 +4,13 @@ use super::ModelManager;
 use crate::context::Ctx;
 use base64::{engine::general_purpose::URL_SAFE, Engine as _};
 use entity::{cluster, user};
-use entity::user_cluster;
+use entity::{device_group, microdevice, user_cluster};
 use sea_orm::ActiveValue::Set;
 use sea_orm::EntityTrait;
-use sea_orm::{entity::prelude::*, QueryTrait};
+use sea_orm::{entity::prelude::*, QuerySelect, QueryTrait};
 use serde::Deserialize;
 use serde::Serialize;
+use std::collections::BTreeMap;
 
 #[derive(Deserialize, Serialize)]
 #[serde(untagged)]
@@ -44,11 +45,46 @@ pub struct ClusterRecord {
     pub name: String,
 }
 
+/// Overview of the microdevices of a cluster
+#[derive(Serialize, utoipa::ToSchema, Debug)]
+pub struct ClusterSummary {
+    #[schema(example = "factory-a")]
+    name: String,
+    #[schema(example = 12)]
+    microdevices: usize,
+    /// Number of microdevices per lifecycle state
+    #[schema(example = json!({"active": 10, "disabled": 2}))]
+    lifecycle: BTreeMap<String, usize>,
+    #[schema(example = 3)]
+    groups: u64,
+}
+
 #[derive(Deserialize, utoipa::ToSchema)]
 pub struct ClusterQuery {
     uuid: Option<String>,
 }
 
+/// Role of a user within a cluster
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Deserialize,
+    Serialize,
+    utoipa::ToSchema,
+    strum::EnumString,
+    strum::AsRefStr,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ClusterRole {
+    Owner,
+    Operator,
+    Viewer,
+}
+
 pub struct ClusterBaseModelController {}
 
 impl Into<ClusterUuid> for String {
@@ -95,6 +131,7 @@ impl ClusterBaseModelController {
         let _ = user_cluster::ActiveModel {
             user_id: Set(ctx_uuid),
             cluster_id: Set(new_uuid),
+            role: Set(ClusterRole::Owner.as_ref().to_string()),
         }
         .insert(&mm.db)
         .await?;
@@ -227,6 +264,94 @@ impl ClusterBaseModelController {
         Ok(res.rows_affected)
     }
 
+    pub async fn summary(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+    ) -> Result<ClusterSummary> {
+        Self::exists(mm, ctx, cluster_uuid.clone()).await?;
+        let cluster_id = parse_cluster_id(&cluster_uuid)?;
+
+        let cluster = cluster::Entity::find_by_id(cluster_id)
+            .one(&mm.db)
+            .await?
+            .ok_or(Error {
+                kind: super::error::ErrorKind::ClusterNotFound,
+                message: format!("cluster `{}` not found.", cluster_id),
+            })?;
+
+        let states: Vec<String> = microdevice::Entity::find()
+            .select_only()
+            .column(microdevice::Column::LifecycleState)
+            .filter(microdevice::Column::ClusterId.eq(cluster_id))
+            .into_tuple()
+            .all(&mm.db)
+            .await?;
+
+        let mut lifecycle: BTreeMap<String, usize> = BTreeMap::new();
+        for state in &states {
+            *lifecycle.entry(state.clone()).or_default() += 1;
+        }
+
+        let groups = device_group::Entity::find()
+            .filter(device_group::Column::ClusterId.eq(cluster_id))
+            .count(&mm.db)
+            .await?;
+
+        Ok(ClusterSummary {
+            name: cluster.name,
+            microdevices: states.len(),
+            lifecycle,
+            groups,
+        })
+    }
+
+    /// Returns the role of the user in `ctx` within the given cluster.
+    ///
+    /// Unknown roles stored in the database are treated as `viewer`.
+    pub async fn get_role(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: &String,
+    ) -> Result<ClusterRole> {
+        let user_id = Self::validate_user_ctx(ctx)?;
+        let ctx_uuid = parse_cluster_id(&user_id.into())?;
+        let cluster_uuid = parse_cluster_id(cluster_uuid)?;
+
+        let membership = user_cluster::Entity::find_by_id((ctx_uuid, cluster_uuid))
+            .one(&mm.db)
+            .await?;
+
+        match membership {
+            Some(m) => Ok(m.role.parse().unwrap_or(ClusterRole::Viewer)),
+            None => Err(Error {
+                kind: super::error::ErrorKind::ClusterNotFound,
+                message: format!("cluster `{}` not found.", cluster_uuid),
+            }),
+        }
+    }
+
+    /// Makes sure the user in `ctx` holds one of the `allowed` roles within the
+    /// given cluster, `operation` describes what was attempted.
+    pub(crate) async fn require_role(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: &String,
+        allowed: &[ClusterRole],
+        operation: &str,
+    ) -> Result<ClusterRole> {
+        let role = Self::get_role(mm, ctx, cluster_uuid).await?;
+
+        if allowed.contains(&role) {
+            Ok(role)
+        } else {
+            Err(Error {
+                kind: super::error::ErrorKind::InsufficientClusterRole,
+                message: format!("role `{}` is not allowed to {}", role.as_ref(), operation),
+            })
+        }
+    }
+
     pub(crate) fn find_clusters_by_user_uuid(user_uuid: Uuid) -> Select<cluster::Entity> {
         cluster::Entity::find()
             .inner_join(user_cluster::Entity)
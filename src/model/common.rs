@@ -1,5 +1,10 @@
-use super::error::Result;
+use super::error::{Error, ErrorKind, Result};
+use super::ModelManager;
+use crate::config::CONFIG;
+use crate::context::Ctx;
 use base64::{engine::general_purpose::URL_SAFE, Engine as _};
+use entity::user;
+use sea_orm::EntityTrait;
 use uuid::Uuid;
 
 /// Method to parse a cluster ID from a string into a UUID
@@ -26,3 +31,24 @@ pub fn parse_cluster_id(uuid_str: &String) -> Result<Uuid> {
 pub fn parse_microdevice_id(id: i32) -> Result<i32> {
     Ok(id)
 }
+
+/// Makes sure the user in `ctx` is listed in `admin.usernames`.
+pub async fn require_admin(mm: &ModelManager, ctx: &Ctx) -> Result<()> {
+    let forbidden = || Error {
+        kind: ErrorKind::AdminRequired,
+        message: "only administrators can use the administration endpoints".to_string(),
+    };
+
+    let user_id = ctx.get_user_id().ok_or_else(forbidden)?;
+    let user_uuid = Uuid::parse_str(user_id).map_err(|_| forbidden())?;
+
+    let user = user::Entity::find_by_id(user_uuid)
+        .one(&mm.db)
+        .await?
+        .ok_or_else(forbidden)?;
+
+    match CONFIG.admin.usernames.contains(&user.username) {
+        true => Ok(()),
+        false => Err(forbidden()),
+    }
+}
@@ -0,0 +1,85 @@
+pub use super::bus::{DeadLetter, DeadLetterSource};
+use super::common::require_admin;
+use super::error::Result;
+use super::ModelManager;
+use crate::context::Ctx;
+use serde::{Deserialize, Serialize};
+
+/// Dead-lettered messages returned when no limit is given
+const DEFAULT_LIMIT: usize = 50;
+/// Dead-lettered messages handled by a single request at most
+const MAX_LIMIT: usize = 500;
+
+#[derive(Deserialize, Debug)]
+pub struct DeadLetterParams {
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema, Debug, Default)]
+pub struct DeadLetterReplay {
+    /// Number of messages to replay, oldest first
+    #[schema(example = 50)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, utoipa::ToSchema, Debug)]
+pub struct DeadLetterReplayResult {
+    /// Messages moved back to the queue they failed in
+    replayed: usize,
+}
+
+#[derive(Serialize, utoipa::ToSchema, Debug)]
+pub struct DeadLetterPurgeResult {
+    /// Messages dropped from the dead-letter queue
+    purged: u32,
+}
+
+pub struct DeadLetterBaseModelController {}
+
+impl DeadLetterBaseModelController {
+    pub async fn list(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        source: DeadLetterSource,
+        limit: Option<usize>,
+    ) -> Result<Vec<DeadLetter>> {
+        require_admin(mm, ctx).await?;
+
+        Ok(mm
+            .bus
+            .dead_letters(source, Self::limit(limit))
+            .await?)
+    }
+
+    pub async fn replay(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        source: DeadLetterSource,
+        params: DeadLetterReplay,
+    ) -> Result<DeadLetterReplayResult> {
+        require_admin(mm, ctx).await?;
+
+        let replayed = mm
+            .bus
+            .replay_dead_letters(source, Self::limit(params.limit))
+            .await?;
+
+        Ok(DeadLetterReplayResult { replayed })
+    }
+
+    pub async fn purge(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        source: DeadLetterSource,
+    ) -> Result<DeadLetterPurgeResult> {
+        require_admin(mm, ctx).await?;
+
+        let purged = mm.bus.purge_dead_letters(source).await?;
+
+        Ok(DeadLetterPurgeResult { purged })
+    }
+
+    fn limit(limit: Option<usize>) -> usize {
+        limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+    }
+}
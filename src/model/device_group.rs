@@ -0,0 +1,552 @@
+use super::cluster::{ClusterBaseModelController as ClusterBMC, ClusterRole};
+use super::common::parse_cluster_id;
+use super::error::{Error, ErrorKind, Result};
+use super::lifecycle::LifecycleState;
+use super::microdevice::MicrodeviceRecord;
+use super::ModelManager;
+use crate::context::Ctx;
+use entity::{device_group, device_group_member, microdevice};
+use sea_orm::ActiveValue::Set;
+use sea_orm::{entity::prelude::*, DatabaseTransaction, QueryOrder, SqlErr, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+const MAX_GROUP_NAME_LEN: usize = 64;
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Deserialize,
+    Serialize,
+    utoipa::ToSchema,
+    strum::EnumString,
+    strum::AsRefStr,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum DeviceGroupKind {
+    /// Members are listed explicitly
+    Static,
+    /// Members are every microdevice matching the filter
+    Dynamic,
+}
+
+/// Criteria a microdevice has to meet to be part of a dynamic group, every
+/// given criterion has to match.
+#[derive(Deserialize, Serialize, utoipa::ToSchema, Debug, Clone, Default)]
+pub struct DeviceGroupFilter {
+    /// Name pattern where `*` matches any sequence of characters
+    #[schema(example = "sensor-*")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    /// Labels the microdevice has to carry with exactly these values
+    #[schema(example = json!({"site": "factory-a"}))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    labels: Option<HashMap<String, String>>,
+    /// Lifecycle states the microdevice has to be in
+    #[schema(example = json!(["active"]))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<Vec<LifecycleState>>,
+}
+
+/// A new device group, either `members` or `filter` has to be given.
+#[derive(Deserialize, utoipa::ToSchema, Debug)]
+pub struct DeviceGroupCreate {
+    #[schema(example = "floor-1")]
+    name: String,
+    #[schema(example = "Sensors on the first floor")]
+    description: Option<String>,
+    /// Ids of the microdevices of a static group
+    #[schema(example = json!([1, 2, 3]))]
+    members: Option<Vec<i32>>,
+    /// Filter of a dynamic group
+    filter: Option<DeviceGroupFilter>,
+}
+
+/// Changes to a device group, fields that are omitted are left untouched.
+///
+/// `members` can only be changed on static groups and `filter` only on
+/// dynamic groups.
+#[derive(Deserialize, utoipa::ToSchema, Debug)]
+pub struct DeviceGroupUpdate {
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    #[schema(value_type = Option<String>, example = "Sensors on the first floor")]
+    description: Option<Option<String>>,
+    #[schema(example = json!([1, 2]))]
+    members: Option<Vec<i32>>,
+    filter: Option<DeviceGroupFilter>,
+}
+
+#[derive(Serialize, utoipa::ToSchema, Debug)]
+pub struct DeviceGroupRecord {
+    id: i32,
+    #[schema(example = "floor-1")]
+    name: String,
+    description: Option<String>,
+    kind: DeviceGroupKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    members: Option<Vec<i32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    filter: Option<DeviceGroupFilter>,
+}
+
+pub struct DeviceGroupBaseModelController {}
+
+impl DeviceGroupBaseModelController {
+    pub async fn list_groups(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+    ) -> Result<Vec<DeviceGroupRecord>> {
+        ClusterBMC::exists(mm, ctx, cluster_uuid.clone()).await?;
+
+        let groups = device_group::Entity::find()
+            .filter(device_group::Column::ClusterId.eq(parse_cluster_id(&cluster_uuid)?))
+            .order_by_asc(device_group::Column::Name)
+            .all(&mm.db)
+            .await?;
+
+        let mut members: HashMap<i32, Vec<i32>> = HashMap::new();
+        for member in device_group_member::Entity::find()
+            .filter(device_group_member::Column::GroupId.is_in(groups.iter().map(|g| g.id)))
+            .order_by_asc(device_group_member::Column::MicrodeviceId)
+            .all(&mm.db)
+            .await?
+        {
+            members
+                .entry(member.group_id)
+                .or_default()
+                .push(member.microdevice_id);
+        }
+
+        Ok(groups
+            .into_iter()
+            .map(|group| {
+                let group_members = members.remove(&group.id).unwrap_or_default();
+                Self::record(group, group_members)
+            })
+            .collect())
+    }
+
+    pub async fn get_group(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        name: String,
+    ) -> Result<DeviceGroupRecord> {
+        let group = Self::find_group(mm, ctx, &cluster_uuid, &name).await?;
+        let members = Self::static_members(mm, group.id).await?;
+
+        Ok(Self::record(group, members))
+    }
+
+    pub async fn create_group(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        group: DeviceGroupCreate,
+    ) -> Result<DeviceGroupRecord> {
+        ClusterBMC::exists(mm, ctx, cluster_uuid.clone()).await?;
+        Self::require_manager(mm, ctx, &cluster_uuid).await?;
+
+        let cluster_id = parse_cluster_id(&cluster_uuid)?;
+        Self::validate_name(&group.name)?;
+
+        let (kind, filter) = match (&group.members, group.filter) {
+            (Some(_), None) => (DeviceGroupKind::Static, None),
+            (None, Some(filter)) => {
+                Self::validate_filter(&filter)?;
+                (
+                    DeviceGroupKind::Dynamic,
+                    Some(serde_json::to_value(filter)?),
+                )
+            }
+            _ => {
+                return Err(Error {
+                    kind: ErrorKind::InvalidDeviceGroup,
+                    message: "a device group needs either `members` or `filter`".to_string(),
+                })
+            }
+        };
+
+        let txn = mm.db.begin().await?;
+
+        let res = device_group::ActiveModel {
+            cluster_id: Set(cluster_id),
+            name: Set(group.name.clone()),
+            description: Set(group.description),
+            kind: Set(kind.as_ref().to_string()),
+            filter: Set(filter),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await;
+
+        let model = match res {
+            Ok(model) => model,
+            Err(e) if matches!(e.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+                return Err(Error {
+                    kind: ErrorKind::DeviceGroupAlreadyExists,
+                    message: format!("device group `{}` already exists", group.name),
+                })
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let members = match group.members {
+            Some(members) => Self::replace_members(&txn, &model, members).await?,
+            None => vec![],
+        };
+
+        txn.commit().await?;
+
+        Ok(Self::record(model, members))
+    }
+
+    pub async fn update_group(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        name: String,
+        update: DeviceGroupUpdate,
+    ) -> Result<DeviceGroupRecord> {
+        let group = Self::find_group(mm, ctx, &cluster_uuid, &name).await?;
+        Self::require_manager(mm, ctx, &cluster_uuid).await?;
+
+        let kind = Self::kind(&group);
+
+        if update.members.is_some() && kind != DeviceGroupKind::Static {
+            return Err(Error {
+                kind: ErrorKind::InvalidDeviceGroup,
+                message: format!(
+                    "members of dynamic group `{}` are defined by its filter",
+                    name
+                ),
+            });
+        }
+
+        if update.filter.is_some() && kind != DeviceGroupKind::Dynamic {
+            return Err(Error {
+                kind: ErrorKind::InvalidDeviceGroup,
+                message: format!("static group `{}` has no filter", name),
+            });
+        }
+
+        let txn = mm.db.begin().await?;
+        let mut model = device_group::ActiveModel::from(group.clone());
+
+        if let Some(description) = update.description {
+            model.description = Set(description);
+        }
+
+        if let Some(filter) = update.filter {
+            Self::validate_filter(&filter)?;
+            model.filter = Set(Some(serde_json::to_value(filter)?));
+        }
+
+        let group = model.update(&txn).await?;
+
+        let members = match update.members {
+            Some(members) => Self::replace_members(&txn, &group, members).await?,
+            None => Self::static_members(mm, group.id).await?,
+        };
+
+        txn.commit().await?;
+
+        Ok(Self::record(group, members))
+    }
+
+    pub async fn delete_group(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        name: String,
+    ) -> Result<()> {
+        let group = Self::find_group(mm, ctx, &cluster_uuid, &name).await?;
+        Self::require_manager(mm, ctx, &cluster_uuid).await?;
+
+        group.delete(&mm.db).await?;
+
+        Ok(())
+    }
+
+    /// Lists the microdevices currently belonging to a group.
+    pub async fn list_members(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        name: String,
+    ) -> Result<Vec<MicrodeviceRecord>> {
+        let group = Self::find_group(mm, ctx, &cluster_uuid, &name).await?;
+
+        Ok(Self::resolve(mm, &group)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    /// Resolves the ids of the microdevices currently belonging to a group.
+    pub(crate) async fn resolve_member_ids(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: &String,
+        name: &str,
+    ) -> Result<Vec<i32>> {
+        let group = Self::find_group(mm, ctx, cluster_uuid, name).await?;
+
+        Ok(Self::resolve(mm, &group)
+            .await?
+            .into_iter()
+            .map(|m| m.id)
+            .collect())
+    }
+
+    async fn resolve(
+        mm: &ModelManager,
+        group: &device_group::Model,
+    ) -> Result<Vec<microdevice::Model>> {
+        let query = microdevice::Entity::find()
+            .filter(microdevice::Column::ClusterId.eq(group.cluster_id))
+            .order_by_asc(microdevice::Column::Id);
+
+        match Self::kind(group) {
+            DeviceGroupKind::Static => {
+                let ids = Self::static_members(mm, group.id).await?;
+
+                Ok(query
+                    .filter(microdevice::Column::Id.is_in(ids))
+                    .all(&mm.db)
+                    .await?)
+            }
+            DeviceGroupKind::Dynamic => {
+                let filter: DeviceGroupFilter = group
+                    .filter
+                    .clone()
+                    .and_then(|f| serde_json::from_value(f).ok())
+                    .ok_or(Error {
+                        kind: ErrorKind::InvalidDeviceGroup,
+                        message: format!("device group `{}` has an invalid filter", group.name),
+                    })?;
+
+                Ok(query
+                    .all(&mm.db)
+                    .await?
+                    .into_iter()
+                    .filter(|m| filter.matches(m))
+                    .collect())
+            }
+        }
+    }
+
+    async fn find_group(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: &String,
+        name: &str,
+    ) -> Result<device_group::Model> {
+        ClusterBMC::exists(mm, ctx, cluster_uuid.clone()).await?;
+
+        device_group::Entity::find()
+            .filter(device_group::Column::ClusterId.eq(parse_cluster_id(cluster_uuid)?))
+            .filter(device_group::Column::Name.eq(name))
+            .one(&mm.db)
+            .await?
+            .ok_or(Error {
+                kind: ErrorKind::DeviceGroupNotFound,
+                message: format!("device group `{}` not found", name),
+            })
+    }
+
+    async fn static_members(mm: &ModelManager, group_id: i32) -> Result<Vec<i32>> {
+        Ok(device_group_member::Entity::find()
+            .filter(device_group_member::Column::GroupId.eq(group_id))
+            .order_by_asc(device_group_member::Column::MicrodeviceId)
+            .all(&mm.db)
+            .await?
+            .into_iter()
+            .map(|m| m.microdevice_id)
+            .collect())
+    }
+
+    /// Replaces the members of a static group, every member has to be a
+    /// microdevice of the cluster of the group.
+    async fn replace_members(
+        txn: &DatabaseTransaction,
+        group: &device_group::Model,
+        members: Vec<i32>,
+    ) -> Result<Vec<i32>> {
+        let mut members: Vec<i32> = members
+            .into_iter()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        members.sort_unstable();
+
+        let found: HashSet<i32> = microdevice::Entity::find()
+            .filter(microdevice::Column::ClusterId.eq(group.cluster_id))
+            .filter(microdevice::Column::Id.is_in(members.clone()))
+            .all(txn)
+            .await?
+            .into_iter()
+            .map(|m| m.id)
+            .collect();
+
+        let missing: Vec<String> = members
+            .iter()
+            .filter(|id| !found.contains(id))
+            .map(|id| id.to_string())
+            .collect();
+
+        if !missing.is_empty() {
+            return Err(Error {
+                kind: ErrorKind::MicrodeviceNotFound,
+                message: format!(
+                    "microdevices `{}` not found in the cluster",
+                    missing.join("`, `")
+                ),
+            });
+        }
+
+        device_group_member::Entity::delete_many()
+            .filter(device_group_member::Column::GroupId.eq(group.id))
+            .exec(txn)
+            .await?;
+
+        if !members.is_empty() {
+            device_group_member::Entity::insert_many(members.iter().map(|id| {
+                device_group_member::ActiveModel {
+                    group_id: Set(group.id),
+                    microdevice_id: Set(*id),
+                }
+            }))
+            .exec_without_returning(txn)
+            .await?;
+        }
+
+        Ok(members)
+    }
+
+    async fn require_manager(mm: &ModelManager, ctx: &Ctx, cluster_uuid: &String) -> Result<()> {
+        ClusterBMC::require_role(
+            mm,
+            ctx,
+            cluster_uuid,
+            &[ClusterRole::Owner, ClusterRole::Operator],
+            "manage device groups",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    fn kind(group: &device_group::Model) -> DeviceGroupKind {
+        group.kind.parse().unwrap_or(DeviceGroupKind::Static)
+    }
+
+    fn record(group: device_group::Model, members: Vec<i32>) -> DeviceGroupRecord {
+        let kind = Self::kind(&group);
+
+        DeviceGroupRecord {
+            id: group.id,
+            name: group.name,
+            description: group.description,
+            kind,
+            members: (kind == DeviceGroupKind::Static).then_some(members),
+            filter: group.filter.and_then(|f| serde_json::from_value(f).ok()),
+        }
+    }
+
+    fn validate_name(name: &str) -> Result<()> {
+        if name.is_empty()
+            || name.len() > MAX_GROUP_NAME_LEN
+            || name
+                .chars()
+                .any(|c| c.is_whitespace() || c.is_control() || c == '/')
+        {
+            return Err(Error {
+                kind: ErrorKind::InvalidDeviceGroup,
+                message: format!(
+                    "group name must be between 1 and {} characters without whitespace or `/`",
+                    MAX_GROUP_NAME_LEN
+                ),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn validate_filter(filter: &DeviceGroupFilter) -> Result<()> {
+        let empty = filter.name.is_none()
+            && filter.labels.as_ref().is_none_or(|l| l.is_empty())
+            && filter.status.as_ref().is_none_or(|s| s.is_empty());
+
+        if empty {
+            return Err(Error {
+                kind: ErrorKind::InvalidDeviceGroup,
+                message: "a filter needs at least one of `name`, `labels` or `status`".to_string(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl DeviceGroupFilter {
+    fn matches(&self, device: &microdevice::Model) -> bool {
+        if let Some(pattern) = &self.name {
+            if !glob_match(pattern, &device.name) {
+                return false;
+            }
+        }
+
+        if let Some(labels) = &self.labels {
+            let matches_labels = labels.iter().all(|(key, value)| {
+                device.labels.get(key).and_then(|v| v.as_str()) == Some(value.as_str())
+            });
+
+            if !matches_labels {
+                return false;
+            }
+        }
+
+        if let Some(status) = &self.status {
+            if !status.contains(&LifecycleState::from_stored(&device.lifecycle_state)) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Matches `text` against `pattern` where `*` matches any sequence of characters.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+
+    // Without a wildcard the pattern has to match exactly
+    let Some(first) = parts.next() else {
+        return text.is_empty();
+    };
+
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.len() >= last.len() && rest.ends_with(last)
+}
@@ -15,9 +15,36 @@ pub enum ErrorKind {
     SerdeError(serde_json::Error),
     UnauthorizedClusterAccess,
     ClusterNotFound,
-    
     MicrodeviceNotFound,
     InvalidContext,
+    InvalidShadowDocument,
+    ShadowVersionConflict,
+    ActionNotFound,
+    ActionAlreadyExists,
+    InvalidActionDefinition,
+    InvalidActionPayload,
+    InsufficientClusterRole,
+    InvalidTopicFormat,
+    DuplicateTopic,
+    TopicNotFound,
+    InvalidBulkImport,
+    InvalidLifecycleTransition,
+    InvalidLifecycleReason,
+    MicrodeviceNotOperational,
+    InvalidLabel,
+    InvalidDeviceGroup,
+    DeviceGroupNotFound,
+    DeviceGroupAlreadyExists,
+    ConfirmationRequired,
+    ActionJobNotFound,
+    InvalidSchedule,
+    ScheduleNotFound,
+    ScheduleAlreadyExists,
+    InvalidIdempotencyKey,
+    IdempotencyKeyInUse,
+    RateLimited,
+    AdminRequired,
+    FakeDeviceUnavailable,
 }
 
 #[derive(Debug)]
@@ -28,6 +55,29 @@ pub struct Error {
 
 impl std::error::Error for Error {}
 
+impl Error {
+    /// Whether the database could not be reached, as opposed to refusing the
+    /// operation, so trying again later may succeed.
+    pub fn is_database_unavailable(&self) -> bool {
+        use sea_orm::sqlx::Error as SqlxError;
+        use sea_orm::{DbErr, RuntimeErr};
+
+        match &self.kind {
+            ErrorKind::DatabaseError(DbErr::ConnectionAcquire(_) | DbErr::Conn(_)) => true,
+            ErrorKind::DatabaseError(
+                DbErr::Exec(RuntimeErr::SqlxError(err)) | DbErr::Query(RuntimeErr::SqlxError(err)),
+            ) => matches!(
+                err,
+                SqlxError::Io(_)
+                    | SqlxError::PoolTimedOut
+                    | SqlxError::PoolClosed
+                    | SqlxError::WorkerCrashed
+            ),
+            _ => false,
+        }
+    }
+}
+
 impl std::fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -41,6 +91,34 @@ impl std::fmt::Display for ErrorKind {
             ErrorKind::AmpqError(e) => write!(f, "Ampq error: {}", e),
             ErrorKind::SerdeError(e) => write!(f, "Serde error: {}", e),
             ErrorKind::InvalidContext => write!(f, "Invalid context encountered"),
+            ErrorKind::InvalidShadowDocument => write!(f, "Invalid shadow document"),
+            ErrorKind::ShadowVersionConflict => write!(f, "Shadow version conflict"),
+            ErrorKind::ActionNotFound => write!(f, "Action not found"),
+            ErrorKind::ActionAlreadyExists => write!(f, "Action already exists"),
+            ErrorKind::InvalidActionDefinition => write!(f, "Invalid action definition"),
+            ErrorKind::InvalidActionPayload => write!(f, "Invalid action payload"),
+            ErrorKind::InsufficientClusterRole => write!(f, "Insufficient cluster role"),
+            ErrorKind::InvalidTopicFormat => write!(f, "Invalid topic format"),
+            ErrorKind::DuplicateTopic => write!(f, "Duplicate topic"),
+            ErrorKind::TopicNotFound => write!(f, "Topic not found"),
+            ErrorKind::InvalidBulkImport => write!(f, "Invalid bulk import"),
+            ErrorKind::InvalidLifecycleTransition => write!(f, "Invalid lifecycle transition"),
+            ErrorKind::InvalidLifecycleReason => write!(f, "Invalid lifecycle reason"),
+            ErrorKind::MicrodeviceNotOperational => write!(f, "Microdevice not operational"),
+            ErrorKind::InvalidLabel => write!(f, "Invalid label"),
+            ErrorKind::InvalidDeviceGroup => write!(f, "Invalid device group"),
+            ErrorKind::DeviceGroupNotFound => write!(f, "Device group not found"),
+            ErrorKind::DeviceGroupAlreadyExists => write!(f, "Device group already exists"),
+            ErrorKind::ConfirmationRequired => write!(f, "Confirmation required"),
+            ErrorKind::ActionJobNotFound => write!(f, "Action job not found"),
+            ErrorKind::InvalidSchedule => write!(f, "Invalid schedule"),
+            ErrorKind::ScheduleNotFound => write!(f, "Schedule not found"),
+            ErrorKind::ScheduleAlreadyExists => write!(f, "Schedule already exists"),
+            ErrorKind::InvalidIdempotencyKey => write!(f, "Invalid idempotency key"),
+            ErrorKind::IdempotencyKeyInUse => write!(f, "Idempotency key in use"),
+            ErrorKind::RateLimited => write!(f, "Rate limited"),
+            ErrorKind::AdminRequired => write!(f, "Administrator required"),
+            ErrorKind::FakeDeviceUnavailable => write!(f, "Fake device unavailable"),
         }
     }
 }
@@ -110,8 +188,39 @@ impl IntoResponse for Error {
                 ErrorKind::UnauthorizedClusterAccess => axum::http::StatusCode::UNAUTHORIZED,
                 ErrorKind::ClusterNotFound => axum::http::StatusCode::NOT_FOUND,
                 ErrorKind::MicrodeviceNotFound => axum::http::StatusCode::NOT_FOUND,
+                ErrorKind::AmpqError(ampq::error::Error::BrokerUnavailable) => {
+                    axum::http::StatusCode::SERVICE_UNAVAILABLE
+                }
                 ErrorKind::AmpqError(_) => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
                 ErrorKind::SerdeError(_) => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorKind::InvalidShadowDocument => axum::http::StatusCode::BAD_REQUEST,
+                ErrorKind::ShadowVersionConflict => axum::http::StatusCode::CONFLICT,
+                ErrorKind::ActionNotFound => axum::http::StatusCode::NOT_FOUND,
+                ErrorKind::ActionAlreadyExists => axum::http::StatusCode::CONFLICT,
+                ErrorKind::InvalidActionDefinition => axum::http::StatusCode::BAD_REQUEST,
+                ErrorKind::InvalidActionPayload => axum::http::StatusCode::BAD_REQUEST,
+                ErrorKind::InsufficientClusterRole => axum::http::StatusCode::FORBIDDEN,
+                ErrorKind::InvalidTopicFormat => axum::http::StatusCode::BAD_REQUEST,
+                ErrorKind::DuplicateTopic => axum::http::StatusCode::CONFLICT,
+                ErrorKind::TopicNotFound => axum::http::StatusCode::NOT_FOUND,
+                ErrorKind::InvalidBulkImport => axum::http::StatusCode::BAD_REQUEST,
+                ErrorKind::InvalidLifecycleTransition => axum::http::StatusCode::CONFLICT,
+                ErrorKind::InvalidLifecycleReason => axum::http::StatusCode::BAD_REQUEST,
+                ErrorKind::MicrodeviceNotOperational => axum::http::StatusCode::CONFLICT,
+                ErrorKind::InvalidLabel => axum::http::StatusCode::BAD_REQUEST,
+                ErrorKind::InvalidDeviceGroup => axum::http::StatusCode::BAD_REQUEST,
+                ErrorKind::DeviceGroupNotFound => axum::http::StatusCode::NOT_FOUND,
+                ErrorKind::DeviceGroupAlreadyExists => axum::http::StatusCode::CONFLICT,
+                ErrorKind::ConfirmationRequired => axum::http::StatusCode::BAD_REQUEST,
+                ErrorKind::ActionJobNotFound => axum::http::StatusCode::NOT_FOUND,
+                ErrorKind::InvalidSchedule => axum::http::StatusCode::BAD_REQUEST,
+                ErrorKind::ScheduleNotFound => axum::http::StatusCode::NOT_FOUND,
+                ErrorKind::ScheduleAlreadyExists => axum::http::StatusCode::CONFLICT,
+                ErrorKind::InvalidIdempotencyKey => axum::http::StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorKind::IdempotencyKeyInUse => axum::http::StatusCode::CONFLICT,
+                ErrorKind::RateLimited => axum::http::StatusCode::TOO_MANY_REQUESTS,
+                ErrorKind::AdminRequired => axum::http::StatusCode::FORBIDDEN,
+                ErrorKind::FakeDeviceUnavailable => axum::http::StatusCode::CONFLICT,
                 _ => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
             })
             .body(self.message.into())
@@ -0,0 +1,73 @@
+use super::lifecycle::LifecycleState;
+use super::telemetry::TelemetrySample;
+use sea_orm::prelude::Uuid;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// Change pushed to live subscribers such as WebSocket connections
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveEvent {
+    /// The lifecycle state of a microdevice changed
+    DeviceStatus {
+        cluster_id: Uuid,
+        microdevice_id: i32,
+        state: LifecycleState,
+    },
+    /// An action job progressed, `microdevice_id` is empty once the whole job
+    /// has finished
+    JobProgress {
+        cluster_id: Uuid,
+        job_id: Uuid,
+        microdevice_id: Option<i32>,
+        status: String,
+    },
+    /// A microdevice sent a telemetry sample
+    Telemetry {
+        cluster_id: Uuid,
+        microdevice_id: i32,
+        sample: TelemetrySample,
+    },
+}
+
+impl LiveEvent {
+    pub fn cluster_id(&self) -> Uuid {
+        match self {
+            Self::DeviceStatus { cluster_id, .. }
+            | Self::JobProgress { cluster_id, .. }
+            | Self::Telemetry { cluster_id, .. } => *cluster_id,
+        }
+    }
+
+    pub fn microdevice_id(&self) -> Option<i32> {
+        match self {
+            Self::DeviceStatus { microdevice_id, .. } | Self::Telemetry { microdevice_id, .. } => {
+                Some(*microdevice_id)
+            }
+            Self::JobProgress { microdevice_id, .. } => *microdevice_id,
+        }
+    }
+}
+
+/// Broadcasts live events to every subscriber of the process
+#[derive(Clone)]
+pub struct EventBus {
+    tx: broadcast::Sender<LiveEvent>,
+}
+
+impl EventBus {
+    /// Subscribers falling more than `capacity` events behind miss the oldest ones.
+    pub(crate) fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity.max(1));
+        Self { tx }
+    }
+
+    pub(crate) fn publish(&self, event: LiveEvent) {
+        // Nobody listening is not an error
+        let _ = self.tx.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<LiveEvent> {
+        self.tx.subscribe()
+    }
+}
@@ -0,0 +1,63 @@
+use super::bus::in_process::InProcessBus;
+pub use super::bus::InboundQueue;
+use super::common::require_admin;
+use super::error::{Error, ErrorKind, Result};
+use super::ModelManager;
+use crate::config::{FakeDeviceRule, CONFIG};
+use crate::context::Ctx;
+use serde::Serialize;
+use serde_json::Value;
+use std::time::Duration;
+
+#[derive(Serialize, utoipa::ToSchema, Debug)]
+pub struct FakeDeviceMessageResult {
+    /// Answer of the consumer, missing when it does not reply to the queue
+    reply: Option<Value>,
+}
+
+pub struct FakeDeviceBaseModelController {}
+
+impl FakeDeviceBaseModelController {
+    /// Adds a rule answering the actions it matches before the configured ones.
+    pub async fn script(mm: &ModelManager, ctx: &Ctx, rule: FakeDeviceRule) -> Result<()> {
+        require_admin(mm, ctx).await?;
+
+        Self::bus(mm)?.responder().script(rule);
+
+        Ok(())
+    }
+
+    /// Hands a message to the consumer of `queue` as a microdevice would and
+    /// waits up to `actions.device_timeout` seconds for its reply.
+    pub async fn send_message(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        queue: InboundQueue,
+        message: Value,
+    ) -> Result<FakeDeviceMessageResult> {
+        require_admin(mm, ctx).await?;
+
+        let reply = Self::bus(mm)?.inject(queue, serde_json::to_vec(&message)?)?;
+        let timeout = Duration::from_secs(CONFIG.actions.device_timeout);
+
+        // The reply is dropped once the message was handled without answering
+        let reply = tokio::time::timeout(timeout, reply)
+            .await
+            .ok()
+            .and_then(|reply| reply.ok());
+
+        Ok(FakeDeviceMessageResult {
+            reply: reply.map(|reply| {
+                serde_json::from_slice(&reply)
+                    .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&reply).into_owned()))
+            }),
+        })
+    }
+
+    fn bus(mm: &ModelManager) -> Result<&InProcessBus> {
+        mm.in_process_bus.as_ref().ok_or_else(|| Error {
+            kind: ErrorKind::FakeDeviceUnavailable,
+            message: "the fake device only runs with `message_bus.kind: in_process`".to_string(),
+        })
+    }
+}
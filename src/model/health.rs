@@ -0,0 +1,28 @@
+pub use super::bus::{BrokerState, BrokerStatus};
+use super::ModelManager;
+use serde::Serialize;
+
+#[derive(Serialize, utoipa::ToSchema, Debug)]
+pub struct HealthRecord {
+    /// Whether the database answered
+    database: bool,
+    broker: BrokerStatus,
+}
+
+impl HealthRecord {
+    pub fn is_healthy(&self) -> bool {
+        self.database && self.broker.state == BrokerState::Connected
+    }
+}
+
+pub struct HealthBaseModelController {}
+
+impl HealthBaseModelController {
+    /// Checks the database and reports the state of the broker connection.
+    pub async fn check(mm: &ModelManager) -> HealthRecord {
+        HealthRecord {
+            database: mm.db.ping().await.is_ok(),
+            broker: mm.bus.status(),
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use super::common::parse_cluster_id;
+use super::error::{Error, ErrorKind, Result};
+use super::ModelManager;
+use crate::config::CONFIG;
+use crate::context::Ctx;
+use entity::idempotency_key;
+use sea_orm::ActiveValue::Set;
+use sea_orm::{entity::prelude::*, Condition, SqlErr};
+use serde_json::Value;
+use std::future::Future;
+
+const MAX_KEY_LEN: usize = 255;
+
+pub struct IdempotencyBaseModelController {}
+
+impl IdempotencyBaseModelController {
+    /// Runs `f` at most once per idempotency key.
+    ///
+    /// Keys are scoped to the user and cluster and remembered for
+    /// `actions.idempotency_window` seconds. Repeating a key returns the result
+    /// of the first request, a failed request releases its key so it can be
+    /// retried. A key whose request has no result after
+    /// `actions.idempotency_claim_timeout` seconds is released as well.
+    pub async fn run<F, Fut, E>(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: &String,
+        key: String,
+        request: Value,
+        f: F,
+    ) -> std::result::Result<Value, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = std::result::Result<Value, E>>,
+        E: From<Error>,
+    {
+        let entry = match Self::claim(mm, ctx, cluster_uuid, key, request).await? {
+            Claim::Replay(response) => return Ok(response),
+            Claim::Claimed(entry) => entry,
+        };
+
+        let res = f().await;
+
+        match &res {
+            Ok(response) => {
+                let mut entry = idempotency_key::ActiveModel::from(entry);
+                entry.response = Set(Some(response.clone()));
+                entry.update(&mm.db).await.map_err(Error::from)?;
+            }
+            Err(_) => {
+                entry.delete(&mm.db).await.map_err(Error::from)?;
+            }
+        }
+
+        res
+    }
+
+    /// Forgets the keys whose window passed, returns how many were forgotten.
+    pub async fn purge_expired(mm: &ModelManager) -> Result<u64> {
+        let res = idempotency_key::Entity::delete_many()
+            .filter(idempotency_key::Column::ExpiresAt.lte(chrono::Utc::now().fixed_offset()))
+            .exec(&mm.db)
+            .await?;
+
+        Ok(res.rows_affected)
+    }
+
+    async fn claim(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: &String,
+        key: String,
+        request: Value,
+    ) -> Result<Claim> {
+        if key.is_empty() || key.len() > MAX_KEY_LEN {
+            return Err(Error {
+                kind: ErrorKind::InvalidIdempotencyKey,
+                message: format!(
+                    "idempotency key must be between 1 and {} characters",
+                    MAX_KEY_LEN
+                ),
+            });
+        }
+
+        let cluster_id = parse_cluster_id(cluster_uuid)?;
+        let user_id = ctx.get_user_id().cloned().unwrap_or_default();
+        let now = chrono::Utc::now().fixed_offset();
+
+        let claim_expires_before =
+            now - chrono::Duration::seconds(CONFIG.actions.idempotency_claim_timeout);
+
+        // Expired keys can be used again, as can keys of requests interrupted
+        // before storing their result
+        idempotency_key::Entity::delete_many()
+            .filter(idempotency_key::Column::ClusterId.eq(cluster_id))
+            .filter(idempotency_key::Column::UserId.eq(&user_id))
+            .filter(idempotency_key::Column::Key.eq(&key))
+            .filter(
+                Condition::any()
+                    .add(idempotency_key::Column::ExpiresAt.lte(now))
+                    .add(
+                        Condition::all()
+                            .add(idempotency_key::Column::Response.is_null())
+                            .add(idempotency_key::Column::CreatedAt.lte(claim_expires_before)),
+                    ),
+            )
+            .exec(&mm.db)
+            .await?;
+
+        let res = idempotency_key::ActiveModel {
+            cluster_id: Set(cluster_id),
+            user_id: Set(user_id.clone()),
+            key: Set(key.clone()),
+            request: Set(request.clone()),
+            response: Set(None),
+            created_at: Set(now),
+            expires_at: Set(now + chrono::Duration::seconds(CONFIG.actions.idempotency_window)),
+            ..Default::default()
+        }
+        .insert(&mm.db)
+        .await;
+
+        match res {
+            Ok(entry) => return Ok(Claim::Claimed(entry)),
+            Err(e) if matches!(e.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        let entry = idempotency_key::Entity::find()
+            .filter(idempotency_key::Column::ClusterId.eq(cluster_id))
+            .filter(idempotency_key::Column::UserId.eq(&user_id))
+            .filter(idempotency_key::Column::Key.eq(&key))
+            .one(&mm.db)
+            .await?;
+
+        match entry {
+            Some(entry) if entry.request != request => Err(Error {
+                kind: ErrorKind::InvalidIdempotencyKey,
+                message: format!(
+                    "idempotency key `{}` was already used for a different request",
+                    key
+                ),
+            }),
+            Some(idempotency_key::Model {
+                response: Some(response),
+                ..
+            }) => Ok(Claim::Replay(response)),
+            // Either still running or released by a failure in the meantime
+            _ => Err(Error {
+                kind: ErrorKind::IdempotencyKeyInUse,
+                message: format!(
+                    "a request with idempotency key `{}` is still in progress",
+                    key
+                ),
+            }),
+        }
+    }
+}
+
+enum Claim {
+    /// The key is new, the request has to be executed
+    Claimed(idempotency_key::Model),
+    /// Result of an earlier request with the same key
+    Replay(Value),
+}
@@ -0,0 +1,307 @@
+use super::cluster::{ClusterBaseModelController as ClusterBMC, ClusterRole};
+use super::error::{Error, ErrorKind, Result};
+use super::event_bus::LiveEvent;
+use super::microdevice::MicrodeviceBaseModelController as MicrodeviceBMC;
+use super::ModelManager;
+use crate::context::Ctx;
+use entity::{microdevice, microdevice_lifecycle_event};
+use sea_orm::ActiveValue::Set;
+use sea_orm::{entity::prelude::*, DatabaseTransaction, QueryOrder, QuerySelect, TransactionTrait};
+use serde::{Deserialize, Serialize};
+
+const MAX_REASON_LEN: usize = 512;
+
+/// Lifecycle state of a microdevice
+///
+/// Disabled and decommissioned microdevices keep their history but are
+/// excluded from actions, telemetry ingestion and registration.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Deserialize,
+    Serialize,
+    utoipa::ToSchema,
+    strum::EnumString,
+    strum::AsRefStr,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum LifecycleState {
+    Provisioned,
+    Active,
+    Disabled,
+    Decommissioned,
+}
+
+impl LifecycleState {
+    /// Decommissioning is final, every other state can be left again.
+    pub fn can_transition_to(self, target: LifecycleState) -> bool {
+        use LifecycleState::*;
+
+        matches!(
+            (self, target),
+            (Provisioned, Active | Disabled | Decommissioned)
+                | (Active, Disabled | Decommissioned)
+                | (Disabled, Active | Decommissioned)
+        )
+    }
+
+    /// Whether the microdevice may receive actions and send messages
+    pub fn is_operational(self) -> bool {
+        matches!(self, LifecycleState::Provisioned | LifecycleState::Active)
+    }
+
+    /// Parses a stored state, unknown values keep the microdevice out of service.
+    pub(crate) fn from_stored(state: &str) -> Self {
+        state.parse().unwrap_or(LifecycleState::Disabled)
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema, Debug)]
+pub struct LifecycleTransition {
+    #[schema(example = "disabled")]
+    pub state: LifecycleState,
+    #[schema(example = "Temperature readings are off by 20 degrees")]
+    pub reason: String,
+}
+
+#[derive(Serialize, utoipa::ToSchema, Debug)]
+pub struct LifecycleEventRecord {
+    #[schema(example = "active")]
+    from_state: String,
+    #[schema(example = "disabled")]
+    to_state: String,
+    #[schema(example = "Temperature readings are off by 20 degrees")]
+    reason: String,
+    /// User that requested the transition, empty for automatic transitions
+    changed_by: Option<String>,
+    #[schema(value_type = String, format = DateTime)]
+    changed_at: DateTimeWithTimeZone,
+}
+
+#[derive(Serialize, utoipa::ToSchema, Debug)]
+pub struct LifecycleRecord {
+    microdevice_id: i32,
+    state: LifecycleState,
+    /// Transitions of the microdevice, most recent first
+    history: Vec<LifecycleEventRecord>,
+}
+
+impl From<microdevice_lifecycle_event::Model> for LifecycleEventRecord {
+    fn from(event: microdevice_lifecycle_event::Model) -> Self {
+        Self {
+            from_state: event.from_state,
+            to_state: event.to_state,
+            reason: event.reason,
+            changed_by: event.changed_by,
+            changed_at: event.changed_at,
+        }
+    }
+}
+
+pub struct LifecycleBaseModelController {}
+
+impl LifecycleBaseModelController {
+    pub async fn get_lifecycle(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        microdevice_id: i32,
+    ) -> Result<LifecycleRecord> {
+        let device =
+            MicrodeviceBMC::find_in_cluster(mm, ctx, &cluster_uuid, microdevice_id).await?;
+
+        Self::record(mm, &device).await
+    }
+
+    pub async fn transition(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        microdevice_id: i32,
+        transition: LifecycleTransition,
+    ) -> Result<LifecycleRecord> {
+        let device =
+            MicrodeviceBMC::find_in_cluster(mm, ctx, &cluster_uuid, microdevice_id).await?;
+
+        ClusterBMC::require_role(
+            mm,
+            ctx,
+            &cluster_uuid,
+            &[ClusterRole::Owner, ClusterRole::Operator],
+            "change the lifecycle of microdevices",
+        )
+        .await?;
+
+        let reason = transition.reason.trim();
+        if reason.is_empty() || reason.len() > MAX_REASON_LEN {
+            return Err(Error {
+                kind: ErrorKind::InvalidLifecycleReason,
+                message: format!("reason must be between 1 and {} characters", MAX_REASON_LEN),
+            });
+        }
+
+        let txn = mm.db.begin().await?;
+
+        let device = microdevice::Entity::find_by_id(device.id)
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(Error {
+                kind: ErrorKind::MicrodeviceNotFound,
+                message: format!("microdevice `{}` not found", microdevice_id),
+            })?;
+
+        let device = Self::apply(
+            &txn,
+            device,
+            transition.state,
+            reason,
+            ctx.get_user_id().cloned(),
+        )
+        .await?;
+
+        txn.commit().await?;
+
+        Self::publish(mm, &device);
+
+        Self::record(mm, &device).await
+    }
+
+    /// Loads the microdevice identified by `ctx`, refusing it unless it is in
+    /// an operational state.
+    pub(crate) async fn require_operational(
+        mm: &ModelManager,
+        ctx: &Ctx,
+    ) -> Result<microdevice::Model> {
+        let microdevice_id = MicrodeviceBMC::microdevice_id_from_ctx(ctx)?;
+
+        let device = microdevice::Entity::find_by_id(microdevice_id)
+            .one(&mm.db)
+            .await?
+            .ok_or(Error {
+                kind: ErrorKind::MicrodeviceNotFound,
+                message: format!("microdevice `{}` not found", microdevice_id),
+            })?;
+
+        let state = LifecycleState::from_stored(&device.lifecycle_state);
+        if !state.is_operational() {
+            return Err(Error {
+                kind: ErrorKind::MicrodeviceNotOperational,
+                message: format!("microdevice `{}` is {}", device.id, state.as_ref()),
+            });
+        }
+
+        Ok(device)
+    }
+
+    /// Handles a registrar request of the microdevice identified by `ctx`.
+    ///
+    /// A provisioned microdevice becomes active the first time it registers.
+    pub(crate) async fn register(mm: &ModelManager, ctx: &Ctx) -> Result<()> {
+        let device = Self::require_operational(mm, ctx).await?;
+
+        if LifecycleState::from_stored(&device.lifecycle_state) != LifecycleState::Provisioned {
+            return Ok(());
+        }
+
+        let txn = mm.db.begin().await?;
+
+        let device = microdevice::Entity::find_by_id(device.id)
+            .lock_exclusive()
+            .one(&txn)
+            .await?;
+
+        // Another transition may have happened since the device was loaded
+        let activated = match device.filter(|d| {
+            LifecycleState::from_stored(&d.lifecycle_state) == LifecycleState::Provisioned
+        }) {
+            Some(device) => Some(
+                Self::apply(
+                    &txn,
+                    device,
+                    LifecycleState::Active,
+                    "registered with the registrar",
+                    None,
+                )
+                .await?,
+            ),
+            None => None,
+        };
+
+        txn.commit().await?;
+
+        if let Some(device) = activated {
+            Self::publish(mm, &device);
+        }
+
+        Ok(())
+    }
+
+    /// Moves a locked microdevice to `target` and records the transition.
+    async fn apply(
+        txn: &DatabaseTransaction,
+        device: microdevice::Model,
+        target: LifecycleState,
+        reason: &str,
+        changed_by: Option<String>,
+    ) -> Result<microdevice::Model> {
+        let current = LifecycleState::from_stored(&device.lifecycle_state);
+
+        if !current.can_transition_to(target) {
+            return Err(Error {
+                kind: ErrorKind::InvalidLifecycleTransition,
+                message: format!(
+                    "microdevice `{}` cannot transition from {} to {}",
+                    device.id,
+                    current.as_ref(),
+                    target.as_ref()
+                ),
+            });
+        }
+
+        microdevice_lifecycle_event::ActiveModel {
+            microdevice_id: Set(device.id),
+            from_state: Set(current.as_ref().to_string()),
+            to_state: Set(target.as_ref().to_string()),
+            reason: Set(reason.to_string()),
+            changed_by: Set(changed_by),
+            changed_at: Set(chrono::Utc::now().fixed_offset()),
+            ..Default::default()
+        }
+        .insert(txn)
+        .await?;
+
+        let mut update = microdevice::ActiveModel::from(device);
+        update.lifecycle_state = Set(target.as_ref().to_string());
+
+        Ok(update.update(txn).await?)
+    }
+
+    /// Notifies live subscribers about the committed state of `device`.
+    fn publish(mm: &ModelManager, device: &microdevice::Model) {
+        mm.events.publish(LiveEvent::DeviceStatus {
+            cluster_id: device.cluster_id,
+            microdevice_id: device.id,
+            state: LifecycleState::from_stored(&device.lifecycle_state),
+        });
+    }
+
+    async fn record(mm: &ModelManager, device: &microdevice::Model) -> Result<LifecycleRecord> {
+        let history = microdevice_lifecycle_event::Entity::find()
+            .filter(microdevice_lifecycle_event::Column::MicrodeviceId.eq(device.id))
+            .order_by_desc(microdevice_lifecycle_event::Column::ChangedAt)
+            .order_by_desc(microdevice_lifecycle_event::Column::Id)
+            .all(&mm.db)
+            .await?;
+
+        Ok(LifecycleRecord {
+            microdevice_id: device.id,
+            state: LifecycleState::from_stored(&device.lifecycle_state),
+            history: history.into_iter().map(Into::into).collect(),
+        })
+    }
+}
@@ -1,13 +1,24 @@
+use super::action_catalog::ActionCatalogBaseModelController as ActionCatalogBMC;
+use super::ampq;
+use super::bus::ActionRoute;
 use super::common::{parse_cluster_id, parse_microdevice_id};
 #[allow(unused_imports)]
-use super::error::{Error, Result};
+use super::error::{Error, ErrorKind, Result};
+use super::lifecycle::LifecycleState;
+use super::topic::validate_topics;
 use super::{cluster::ClusterBaseModelController as ClusterBMC, ModelManager};
+use crate::config;
 use crate::context::Ctx;
-use entity::microdevice;
+use entity::{microdevice, microdevice_action};
+use futures::StreamExt;
 use sea_orm::ActiveValue::Set;
 use sea_orm::{entity::prelude::*, QueryTrait};
 use sea_orm::{EntityTrait, QuerySelect, SelectColumns};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const MAX_LABEL_KEY_LEN: usize = 63;
+const MAX_LABEL_VALUE_LEN: usize = 255;
 
 #[derive(Clone, Deserialize, Serialize)]
 #[serde(untagged)]
@@ -29,6 +40,26 @@ pub enum MicrodeviceAction {
     UserDefined(String),
 }
 
+impl MicrodeviceAction {
+    /// Name of the action as sent to the microdevice
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Start => "start",
+            Self::Stop => "stop",
+            Self::Restart => "restart",
+            Self::Reset => "reset",
+            Self::PowerOn => "power-on",
+            Self::PowerOff => "power-off",
+            Self::UserDefined(name) => name,
+        }
+    }
+
+    /// Built-in actions that interrupt the microdevice until it is handled manually
+    pub fn is_destructive(&self) -> bool {
+        matches!(self, Self::PowerOff | Self::Reset)
+    }
+}
+
 impl From<i32> for MicrodeviceId {
     fn from(id: i32) -> Self {
         Self::Id(id)
@@ -44,15 +75,42 @@ impl From<String> for MicrodeviceId {
 #[derive(Deserialize, Debug, Clone, utoipa::ToSchema)]
 pub struct MicrodeviceCreate {
     #[schema(example = "sensor-1")]
-    name: String,
+    pub name: String,
     #[schema(example = "Sensor 1 in factory-a")]
-    description: Option<String>,
+    pub description: Option<String>,
     #[schema(example = json!([{
         "topic": "/temperature",
         "qos": 1,
         "name": "AHT10 temperature stream"
     }]))]
-    topics: Option<Vec<MicrodeviceTopic>>,
+    pub topics: Option<Vec<MicrodeviceTopic>>,
+    #[schema(example = json!({"site": "factory-a", "kind": "sensor"}))]
+    pub labels: Option<HashMap<String, String>>,
+}
+
+impl MicrodeviceCreate {
+    pub(crate) fn into_active_model(self, cluster_id: Uuid) -> Result<microdevice::ActiveModel> {
+        let mut new_microdevice = microdevice::ActiveModel {
+            name: Set(self.name),
+            cluster_id: Set(cluster_id),
+            lifecycle_state: Set(LifecycleState::Provisioned.as_ref().to_string()),
+            ..Default::default()
+        };
+
+        if let Some(topics) = self.topics {
+            new_microdevice.topics = Set(Some(serde_json::to_value(topics)?));
+        }
+
+        if let Some(description) = self.description {
+            new_microdevice.description = Set(Some(description));
+        }
+
+        if let Some(labels) = self.labels {
+            new_microdevice.labels = Set(serde_json::to_value(labels)?);
+        }
+
+        Ok(new_microdevice)
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, utoipa::ToSchema)]
@@ -70,13 +128,41 @@ pub struct MicrodeviceRecord {
     #[serde(skip_serializing_if = "Option::is_none")]
     cluster_id: Option<Uuid>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    id: Option<i32>,
+    pub(crate) id: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     description: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     topics: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "active")]
+    lifecycle_state: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = json!({"site": "factory-a"}))]
+    labels: Option<serde_json::Value>,
+}
+
+impl MicrodeviceRecord {
+    fn lifecycle_state(&self) -> Option<LifecycleState> {
+        self.lifecycle_state
+            .as_deref()
+            .map(LifecycleState::from_stored)
+    }
+}
+
+impl From<microdevice::Model> for MicrodeviceRecord {
+    fn from(model: microdevice::Model) -> Self {
+        Self {
+            cluster_id: Some(model.cluster_id),
+            id: Some(model.id),
+            name: Some(model.name),
+            description: model.description,
+            topics: model.topics,
+            lifecycle_state: Some(model.lifecycle_state),
+            labels: Some(model.labels),
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -104,7 +190,9 @@ pub struct MicrodeviceUpdateParams {
     cluster_id: Option<String>,
     description: Option<String>,
     name: Option<String>,
-    topics: Option<Vec<String>>,
+    topics: Option<Vec<MicrodeviceTopic>>,
+    /// Replaces every label of the microdevice
+    labels: Option<HashMap<String, String>>,
 }
 
 #[derive(Serialize, utoipa::ToSchema)]
@@ -120,14 +208,107 @@ pub enum DeviceStatus {
 }
 pub struct MicrodeviceBaseModelController {}
 
+/// Result of an action for a single microdevice
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionResultStatus {
+    Success,
+    /// The action is not declared in the catalog of the microdevice
+    Unsupported,
+    /// The microdevice is disabled or decommissioned
+    NotOperational,
+    /// The microdevice exceeded its action limits
+    RateLimited,
+    /// The microdevice did not answer in time
+    Timeout,
+    /// The action could not be handed to the broker
+    BrokerError,
+    /// No queue is bound to receive the action
+    NotRouted,
+    /// The broker refused the action
+    Nacked,
+}
+
+impl ActionResultStatus {
+    /// Stable code reported next to failed results
+    pub fn error_code(self) -> Option<u16> {
+        match self {
+            Self::Success => None,
+            Self::Unsupported => Some(1001),
+            Self::NotOperational => Some(1002),
+            Self::RateLimited => Some(1003),
+            Self::Timeout => Some(1004),
+            Self::BrokerError => Some(1005),
+            Self::NotRouted => Some(1006),
+            Self::Nacked => Some(1007),
+        }
+    }
+}
+
 #[derive(Serialize, utoipa::ToSchema)]
 pub struct MicrodeviceActionResponse {
-    microdevice_id: MicrodeviceId,
-    status: String,
-    message: String,
+    pub(crate) microdevice_id: MicrodeviceId,
+    status: ActionResultStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 1004)]
+    error_code: Option<u16>,
+    pub(crate) message: String,
     payload: serde_json::Value,
 }
 
+impl MicrodeviceActionResponse {
+    fn new(
+        microdevice: &MicrodeviceRecord,
+        status: ActionResultStatus,
+        message: String,
+        payload: serde_json::Value,
+    ) -> Self {
+        Self {
+            microdevice_id: microdevice.id.unwrap().into(),
+            status,
+            error_code: status.error_code(),
+            message,
+            payload,
+        }
+    }
+
+    pub(crate) fn is_success(&self) -> bool {
+        self.status == ActionResultStatus::Success
+    }
+}
+
+/// Microdevices an action is about to be sent to
+pub(crate) struct PreparedAction {
+    pub(crate) targets: Vec<MicrodeviceRecord>,
+    /// Responses for the microdevices the action cannot be sent to
+    pub(crate) rejected: Vec<MicrodeviceActionResponse>,
+    pub(crate) action: MicrodeviceAction,
+}
+
+/// Outcome of an action sent to several microdevices, every microdevice has
+/// its own result
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ActionSummary {
+    /// Number of microdevices the action was meant for
+    total: usize,
+    succeeded: usize,
+    failed: usize,
+    pub(crate) results: Vec<MicrodeviceActionResponse>,
+}
+
+impl ActionSummary {
+    pub(crate) fn new(results: Vec<MicrodeviceActionResponse>) -> Self {
+        let succeeded = results.iter().filter(|r| r.is_success()).count();
+
+        Self {
+            total: results.len(),
+            succeeded,
+            failed: results.len() - succeeded,
+            results,
+        }
+    }
+}
+
 #[derive(Serialize)]
 pub struct MicrodeviceActionMessage {
     cluster_id: String,
@@ -136,6 +317,45 @@ pub struct MicrodeviceActionMessage {
     payload: serde_json::Value,
 }
 
+impl MicrodeviceActionMessage {
+    fn new(
+        microdevice: &MicrodeviceRecord,
+        action: MicrodeviceAction,
+        payload: serde_json::Value,
+    ) -> Self {
+        Self {
+            cluster_id: microdevice.cluster_id.unwrap().to_string(),
+            microdevice_id: microdevice.id.unwrap().into(),
+            action,
+            payload,
+        }
+    }
+}
+
+/// Microdevice an action would be sent to and the message it would receive
+#[derive(Serialize)]
+pub struct ActionDryRunTarget {
+    microdevice: MicrodeviceRecord,
+    message: MicrodeviceActionMessage,
+}
+
+/// Outcome of an action that was checked but not sent
+#[derive(Serialize)]
+pub struct ActionDryRun {
+    targets: Vec<ActionDryRunTarget>,
+    /// Microdevices the action would not be sent to and why
+    skipped: Vec<MicrodeviceActionResponse>,
+}
+
+impl ActionDryRun {
+    pub(crate) fn empty() -> Self {
+        Self {
+            targets: vec![],
+            skipped: vec![],
+        }
+    }
+}
+
 impl From<String> for MicrodeviceAction {
     fn from(action: String) -> Self {
         match action.as_str() {
@@ -158,7 +378,47 @@ impl MicrodeviceBaseModelController {
         microdevice_ids: I,
         action: A,
         payload: serde_json::Value,
-    ) -> Result<Vec<MicrodeviceActionResponse>>
+    ) -> Result<ActionSummary>
+    where
+        I: IntoIterator + Clone,
+        I::IntoIter: ExactSizeIterator,
+        I::Item: Into<MicrodeviceId>,
+        A: Into<MicrodeviceAction> + Clone + Serialize,
+    {
+        let PreparedAction {
+            targets,
+            mut rejected,
+            action,
+        } = Self::prepare_action(mm, ctx, &cluster_id, microdevice_ids, action, &payload).await?;
+
+        // Collect the futures
+        let fut: Vec<_> = targets
+            .into_iter()
+            .map(|rec| Self::transmit_action(mm, rec, action.clone(), payload.clone()))
+            .collect();
+
+        // Execute the futures, bounding how many transmissions run at once
+        let action_reponses: Vec<MicrodeviceActionResponse> = futures::stream::iter(fut)
+            .buffered(config::CONFIG.actions.max_concurrency.max(1))
+            .collect()
+            .await;
+
+        // Combine the not supported and supported responses
+        rejected.extend(action_reponses);
+
+        Ok(ActionSummary::new(rejected))
+    }
+
+    /// Looks up the targeted microdevices and checks the action can be sent to
+    /// them, without transmitting anything yet.
+    pub(crate) async fn prepare_action<I, A>(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_id: &String,
+        microdevice_ids: I,
+        action: A,
+        payload: &serde_json::Value,
+    ) -> Result<PreparedAction>
     where
         I: IntoIterator + Clone,
         I::IntoIter: ExactSizeIterator,
@@ -171,7 +431,7 @@ impl MicrodeviceBaseModelController {
         let microdevice_data = Self::get_microdevice_from_cluster(
             mm,
             ctx,
-            cluster_id,
+            cluster_id.clone(),
             Some(microdevice_ids.clone()),
             None::<Vec<String>>,
             Some(true),
@@ -197,57 +457,176 @@ impl MicrodeviceBaseModelController {
             });
         }
 
+        // Load the catalog entries of user-defined actions
+        let catalog = match &action {
+            MicrodeviceAction::UserDefined(name) => {
+                ActionCatalogBMC::find_for_microdevices(
+                    mm,
+                    microdevice_data.iter().filter_map(|m| m.id),
+                    name,
+                )
+                .await?
+            }
+            _ => HashMap::new(),
+        };
+
         // Partition the microdevices into supported and not supported
-        let (to_process, mut not_supported) =
-            Self::partition_supported_microdevices(&microdevice_data, &action);
+        let (targets, rejected) =
+            Self::partition_supported_microdevices(&microdevice_data, &action, &catalog);
 
-        // Collect the futures
-        let fut: Vec<_> = to_process
+        // Check roles and payload before anything is published
+        let definitions: Vec<&microdevice_action::Model> = targets
+            .iter()
+            .filter_map(|rec| rec.id.and_then(|id| catalog.get(&id)))
+            .collect();
+
+        ActionCatalogBMC::check_invocation(mm, ctx, cluster_id, definitions, payload).await?;
+
+        Ok(PreparedAction {
+            targets,
+            rejected,
+            action,
+        })
+    }
+
+    /// Resolves the targets of an action and validates its payload like
+    /// `trigger_action`, but returns the messages instead of publishing them.
+    pub async fn dry_run_action<I, A>(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_id: String,
+        microdevice_ids: I,
+        action: A,
+        payload: serde_json::Value,
+    ) -> Result<ActionDryRun>
+    where
+        I: IntoIterator + Clone,
+        I::IntoIter: ExactSizeIterator,
+        I::Item: Into<MicrodeviceId>,
+        A: Into<MicrodeviceAction> + Clone + Serialize,
+    {
+        let PreparedAction {
+            targets,
+            rejected,
+            action,
+        } = Self::prepare_action(mm, ctx, &cluster_id, microdevice_ids, action, &payload).await?;
+
+        let targets = targets
             .into_iter()
-            .map(|rec| Self::transmit_action(mm, rec.clone(), action.clone(), payload.clone()))
+            .map(|microdevice| ActionDryRunTarget {
+                message: MicrodeviceActionMessage::new(
+                    &microdevice,
+                    action.clone(),
+                    payload.clone(),
+                ),
+                microdevice,
+            })
             .collect();
 
-        // Execute the futures
-        let action_reponses: Result<Vec<MicrodeviceActionResponse>> =
-            futures::future::join_all(fut).await.into_iter().collect();
+        Ok(ActionDryRun {
+            targets,
+            skipped: rejected,
+        })
+    }
+
+    /// Sends an action to every operational microdevice of the cluster.
+    ///
+    /// Destructive actions are refused unless `confirmed` is set.
+    pub async fn trigger_cluster_action<A>(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_id: String,
+        action: A,
+        payload: serde_json::Value,
+        confirmed: bool,
+    ) -> Result<ActionSummary>
+    where
+        A: Into<MicrodeviceAction> + Clone + Serialize,
+    {
+        let action: MicrodeviceAction = action.into();
+        let eligible = Self::cluster_action_targets(mm, ctx, &cluster_id, &action, confirmed).await?;
 
-        // Combine the not supported and supported responses
-        not_supported.extend(action_reponses?);
+        if eligible.is_empty() {
+            return Ok(ActionSummary::new(vec![]));
+        }
+
+        Self::trigger_action(mm, ctx, cluster_id, eligible, action, payload).await
+    }
+
+    /// Ids of the operational microdevices of the cluster a cluster-wide action is sent to.
+    pub(crate) async fn cluster_action_targets(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_id: &String,
+        action: &MicrodeviceAction,
+        confirmed: bool,
+    ) -> Result<Vec<i32>> {
+        if action.is_destructive() && !confirmed {
+            return Err(Error {
+                kind: super::error::ErrorKind::ConfirmationRequired,
+                message: format!(
+                    "action `{}` targets every microdevice of the cluster and requires `confirm: true`",
+                    action.name()
+                ),
+            });
+        }
 
-        Ok(not_supported)
+        ClusterBMC::exists(mm, ctx, cluster_id.clone()).await?;
+
+        Ok(microdevice::Entity::find()
+            .select_only()
+            .column(microdevice::Column::Id)
+            .filter(microdevice::Column::ClusterId.eq(parse_cluster_id(cluster_id)?))
+            .filter(microdevice::Column::LifecycleState.is_in([
+                LifecycleState::Provisioned.as_ref(),
+                LifecycleState::Active.as_ref(),
+            ]))
+            .into_tuple()
+            .all(&mm.db)
+            .await?)
     }
 
     fn partition_supported_microdevices(
         microdevices: &Vec<MicrodeviceRecord>,
         action: &MicrodeviceAction,
+        catalog: &HashMap<i32, microdevice_action::Model>,
     ) -> (Vec<MicrodeviceRecord>, Vec<MicrodeviceActionResponse>) {
         let mut not_supported: Vec<MicrodeviceActionResponse> = vec![];
         let mut to_process: Vec<MicrodeviceRecord> = vec![];
 
         for microdevice in microdevices {
-            if Self::is_action_supported(microdevice, action) {
-                to_process.push(microdevice.clone());
-            } else {
-                not_supported.push(Self::unsupported_action_response(microdevice, action));
+            // Disabled and decommissioned microdevices are never targeted
+            match microdevice.lifecycle_state() {
+                Some(state) if !state.is_operational() => {
+                    not_supported.push(MicrodeviceActionResponse::new(
+                        microdevice,
+                        ActionResultStatus::NotOperational,
+                        format!(
+                            "microdevice `{}` is {}",
+                            microdevice.name.clone().unwrap(),
+                            state.as_ref()
+                        ),
+                        serde_json::Value::Null,
+                    ));
+                }
+                _ if Self::is_action_supported(microdevice, action, catalog) => {
+                    to_process.push(microdevice.clone());
+                }
+                _ => not_supported.push(Self::unsupported_action_response(microdevice, action)),
             }
         }
 
         (to_process, not_supported)
     }
 
-    fn is_action_supported(rec: &MicrodeviceRecord, action: &MicrodeviceAction) -> bool {
-        // Check if the user-defined action is supported by the microdevice
-        if let MicrodeviceAction::UserDefined(action) = action {
-            if let Some(topics) = &rec.topics {
-                if let Some(topic_array) = topics.as_array() {
-                    return topic_array
-                        .iter()
-                        .any(|v| v.as_str().unwrap_or_default().to_string() == *action);
-                }
-            }
-
-            // If the microdevice has no topics or the topics are malformed
-            return false;
+    fn is_action_supported(
+        rec: &MicrodeviceRecord,
+        action: &MicrodeviceAction,
+        catalog: &HashMap<i32, microdevice_action::Model>,
+    ) -> bool {
+        // User-defined actions have to be declared in the action catalog of the microdevice
+        if let MicrodeviceAction::UserDefined(_) = action {
+            return rec.id.is_some_and(|id| catalog.contains_key(&id));
         }
 
         // Execute the Default actions
@@ -259,56 +638,99 @@ impl MicrodeviceBaseModelController {
         action: &MicrodeviceAction,
     ) -> MicrodeviceActionResponse {
         let message = match action {
-            MicrodeviceAction::UserDefined(action_name) => {
-                if microdevice.topics.is_none() {
-                    format!(
-                        "microdevice `{}` has no user-defined topics",
-                        microdevice.name.clone().unwrap()
-                    )
-                } else {
-                    format!(
-                        "microdevice `{}` does not support action `{}`",
-                        microdevice.name.clone().unwrap(),
-                        action_name
-                    )
-                }
-            },
+            MicrodeviceAction::UserDefined(action_name) => format!(
+                "microdevice `{}` does not support action `{}`",
+                microdevice.name.clone().unwrap(),
+                action_name
+            ),
             _ => panic!("It should not be possible to reach this point as the default actions are always supported."),
         };
 
-        MicrodeviceActionResponse {
-            microdevice_id: microdevice.id.unwrap().into(),
-            status: "error".to_string(),
+        MicrodeviceActionResponse::new(
+            microdevice,
+            ActionResultStatus::Unsupported,
             message,
-            payload: serde_json::Value::Null,
-        }
+            serde_json::Value::Null,
+        )
     }
 
+    /// Sends the action to a single microdevice, failures only affect the
+    /// result of this microdevice.
     async fn transmit_action(
         mm: &ModelManager,
         microdevice: MicrodeviceRecord,
         action: MicrodeviceAction,
         payload: serde_json::Value,
-    ) -> Result<MicrodeviceActionResponse> {
-        // Create the action message
-        let action_message = MicrodeviceActionMessage {
+    ) -> MicrodeviceActionResponse {
+        let (status, message, payload) =
+            match Self::send_action(mm, &microdevice, action, payload).await {
+                Ok(res) => (
+                    ActionResultStatus::Success,
+                    "action was successfully transmitted".to_string(),
+                    serde_json::to_string(&res).unwrap().into(),
+                ),
+                Err(e) => {
+                    let status = match e.kind {
+                        ErrorKind::RateLimited => ActionResultStatus::RateLimited,
+                        ErrorKind::AmpqError(ampq::error::Error::ResponseTimeout) => {
+                            ActionResultStatus::Timeout
+                        }
+                        ErrorKind::AmpqError(ampq::error::Error::NotRouted(_)) => {
+                            ActionResultStatus::NotRouted
+                        }
+                        ErrorKind::AmpqError(ampq::error::Error::Nacked) => {
+                            ActionResultStatus::Nacked
+                        }
+                        _ => ActionResultStatus::BrokerError,
+                    };
+
+                    (status, e.message, serde_json::Value::Null)
+                }
+            };
+
+        MicrodeviceActionResponse::new(&microdevice, status, message, payload)
+    }
+
+    /// Publishes an action for a single microdevice and waits for the reply of the broker.
+    ///
+    /// Fails with `RateLimited` when the microdevice exceeds the configured
+    /// action limits and with a response timeout when it does not answer
+    /// within `actions.device_timeout` seconds.
+    pub(crate) async fn send_action(
+        mm: &ModelManager,
+        microdevice: &MicrodeviceRecord,
+        action: MicrodeviceAction,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        // Held until the microdevice answered
+        let _permit = mm.action_limiter.acquire(microdevice.id.unwrap())?;
+
+        // Gateways only receive the actions of the clusters they serve
+        let route = ActionRoute {
             cluster_id: microdevice.cluster_id.unwrap().to_string(),
-            microdevice_id: microdevice.id.unwrap().into(),
-            action,
-            payload,
+            microdevice_id: microdevice.id.unwrap(),
+            action: action.name().to_string(),
         };
 
+        // Create the action message
+        let action_message = MicrodeviceActionMessage::new(microdevice, action, payload);
+
         // Serilize the action message
         let action_payload = serde_json::to_value(action_message)?;
 
-        let res = mm.ampq_bridge.transmit_action(action_payload).await?;
+        // Bounds the retries of the broker as well, the action expires then
+        let timeout = std::time::Duration::from_secs(config::CONFIG.actions.device_timeout);
+        let deadline = chrono::Utc::now() + timeout;
 
-        Ok(MicrodeviceActionResponse {
-            microdevice_id: microdevice.id.unwrap().into(),
-            status: "success".to_string(),
-            message: "action was successfully transmitted".to_string(),
-            payload: serde_json::to_string(&res).unwrap().into(),
-        })
+        match tokio::time::timeout(
+            timeout,
+            mm.bus.transmit_action(&route, action_payload, deadline),
+        )
+        .await
+        {
+            Ok(res) => Ok(res?),
+            Err(_) => Err(ampq::error::Error::ResponseTimeout.into()),
+        }
     }
 
     pub async fn get_microdevice_from_cluster<I, S>(
@@ -334,6 +756,8 @@ impl MicrodeviceBaseModelController {
             .select_only()
             .select_column(microdevice::Column::Id)
             .select_column(microdevice::Column::Name)
+            .select_column(microdevice::Column::LifecycleState)
+            .select_column(microdevice::Column::Labels)
             .filter(microdevice::Column::ClusterId.eq(parse_cluster_id(&cluster_uuid)?))
             .apply_if(microdevice_id, |q, v| {
                 q.filter(
@@ -382,29 +806,20 @@ impl MicrodeviceBaseModelController {
     ) -> Result<MicrodeviceRecord> {
         ClusterBMC::exists(mm, ctx, cluster_uuid.clone()).await?;
 
-        let mut new_microdevice = microdevice::ActiveModel {
-            name: Set(microdevice.name),
-            cluster_id: Set(parse_cluster_id(&cluster_uuid)?),
-            ..Default::default()
-        };
-
-        microdevice.topics.map(|v| {
-            new_microdevice.topics = Set(Some(serde_json::to_value(v).unwrap()));
-        });
+        if let Some(topics) = &microdevice.topics {
+            validate_topics(topics)?;
+        }
 
-        microdevice.description.map(|v| {
-            new_microdevice.description = Set(Some(v));
-        });
+        if let Some(labels) = &microdevice.labels {
+            validate_labels(labels)?;
+        }
 
-        let new_microdevice = new_microdevice.insert(&mm.db).await?;
+        let new_microdevice = microdevice
+            .into_active_model(parse_cluster_id(&cluster_uuid)?)?
+            .insert(&mm.db)
+            .await?;
 
-        Ok(MicrodeviceRecord {
-            cluster_id: Some(new_microdevice.cluster_id),
-            id: Some(new_microdevice.id),
-            name: Some(new_microdevice.name),
-            description: new_microdevice.description,
-            topics: new_microdevice.topics,
-        })
+        Ok(new_microdevice.into())
     }
 
     #[allow(unused_variables)]
@@ -450,6 +865,14 @@ impl MicrodeviceBaseModelController {
     ) -> Result<MicrodeviceRecord> {
         ClusterBMC::exists(mm, ctx, cluster_uuid.clone()).await?;
 
+        if let Some(topics) = &params.topics {
+            validate_topics(topics)?;
+        }
+
+        if let Some(labels) = &params.labels {
+            validate_labels(labels)?;
+        }
+
         let target = microdevice::Entity::find()
             .filter(microdevice::Column::ClusterId.eq(parse_cluster_id(&cluster_uuid)?))
             .filter(microdevice::Column::Id.eq(parse_microdevice_id(microdevice_id)?))
@@ -475,22 +898,18 @@ impl MicrodeviceBaseModelController {
                     .topics
                     .map(|v| update.topics = Set(Some(serde_json::to_value(v).unwrap())));
 
+                if let Some(labels) = params.labels {
+                    update.labels = Set(serde_json::to_value(labels)?);
+                }
+
                 let res = update.update(&mm.db).await?;
 
-                return Ok(MicrodeviceRecord {
-                    cluster_id: Some(res.cluster_id),
-                    id: Some(res.id),
-                    name: Some(res.name),
-                    description: res.description,
-                    topics: res.topics,
-                });
-            }
-            None => {
-                return Err(Error {
-                    kind: super::error::ErrorKind::MicrodeviceNotFound,
-                    message: "failed to update microdevice because it was not found".to_string(),
-                })
+                Ok(res.into())
             }
+            None => Err(Error {
+                kind: super::error::ErrorKind::MicrodeviceNotFound,
+                message: "failed to update microdevice because it was not found".to_string(),
+            }),
         }
     }
 
@@ -498,6 +917,39 @@ impl MicrodeviceBaseModelController {
         ctx: &Ctx,
         mm: &ModelManager,
     ) -> Result<Option<MicrodeviceRecord>> {
+        let microdevice_id = Self::microdevice_id_from_ctx(ctx)?;
+
+        let rec: Option<MicrodeviceRecord> = microdevice::Entity::find_by_id(microdevice_id)
+            .into_model()
+            .one(&mm.db)
+            .await?;
+
+        Ok(rec)
+    }
+
+    /// Fetches a single microdevice, making sure it belongs to a cluster the
+    /// user context has access to.
+    pub(crate) async fn find_in_cluster(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: &String,
+        microdevice_id: i32,
+    ) -> Result<microdevice::Model> {
+        ClusterBMC::exists(mm, ctx, cluster_uuid.clone()).await?;
+
+        microdevice::Entity::find()
+            .filter(microdevice::Column::ClusterId.eq(parse_cluster_id(cluster_uuid)?))
+            .filter(microdevice::Column::Id.eq(parse_microdevice_id(microdevice_id)?))
+            .one(&mm.db)
+            .await?
+            .ok_or(Error {
+                kind: super::error::ErrorKind::MicrodeviceNotFound,
+                message: format!("microdevice `{}` not found", microdevice_id),
+            })
+    }
+
+    /// Extracts the numeric microdevice id from a microdevice context.
+    pub(crate) fn microdevice_id_from_ctx(ctx: &Ctx) -> Result<i32> {
         let microdevice_id = match ctx.get_microdevice_ids() {
             Some(v) => v,
             None => {
@@ -509,21 +961,120 @@ impl MicrodeviceBaseModelController {
         };
 
         // convet microdevice id to integer
-        let microdevice_id = match microdevice_id.0.parse::<i32>() {
-            Ok(v) => v,
-            Err(_) => {
-                return Err(Error {
-                    kind: super::error::ErrorKind::InvalidContext,
-                    message: "invalid context".to_string(),
-                })
-            }
-        };
+        match microdevice_id.0.parse::<i32>() {
+            Ok(v) => Ok(v),
+            Err(_) => Err(Error {
+                kind: super::error::ErrorKind::InvalidContext,
+                message: "invalid context".to_string(),
+            }),
+        }
+    }
+}
 
-        let rec: Option<MicrodeviceRecord> = microdevice::Entity::find_by_id(microdevice_id)
-            .into_model()
-            .one(&mm.db)
-            .await?;
+/// Validates the labels of a microdevice, keys have to be short identifiers.
+pub(crate) fn validate_labels(labels: &HashMap<String, String>) -> Result<()> {
+    for (key, value) in labels {
+        let valid_key = !key.is_empty()
+            && key.len() <= MAX_LABEL_KEY_LEN
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'));
 
-        Ok(rec)
+        if !valid_key {
+            return Err(Error {
+                kind: super::error::ErrorKind::InvalidLabel,
+                message: format!(
+                    "label key `{}` must be 1 to {} characters of letters, digits, `-`, `_`, `.` or `/`",
+                    key, MAX_LABEL_KEY_LEN
+                ),
+            });
+        }
+
+        if value.len() > MAX_LABEL_VALUE_LEN {
+            return Err(Error {
+                kind: super::error::ErrorKind::InvalidLabel,
+                message: format!(
+                    "value of label `{}` must not exceed {} characters",
+                    key, MAX_LABEL_VALUE_LEN
+                ),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{FakeDeviceError, FakeDeviceRule};
+    use crate::model::bus::in_process::{FakeDeviceResponder, InProcessBus};
+    use serde_json::json;
+
+    fn microdevice(id: i32) -> MicrodeviceRecord {
+        MicrodeviceRecord {
+            cluster_id: Some(Uuid::nil()),
+            id: Some(id),
+            name: Some(format!("sensor-{}", id)),
+            description: None,
+            topics: None,
+            lifecycle_state: Some(LifecycleState::Active.as_ref().to_string()),
+            labels: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn actions_are_answered_by_the_fake_device() {
+        let mm = ModelManager::in_process(InProcessBus::new(FakeDeviceResponder::default()));
+
+        let reply = MicrodeviceBaseModelController::send_action(
+            &mm,
+            &microdevice(1),
+            MicrodeviceAction::Restart,
+            json!({ "delay": 5 }),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            reply,
+            json!({ "status": "ok", "action": "restart", "payload": { "delay": 5 } })
+        );
+    }
+
+    #[tokio::test]
+    async fn summaries_report_the_result_of_every_microdevice() {
+        let mm = ModelManager::in_process(InProcessBus::new(FakeDeviceResponder::default()));
+
+        mm.in_process_bus
+            .as_ref()
+            .unwrap()
+            .responder()
+            .script(FakeDeviceRule {
+                action: None,
+                microdevice_id: Some(2),
+                reply: None,
+                error: Some(FakeDeviceError::Timeout),
+                delay_ms: None,
+            });
+
+        let mut results = vec![];
+        for id in [1, 2] {
+            results.push(
+                MicrodeviceBaseModelController::transmit_action(
+                    &mm,
+                    microdevice(id),
+                    MicrodeviceAction::PowerOn,
+                    json!({}),
+                )
+                .await,
+            );
+        }
+
+        let summary = ActionSummary::new(results);
+
+        assert_eq!((summary.total, summary.succeeded, summary.failed), (2, 1, 1));
+        assert_eq!(summary.results[1].status, ActionResultStatus::Timeout);
+        assert_eq!(summary.results[1].error_code, Some(1004));
     }
 }
@@ -1,10 +1,26 @@
 use super::config;
 use futures::executor::block_on;
+pub mod action_catalog;
+pub mod action_job;
 mod ampq;
+pub mod bulk_import;
+pub mod bus;
 pub mod cluster;
-mod common;
+pub(crate) mod common;
+pub mod dead_letter;
+pub mod device_group;
 pub mod error;
+pub mod event_bus;
+pub mod fake_device;
+pub mod health;
+pub mod idempotency;
+pub mod lifecycle;
 pub mod microdevice;
+mod rate_limit;
+pub mod schedule;
+pub mod shadow;
+pub mod telemetry;
+pub mod topic;
 #[allow(unused_imports)]
 use error::{Error, Result};
 use tracing::{info, debug, error};
@@ -12,7 +28,13 @@ use tracing::{info, debug, error};
 #[derive(Clone)]
 pub struct ModelManager {
     pub(crate) db: sea_orm::DatabaseConnection,
-    pub(crate) ampq_bridge: ampq::MessageBroker,
+    pub(crate) bus: std::sync::Arc<dyn bus::MessageBus>,
+    /// Same bus as `bus` when `message_bus.kind` is `in_process`
+    pub(crate) in_process_bus: Option<bus::in_process::InProcessBus>,
+    pub(crate) events: event_bus::EventBus,
+    pub(crate) action_limiter: rate_limit::ActionLimiter,
+    /// Tells apart the instances of the API sharing the database
+    pub(crate) instance_id: String,
 }
 
 impl ModelManager {
@@ -30,11 +52,29 @@ impl ModelManager {
             }
         };
 
-        let msg_broker = ampq::MessageBroker::new().await;
+        let (bus, in_process_bus) = bus::from_config();
 
         Self {
             db: sea_orm_db,
-            ampq_bridge: msg_broker,
+            bus,
+            in_process_bus,
+            events: event_bus::EventBus::new(config::CONFIG.rpc.event_buffer),
+            action_limiter: rate_limit::ActionLimiter::default(),
+            instance_id: uuid::Uuid::new_v4().to_string(),
+        }
+    }
+
+    /// Model manager on the in-process bus without a database, for the tests
+    /// of the code that only talks to the microdevices.
+    #[cfg(test)]
+    pub(crate) fn in_process(bus: bus::in_process::InProcessBus) -> Self {
+        Self {
+            db: sea_orm::DatabaseConnection::Disconnected,
+            bus: std::sync::Arc::new(bus.clone()),
+            in_process_bus: Some(bus),
+            events: event_bus::EventBus::new(config::CONFIG.rpc.event_buffer),
+            action_limiter: rate_limit::ActionLimiter::default(),
+            instance_id: uuid::Uuid::new_v4().to_string(),
         }
     }
 }
@@ -0,0 +1,235 @@
+use super::error::{Error, ErrorKind, Result};
+use crate::config::CONFIG;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Window of the per microdevice action rate
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Default)]
+struct DeviceUsage {
+    /// Start of the actions sent within the rate window, oldest first
+    sent: VecDeque<Instant>,
+    in_flight: usize,
+}
+
+impl DeviceUsage {
+    /// Whether the microdevice handles no action and sent none within the window
+    fn is_idle(&self, now: Instant) -> bool {
+        self.in_flight == 0
+            && self
+                .sent
+                .back()
+                .is_none_or(|sent| now.duration_since(*sent) >= RATE_WINDOW)
+    }
+}
+
+struct Usage {
+    devices: HashMap<i32, DeviceUsage>,
+    /// Last time idle microdevices were forgotten
+    swept_at: Instant,
+}
+
+impl Default for Usage {
+    fn default() -> Self {
+        Self {
+            devices: HashMap::new(),
+            swept_at: Instant::now(),
+        }
+    }
+}
+
+/// Limits how often and how many actions at once are sent to a microdevice
+///
+/// Limits are configured in `actions` and apply to the current process.
+#[derive(Clone)]
+pub(crate) struct ActionLimiter {
+    usage: Arc<Mutex<Usage>>,
+    /// Actions a microdevice handles at once, 0 disables the limit
+    max_in_flight: usize,
+    /// Actions a microdevice receives within the window, 0 disables the limit
+    max_per_window: usize,
+}
+
+impl Default for ActionLimiter {
+    fn default() -> Self {
+        Self::new(
+            CONFIG.actions.max_in_flight_per_device,
+            CONFIG.actions.max_per_device_per_minute,
+        )
+    }
+}
+
+impl ActionLimiter {
+    fn new(max_in_flight: usize, max_per_window: usize) -> Self {
+        Self {
+            usage: Arc::new(Mutex::new(Usage::default())),
+            max_in_flight,
+            max_per_window,
+        }
+    }
+
+    /// Reserves an action for the microdevice, the permit has to be held until
+    /// the microdevice answered.
+    pub(crate) fn acquire(&self, microdevice_id: i32) -> Result<ActionPermit> {
+        self.acquire_at(microdevice_id, Instant::now())
+    }
+
+    fn acquire_at(&self, microdevice_id: i32, now: Instant) -> Result<ActionPermit> {
+        let mut usage = self.usage.lock().map_err(|_| Error {
+            kind: ErrorKind::RateLimited,
+            message: "action limits are unavailable".to_string(),
+        })?;
+
+        // Microdevices that stopped receiving actions are forgotten once per
+        // window, releasing a permit only forgets its own microdevice
+        if now.duration_since(usage.swept_at) >= RATE_WINDOW {
+            usage.devices.retain(|_, device| !device.is_idle(now));
+            usage.swept_at = now;
+        }
+
+        let device = usage.devices.entry(microdevice_id).or_default();
+
+        while device
+            .sent
+            .front()
+            .is_some_and(|sent| now.duration_since(*sent) >= RATE_WINDOW)
+        {
+            device.sent.pop_front();
+        }
+
+        if self.max_in_flight > 0 && device.in_flight >= self.max_in_flight {
+            return Err(Error {
+                kind: ErrorKind::RateLimited,
+                message: format!(
+                    "microdevice `{}` is still handling {} action(s)",
+                    microdevice_id, device.in_flight
+                ),
+            });
+        }
+
+        if self.max_per_window > 0 && device.sent.len() >= self.max_per_window {
+            return Err(Error {
+                kind: ErrorKind::RateLimited,
+                message: format!(
+                    "microdevice `{}` received {} actions within the last minute",
+                    microdevice_id,
+                    device.sent.len()
+                ),
+            });
+        }
+
+        device.sent.push_back(now);
+        device.in_flight += 1;
+
+        Ok(ActionPermit {
+            usage: self.usage.clone(),
+            microdevice_id,
+        })
+    }
+}
+
+/// Action in flight, released when dropped
+pub(crate) struct ActionPermit {
+    usage: Arc<Mutex<Usage>>,
+    microdevice_id: i32,
+}
+
+impl Drop for ActionPermit {
+    fn drop(&mut self) {
+        let Ok(mut usage) = self.usage.lock() else {
+            return;
+        };
+
+        if let Some(device) = usage.devices.get_mut(&self.microdevice_id) {
+            device.in_flight = device.in_flight.saturating_sub(1);
+
+            // Forget idle microdevices once their window has passed
+            if device.is_idle(Instant::now()) {
+                usage.devices.remove(&self.microdevice_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracked(limiter: &ActionLimiter) -> usize {
+        limiter.usage.lock().unwrap().devices.len()
+    }
+
+    #[test]
+    fn in_flight_actions_are_limited_until_released() {
+        let limiter = ActionLimiter::new(1, 0);
+
+        let permit = limiter.acquire(1).unwrap();
+        let err = limiter.acquire(1).err().unwrap();
+        assert!(matches!(err.kind, ErrorKind::RateLimited));
+
+        // Other microdevices are limited on their own
+        let _other = limiter.acquire(2).unwrap();
+
+        drop(permit);
+        assert!(limiter.acquire(1).is_ok());
+    }
+
+    #[test]
+    fn actions_per_window_are_limited_until_the_window_passed() {
+        let limiter = ActionLimiter::new(0, 2);
+        let start = Instant::now();
+
+        let _first = limiter.acquire_at(1, start).unwrap();
+        let _second = limiter
+            .acquire_at(1, start + Duration::from_secs(30))
+            .unwrap();
+
+        let err = limiter
+            .acquire_at(1, start + Duration::from_secs(59))
+            .err()
+            .unwrap();
+        assert!(err.message.contains("2 actions within the last minute"));
+
+        // The first action left the window, the second one still counts
+        assert!(limiter.acquire_at(1, start + RATE_WINDOW).is_ok());
+        assert!(limiter
+            .acquire_at(1, start + Duration::from_secs(61))
+            .is_err());
+    }
+
+    #[test]
+    fn zero_disables_each_limit() {
+        let limiter = ActionLimiter::new(0, 0);
+
+        let permits: Vec<_> = (0..100).map(|_| limiter.acquire(1).unwrap()).collect();
+        assert_eq!(permits.len(), 100);
+
+        let per_window_only = ActionLimiter::new(0, 3);
+        let permits: Vec<_> = (0..3)
+            .map(|_| per_window_only.acquire(1).unwrap())
+            .collect();
+        assert!(per_window_only.acquire(1).is_err());
+        drop(permits);
+
+        let in_flight_only = ActionLimiter::new(2, 0);
+        for _ in 0..100 {
+            drop(in_flight_only.acquire(1).unwrap());
+        }
+    }
+
+    #[test]
+    fn idle_microdevices_are_forgotten() {
+        let limiter = ActionLimiter::new(1, 1);
+        let start = Instant::now();
+
+        // Released within the window, so the microdevice is still tracked
+        drop(limiter.acquire_at(1, start).unwrap());
+        assert!(limiter.acquire_at(1, start).is_err());
+        assert_eq!(tracked(&limiter), 1);
+
+        let _permit = limiter.acquire_at(2, start + RATE_WINDOW * 2).unwrap();
+        assert_eq!(tracked(&limiter), 1);
+    }
+}
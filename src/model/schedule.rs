@@ -0,0 +1,626 @@
+use super::cluster::{ClusterBaseModelController as ClusterBMC, ClusterRole};
+use super::common::parse_cluster_id;
+use super::device_group::DeviceGroupBaseModelController as DeviceGroupBMC;
+use super::error::{Error, ErrorKind, Result};
+use super::microdevice::MicrodeviceBaseModelController as MicrodeviceBMC;
+use super::ModelManager;
+use crate::context::Ctx;
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use entity::action_schedule;
+use sea_orm::ActiveValue::Set;
+use sea_orm::{entity::prelude::*, QueryOrder, SqlErr};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::str::FromStr;
+
+const MAX_SCHEDULE_NAME_LEN: usize = 64;
+
+/// A new schedule, exactly one of `microdevice_ids` and `group` and exactly
+/// one of `cron` and `run_at` have to be given.
+#[derive(Deserialize, utoipa::ToSchema, Debug)]
+pub struct ScheduleCreate {
+    #[schema(example = "weekly-gateway-restart")]
+    name: String,
+    #[schema(example = "restart")]
+    action: String,
+    #[schema(example = json!({}))]
+    payload: Option<Value>,
+    /// Microdevices the action is sent to
+    #[schema(example = json!([1, 2]))]
+    microdevice_ids: Option<Vec<i32>>,
+    /// Device group whose members at the time of each run are targeted
+    #[schema(example = "gateways")]
+    group: Option<String>,
+    /// Recurring runs as `minute hour day-of-month month day-of-week`, a
+    /// leading seconds field is accepted as well. Days of the week are
+    /// numbered from 0 to 7 with Sunday as 0 or 7, or named
+    #[schema(example = "0 3 * * Sun")]
+    cron: Option<String>,
+    /// Single run, either in RFC 3339 or as a local time in `timezone`
+    #[schema(example = "2024-12-07T18:00:00")]
+    run_at: Option<String>,
+    /// IANA time zone the schedule is evaluated in, defaults to UTC
+    #[schema(example = "Europe/Berlin")]
+    timezone: Option<String>,
+}
+
+#[derive(Serialize, utoipa::ToSchema, Debug)]
+pub struct ScheduleRecord {
+    id: i32,
+    #[schema(example = "weekly-gateway-restart")]
+    name: String,
+    #[schema(example = "restart")]
+    action: String,
+    #[schema(example = json!({}))]
+    payload: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    microdevice_ids: Option<Vec<i32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "0 3 * * Sun")]
+    cron: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    run_at: Option<DateTimeWithTimeZone>,
+    #[schema(example = "Europe/Berlin")]
+    timezone: String,
+    paused: bool,
+    /// Next time the action is sent, empty once a single run happened
+    #[schema(value_type = Option<String>, format = DateTime)]
+    next_run_at: Option<DateTimeWithTimeZone>,
+    #[schema(value_type = Option<String>, format = DateTime)]
+    last_run_at: Option<DateTimeWithTimeZone>,
+    /// Outcome of the last run, `success` or `failed`
+    #[schema(example = "success")]
+    last_status: Option<String>,
+    last_error: Option<String>,
+    /// User the action is sent on behalf of
+    created_by: String,
+}
+
+impl From<action_schedule::Model> for ScheduleRecord {
+    fn from(schedule: action_schedule::Model) -> Self {
+        Self {
+            id: schedule.id,
+            name: schedule.name,
+            action: schedule.action,
+            payload: schedule.payload,
+            microdevice_ids: schedule
+                .microdevice_ids
+                .and_then(|ids| serde_json::from_value(ids).ok()),
+            group: schedule.group_name,
+            cron: schedule.cron,
+            run_at: schedule.run_at,
+            timezone: schedule.timezone,
+            paused: schedule.paused,
+            next_run_at: schedule.next_run_at,
+            last_run_at: schedule.last_run_at,
+            last_status: schedule.last_status,
+            last_error: schedule.last_error,
+            created_by: schedule.created_by,
+        }
+    }
+}
+
+pub struct ScheduleBaseModelController {}
+
+impl ScheduleBaseModelController {
+    pub async fn list_schedules(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+    ) -> Result<Vec<ScheduleRecord>> {
+        ClusterBMC::exists(mm, ctx, cluster_uuid.clone()).await?;
+
+        Ok(action_schedule::Entity::find()
+            .filter(action_schedule::Column::ClusterId.eq(parse_cluster_id(&cluster_uuid)?))
+            .order_by_asc(action_schedule::Column::Id)
+            .all(&mm.db)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    pub async fn get_schedule(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        schedule_id: i32,
+    ) -> Result<ScheduleRecord> {
+        Ok(Self::find_schedule(mm, ctx, &cluster_uuid, schedule_id)
+            .await?
+            .into())
+    }
+
+    pub async fn create_schedule(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        schedule: ScheduleCreate,
+    ) -> Result<ScheduleRecord> {
+        ClusterBMC::exists(mm, ctx, cluster_uuid.clone()).await?;
+        Self::require_scheduler(mm, ctx, &cluster_uuid).await?;
+
+        let cluster_id = parse_cluster_id(&cluster_uuid)?;
+        let created_by = ctx.get_user_id().cloned().ok_or(Error {
+            kind: ErrorKind::InvalidSchedule,
+            message: "schedules can only be created by users".to_string(),
+        })?;
+
+        let name = schedule.name.trim().to_string();
+        if name.is_empty() || name.len() > MAX_SCHEDULE_NAME_LEN {
+            return Err(invalid(format!(
+                "name must be between 1 and {} characters",
+                MAX_SCHEDULE_NAME_LEN
+            )));
+        }
+
+        let tz = parse_timezone(schedule.timezone.as_deref().unwrap_or("UTC"))?;
+        let now = Utc::now();
+
+        // Targets are checked now, group members are resolved again on every run
+        let microdevice_ids = match (schedule.microdevice_ids, &schedule.group) {
+            (Some(ids), None) if !ids.is_empty() => {
+                for id in &ids {
+                    MicrodeviceBMC::find_in_cluster(mm, ctx, &cluster_uuid, *id).await?;
+                }
+                Some(serde_json::to_value(ids)?)
+            }
+            (None, Some(group)) => {
+                DeviceGroupBMC::resolve_member_ids(mm, ctx, &cluster_uuid, group).await?;
+                None
+            }
+            _ => {
+                return Err(invalid(
+                    "a schedule needs either a non-empty `microdevice_ids` or `group`".to_string(),
+                ))
+            }
+        };
+
+        let (cron, run_at, next_run_at) = match (schedule.cron, schedule.run_at) {
+            (Some(expression), None) => {
+                let cron = parse_cron(&expression)?;
+                let next_run_at = next_cron_run(&cron, tz, now);
+                (Some(expression.trim().to_string()), None, next_run_at)
+            }
+            (None, Some(run_at)) => {
+                let run_at = parse_run_at(&run_at, tz)?;
+                if run_at <= now {
+                    return Err(invalid("`run_at` must be in the future".to_string()));
+                }
+                (None, Some(run_at), Some(run_at))
+            }
+            _ => {
+                return Err(invalid(
+                    "a schedule needs either `cron` or `run_at`".to_string(),
+                ))
+            }
+        };
+
+        let res = action_schedule::ActiveModel {
+            cluster_id: Set(cluster_id),
+            name: Set(name.clone()),
+            action: Set(schedule.action),
+            payload: Set(schedule.payload.unwrap_or_default()),
+            microdevice_ids: Set(microdevice_ids),
+            group_name: Set(schedule.group),
+            cron: Set(cron),
+            run_at: Set(run_at),
+            timezone: Set(tz.name().to_string()),
+            paused: Set(false),
+            next_run_at: Set(next_run_at),
+            created_by: Set(created_by),
+            created_at: Set(now.fixed_offset()),
+            ..Default::default()
+        }
+        .insert(&mm.db)
+        .await;
+
+        match res {
+            Ok(model) => Ok(model.into()),
+            Err(e) if matches!(e.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+                Err(Error {
+                    kind: ErrorKind::ScheduleAlreadyExists,
+                    message: format!("schedule `{}` already exists", name),
+                })
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Stops a schedule from running until it is resumed.
+    pub async fn pause_schedule(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        schedule_id: i32,
+    ) -> Result<ScheduleRecord> {
+        let schedule = Self::find_schedule(mm, ctx, &cluster_uuid, schedule_id).await?;
+        Self::require_scheduler(mm, ctx, &cluster_uuid).await?;
+
+        let mut model = action_schedule::ActiveModel::from(schedule);
+        model.paused = Set(true);
+
+        Ok(model.update(&mm.db).await?.into())
+    }
+
+    /// Resumes a paused schedule.
+    ///
+    /// Recurring runs missed while paused are skipped, a single run whose time
+    /// has passed cannot be resumed.
+    pub async fn resume_schedule(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        schedule_id: i32,
+    ) -> Result<ScheduleRecord> {
+        let schedule = Self::find_schedule(mm, ctx, &cluster_uuid, schedule_id).await?;
+        Self::require_scheduler(mm, ctx, &cluster_uuid).await?;
+
+        let now = Utc::now();
+        let next_run_at = match (&schedule.cron, schedule.run_at) {
+            (Some(expression), _) => next_cron_run(
+                &parse_cron(expression)?,
+                parse_timezone(&schedule.timezone)?,
+                now,
+            ),
+            (None, Some(run_at)) if run_at > now && schedule.last_run_at.is_none() => Some(run_at),
+            _ => {
+                return Err(invalid(format!(
+                    "single run of schedule `{}` has already passed",
+                    schedule.name
+                )))
+            }
+        };
+
+        let mut model = action_schedule::ActiveModel::from(schedule);
+        model.paused = Set(false);
+        model.next_run_at = Set(next_run_at);
+
+        Ok(model.update(&mm.db).await?.into())
+    }
+
+    pub async fn delete_schedule(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        schedule_id: i32,
+    ) -> Result<()> {
+        let schedule = Self::find_schedule(mm, ctx, &cluster_uuid, schedule_id).await?;
+        Self::require_scheduler(mm, ctx, &cluster_uuid).await?;
+
+        schedule.delete(&mm.db).await?;
+
+        Ok(())
+    }
+
+    /// Schedules that are not paused and whose next run is due.
+    pub(crate) async fn due(mm: &ModelManager) -> Result<Vec<action_schedule::Model>> {
+        Ok(action_schedule::Entity::find()
+            .filter(action_schedule::Column::Paused.eq(false))
+            .filter(action_schedule::Column::NextRunAt.lte(Utc::now().fixed_offset()))
+            .order_by_asc(action_schedule::Column::NextRunAt)
+            .all(&mm.db)
+            .await?)
+    }
+
+    /// Moves a due schedule to its following run.
+    ///
+    /// Returns `false` when another scheduler claimed the run first.
+    pub(crate) async fn claim(
+        mm: &ModelManager,
+        schedule: &action_schedule::Model,
+    ) -> Result<bool> {
+        let now = Utc::now();
+
+        let next_run_at = match &schedule.cron {
+            Some(expression) => next_cron_run(
+                &parse_cron(expression)?,
+                parse_timezone(&schedule.timezone)?,
+                now,
+            ),
+            None => None,
+        };
+
+        let res = action_schedule::Entity::update_many()
+            .set(action_schedule::ActiveModel {
+                next_run_at: Set(next_run_at),
+                last_run_at: Set(Some(now.fixed_offset())),
+                ..Default::default()
+            })
+            .filter(action_schedule::Column::Id.eq(schedule.id))
+            .filter(action_schedule::Column::NextRunAt.eq(schedule.next_run_at))
+            .exec(&mm.db)
+            .await?;
+
+        Ok(res.rows_affected == 1)
+    }
+
+    /// Sends the action of a schedule on behalf of the user who created it and
+    /// records the outcome.
+    pub(crate) async fn run(mm: &ModelManager, schedule: action_schedule::Model) -> Result<()> {
+        let ctx = Ctx::new_user(schedule.created_by.clone());
+        let cluster_uuid = schedule.cluster_id.to_string();
+
+        let res = Self::dispatch(mm, &ctx, &cluster_uuid, &schedule).await;
+
+        let (status, error) = match res {
+            Ok(None) => ("success", None),
+            Ok(Some(error)) | Err(Error { message: error, .. }) => ("failed", Some(error)),
+        };
+
+        action_schedule::Entity::update_many()
+            .set(action_schedule::ActiveModel {
+                last_status: Set(Some(status.to_string())),
+                last_error: Set(error),
+                ..Default::default()
+            })
+            .filter(action_schedule::Column::Id.eq(schedule.id))
+            .exec(&mm.db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Returns a description of the microdevices the action failed on, if any.
+    async fn dispatch(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: &String,
+        schedule: &action_schedule::Model,
+    ) -> Result<Option<String>> {
+        let ids: Vec<i32> = match &schedule.group_name {
+            Some(group) => DeviceGroupBMC::resolve_member_ids(mm, ctx, cluster_uuid, group).await?,
+            None => schedule
+                .microdevice_ids
+                .clone()
+                .and_then(|ids| serde_json::from_value(ids).ok())
+                .unwrap_or_default(),
+        };
+
+        // Nothing to do for a group that is empty at the time of the run
+        if ids.is_empty() {
+            return Ok(None);
+        }
+
+        let total = ids.len();
+        let summary = MicrodeviceBMC::trigger_action(
+            mm,
+            ctx,
+            cluster_uuid.clone(),
+            ids,
+            schedule.action.clone(),
+            schedule.payload.clone(),
+        )
+        .await?;
+
+        let failed: Vec<String> = summary
+            .results
+            .iter()
+            .filter(|r| !r.is_success())
+            .map(|r| r.message.clone())
+            .collect();
+
+        Ok((!failed.is_empty()).then(|| {
+            format!(
+                "{} of {} microdevices failed: {}",
+                failed.len(),
+                total,
+                failed.join("; ")
+            )
+        }))
+    }
+
+    async fn find_schedule(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: &String,
+        schedule_id: i32,
+    ) -> Result<action_schedule::Model> {
+        ClusterBMC::exists(mm, ctx, cluster_uuid.clone()).await?;
+
+        action_schedule::Entity::find_by_id(schedule_id)
+            .filter(action_schedule::Column::ClusterId.eq(parse_cluster_id(cluster_uuid)?))
+            .one(&mm.db)
+            .await?
+            .ok_or(Error {
+                kind: ErrorKind::ScheduleNotFound,
+                message: format!("schedule `{}` not found", schedule_id),
+            })
+    }
+
+    async fn require_scheduler(mm: &ModelManager, ctx: &Ctx, cluster_uuid: &String) -> Result<()> {
+        ClusterBMC::require_role(
+            mm,
+            ctx,
+            cluster_uuid,
+            &[ClusterRole::Owner, ClusterRole::Operator],
+            "manage schedules",
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+fn invalid(message: String) -> Error {
+    Error {
+        kind: ErrorKind::InvalidSchedule,
+        message,
+    }
+}
+
+fn parse_timezone(timezone: &str) -> Result<Tz> {
+    Tz::from_str(timezone).map_err(|_| invalid(format!("unknown time zone `{}`", timezone)))
+}
+
+/// Parses a cron expression, the seconds field is optional.
+///
+/// The `cron` crate numbers the days of the week from 1 to 7 starting on
+/// Sunday, the POSIX numbers are translated before parsing.
+fn parse_cron(expression: &str) -> Result<cron::Schedule> {
+    let expression = expression.trim();
+    let mut fields: Vec<String> = expression.split_whitespace().map(str::to_string).collect();
+
+    if fields.len() == 5 {
+        fields.insert(0, "0".to_string());
+    }
+
+    if let Some(day_of_week) = fields.get_mut(5) {
+        *day_of_week = posix_day_of_week(day_of_week)
+            .map_err(|e| invalid(format!("invalid cron expression `{}`: {}", expression, e)))?;
+    }
+
+    cron::Schedule::from_str(&fields.join(" "))
+        .map_err(|e| invalid(format!("invalid cron expression `{}`: {}", expression, e)))
+}
+
+/// Translates a POSIX day-of-week field, Sunday being 0 or 7, to the numbers
+/// of the `cron` crate. Named days are kept as they are.
+fn posix_day_of_week(field: &str) -> std::result::Result<String, String> {
+    let day = |value: &str| -> std::result::Result<Option<u8>, String> {
+        match value.parse::<u8>() {
+            Ok(day) if day <= 7 => Ok(Some(day)),
+            Ok(day) => Err(format!("day of the week `{}` is not within 0-7", day)),
+            Err(_) => Ok(None),
+        }
+    };
+
+    let mut items = Vec::new();
+
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, Some(step)),
+            None => (item, None),
+        };
+        let with_step = |range: String| match step {
+            Some(step) => format!("{}/{}", range, step),
+            None => range,
+        };
+
+        match range.split_once('-') {
+            Some((start, end)) => match (day(start)?, day(end)?) {
+                // The whole week, from Sunday to Sunday
+                (Some(0), Some(7)) => items.push(with_step("1-7".to_string())),
+                // Sunday ends the range as 7, it is the first day of the crate
+                (Some(start), Some(7)) if start > 0 && start < 7 => {
+                    items.push(with_step(format!("{}-7", start + 1)));
+
+                    let step = step.and_then(|s| s.parse::<u8>().ok()).unwrap_or(1);
+                    if step > 0 && (7 - start) % step == 0 {
+                        items.push("1".to_string());
+                    }
+                }
+                (Some(start), Some(end)) => {
+                    items.push(with_step(format!("{}-{}", start % 7 + 1, end % 7 + 1)))
+                }
+                _ => items.push(item.to_string()),
+            },
+            None => match day(range)? {
+                Some(day) => items.push(with_step((day % 7 + 1).to_string())),
+                None => items.push(item.to_string()),
+            },
+        }
+    }
+
+    Ok(items.join(","))
+}
+
+fn next_cron_run(
+    cron: &cron::Schedule,
+    tz: Tz,
+    after: DateTime<Utc>,
+) -> Option<DateTime<FixedOffset>> {
+    cron.after(&after.with_timezone(&tz))
+        .next()
+        .map(|next| next.fixed_offset())
+}
+
+/// Parses a point in time given either with an offset or as a local time in `tz`.
+fn parse_run_at(run_at: &str, tz: Tz) -> Result<DateTime<FixedOffset>> {
+    if let Ok(run_at) = DateTime::parse_from_rfc3339(run_at) {
+        return Ok(run_at);
+    }
+
+    let local = NaiveDateTime::parse_from_str(run_at, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(run_at, "%Y-%m-%dT%H:%M"))
+        .map_err(|_| invalid(format!("invalid `run_at` `{}`", run_at)))?;
+
+    tz.from_local_datetime(&local)
+        .single()
+        .map(|run_at| run_at.fixed_offset())
+        .ok_or(invalid(format!(
+            "`run_at` `{}` does not exist or is ambiguous in {}",
+            run_at,
+            tz.name()
+        )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Next run of `expression` after `after` in UTC, to the minute
+    fn next_day(expression: &str, after: &str) -> String {
+        let cron = parse_cron(expression).unwrap();
+        let after = DateTime::parse_from_rfc3339(after).unwrap().to_utc();
+
+        next_cron_run(&cron, chrono_tz::UTC, after)
+            .unwrap()
+            .format("%Y-%m-%d %H:%M")
+            .to_string()
+    }
+
+    // 2026-01-01 is a Thursday
+    const THURSDAY: &str = "2026-01-01T12:00:00Z";
+
+    #[test]
+    fn monday_is_one() {
+        assert_eq!(next_day("0 3 * * 1", THURSDAY), "2026-01-05 03:00");
+        assert_eq!(next_day("0 3 * * Mon", THURSDAY), "2026-01-05 03:00");
+    }
+
+    #[test]
+    fn sunday_is_zero_or_seven() {
+        assert_eq!(next_day("0 3 * * 0", THURSDAY), "2026-01-04 03:00");
+        assert_eq!(next_day("0 3 * * 7", THURSDAY), "2026-01-04 03:00");
+        assert_eq!(next_day("* * * * 0", THURSDAY), "2026-01-04 00:00");
+        assert_eq!(next_day("0 3 * * 6", THURSDAY), "2026-01-03 03:00");
+    }
+
+    #[test]
+    fn ranges_lists_and_steps() {
+        // Weekdays skip the weekend after a Friday
+        assert_eq!(
+            next_day("0 3 * * 1-5", "2026-01-02T12:00:00Z"),
+            "2026-01-05 03:00"
+        );
+        // Friday to Sunday through 7 still reaches Sunday
+        assert_eq!(
+            next_day("0 3 * * 5-7", "2026-01-03T12:00:00Z"),
+            "2026-01-04 03:00"
+        );
+        assert_eq!(next_day("0 3 * * 0,3", THURSDAY), "2026-01-04 03:00");
+        // Monday, Wednesday and Friday
+        assert_eq!(next_day("0 3 * * 1-5/2", THURSDAY), "2026-01-02 03:00");
+        // Every day, Sunday being both ends of the range
+        assert_eq!(next_day("0 3 * * 0-7", THURSDAY), "2026-01-02 03:00");
+        // Sunday, Tuesday, Thursday and Saturday
+        assert_eq!(next_day("0 3 * * 0-7/2", THURSDAY), "2026-01-03 03:00");
+        assert_eq!(
+            next_day("0 3 * * 0-7/2", "2026-01-03T12:00:00Z"),
+            "2026-01-04 03:00"
+        );
+        // The seconds field keeps the day of the week in place
+        assert_eq!(next_day("0 0 3 * * 1", THURSDAY), "2026-01-05 03:00");
+    }
+
+    #[test]
+    fn out_of_range_days_are_rejected() {
+        assert!(parse_cron("0 3 * * 8").is_err());
+        assert!(parse_cron("0 3 * * 1-9").is_err());
+    }
+}
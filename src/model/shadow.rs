@@ -0,0 +1,367 @@
+use super::error::{Error, ErrorKind, Result};
+use super::microdevice::MicrodeviceBaseModelController as MicrodeviceBMC;
+use super::ModelManager;
+use crate::context::Ctx;
+use entity::{microdevice, microdevice_shadow};
+use sea_orm::ActiveValue::Set;
+use sea_orm::{entity::prelude::*, sea_query::Expr, QuerySelect, SqlErr, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tracing::{debug, error};
+
+/// AMQP `type` property of the delta messages published to the gateway.
+pub const SHADOW_DELTA_MESSAGE_TYPE: &str = "shadow-delta";
+
+/// Patch applied to the desired state of a microdevice shadow.
+///
+/// `desired` is merged into the current desired document following JSON merge
+/// patch semantics, a `null` value removes the key. When `version` is provided
+/// the update is rejected unless it matches the current desired version.
+#[derive(Deserialize, utoipa::ToSchema, Debug)]
+pub struct ShadowDesiredUpdate {
+    #[schema(example = 3)]
+    pub version: Option<i32>,
+    #[schema(example = json!({"sample_rate": 10, "led": null}))]
+    pub desired: Value,
+}
+
+#[derive(Serialize, utoipa::ToSchema, Debug)]
+pub struct ShadowRecord {
+    microdevice_id: i32,
+    #[schema(example = json!({"sample_rate": 10}))]
+    desired: Value,
+    #[schema(example = json!({"sample_rate": 5}))]
+    reported: Value,
+    #[schema(example = json!({"sample_rate": 10}))]
+    delta: Value,
+    desired_version: i32,
+    reported_version: i32,
+    #[schema(value_type = Option<String>, format = DateTime)]
+    updated_at: Option<DateTimeWithTimeZone>,
+}
+
+/// Message published to the gateway whenever the desired state diverges from
+/// the reported state.
+#[derive(Serialize)]
+pub struct ShadowDeltaMessage {
+    cluster_id: String,
+    microdevice_id: i32,
+    version: i32,
+    delta: Value,
+}
+
+impl From<microdevice_shadow::Model> for ShadowRecord {
+    fn from(shadow: microdevice_shadow::Model) -> Self {
+        Self {
+            microdevice_id: shadow.microdevice_id,
+            delta: compute_delta(&shadow.desired, &shadow.reported),
+            desired: shadow.desired,
+            reported: shadow.reported,
+            desired_version: shadow.desired_version,
+            reported_version: shadow.reported_version,
+            updated_at: Some(shadow.updated_at),
+        }
+    }
+}
+
+impl ShadowRecord {
+    fn empty(microdevice_id: i32) -> Self {
+        Self {
+            microdevice_id,
+            desired: Value::Object(Map::new()),
+            reported: Value::Object(Map::new()),
+            delta: Value::Object(Map::new()),
+            desired_version: 0,
+            reported_version: 0,
+            updated_at: None,
+        }
+    }
+}
+
+pub struct ShadowBaseModelController {}
+
+impl ShadowBaseModelController {
+    pub async fn get_shadow(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        microdevice_id: i32,
+    ) -> Result<ShadowRecord> {
+        let device =
+            MicrodeviceBMC::find_in_cluster(mm, ctx, &cluster_uuid, microdevice_id).await?;
+
+        let shadow = microdevice_shadow::Entity::find_by_id(device.id)
+            .one(&mm.db)
+            .await?;
+
+        Ok(match shadow {
+            Some(shadow) => shadow.into(),
+            None => ShadowRecord::empty(device.id),
+        })
+    }
+
+    pub async fn update_desired(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        microdevice_id: i32,
+        update: ShadowDesiredUpdate,
+    ) -> Result<ShadowRecord> {
+        let device =
+            MicrodeviceBMC::find_in_cluster(mm, ctx, &cluster_uuid, microdevice_id).await?;
+
+        Self::validate_document(&update.desired)?;
+
+        let current = microdevice_shadow::Entity::find_by_id(device.id)
+            .one(&mm.db)
+            .await?;
+
+        let current_version = current.as_ref().map_or(0, |s| s.desired_version);
+
+        if let Some(expected) = update.version {
+            if expected != current_version {
+                return Err(Self::version_conflict(expected, current_version));
+            }
+        }
+
+        let now = chrono::Utc::now().fixed_offset();
+
+        let shadow = match current {
+            Some(current) => {
+                let mut desired = current.desired.clone();
+                merge_patch(&mut desired, &update.desired);
+
+                // Only update the row if nobody bumped the version in between
+                let res = microdevice_shadow::Entity::update_many()
+                    .col_expr(
+                        microdevice_shadow::Column::Desired,
+                        Expr::value(desired.clone()),
+                    )
+                    .col_expr(
+                        microdevice_shadow::Column::DesiredVersion,
+                        Expr::value(current_version + 1),
+                    )
+                    .col_expr(microdevice_shadow::Column::UpdatedAt, Expr::value(now))
+                    .filter(microdevice_shadow::Column::MicrodeviceId.eq(device.id))
+                    .filter(microdevice_shadow::Column::DesiredVersion.eq(current_version))
+                    .exec(&mm.db)
+                    .await?;
+
+                if res.rows_affected == 0 {
+                    return Err(Self::version_conflict(
+                        update.version.unwrap_or(current_version),
+                        current_version + 1,
+                    ));
+                }
+
+                microdevice_shadow::Model {
+                    desired,
+                    desired_version: current_version + 1,
+                    updated_at: now,
+                    ..current
+                }
+            }
+            None => {
+                let mut desired = Value::Object(Map::new());
+                merge_patch(&mut desired, &update.desired);
+
+                let res = microdevice_shadow::ActiveModel {
+                    microdevice_id: Set(device.id),
+                    desired: Set(desired),
+                    reported: Set(Value::Object(Map::new())),
+                    desired_version: Set(1),
+                    reported_version: Set(0),
+                    updated_at: Set(now),
+                }
+                .insert(&mm.db)
+                .await;
+
+                match res {
+                    Ok(shadow) => shadow,
+                    // Another request created the shadow first
+                    Err(e) if matches!(e.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+                        return Err(Self::version_conflict(
+                            update.version.unwrap_or(current_version),
+                            current_version + 1,
+                        ));
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        };
+
+        Self::publish_delta(mm, &device, &shadow).await;
+
+        Ok(shadow.into())
+    }
+
+    /// Merges a state report sent by the microdevice identified by `ctx` into
+    /// its shadow and republishes the remaining delta, if any.
+    pub async fn update_reported(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        reported: Value,
+    ) -> Result<ShadowRecord> {
+        let microdevice_id = MicrodeviceBMC::microdevice_id_from_ctx(ctx)?;
+
+        Self::validate_document(&reported)?;
+
+        let device = microdevice::Entity::find_by_id(microdevice_id)
+            .one(&mm.db)
+            .await?
+            .ok_or(Error {
+                kind: ErrorKind::MicrodeviceNotFound,
+                message: format!("microdevice `{}` not found", microdevice_id),
+            })?;
+
+        let txn = mm.db.begin().await?;
+
+        let current = microdevice_shadow::Entity::find_by_id(device.id)
+            .lock_exclusive()
+            .one(&txn)
+            .await?;
+
+        let now = chrono::Utc::now().fixed_offset();
+
+        let shadow = match current {
+            Some(current) => {
+                let mut merged = current.reported.clone();
+                merge_patch(&mut merged, &reported);
+
+                let mut update = microdevice_shadow::ActiveModel::from(current.clone());
+                update.reported = Set(merged);
+                update.reported_version = Set(current.reported_version + 1);
+                update.updated_at = Set(now);
+                update.update(&txn).await?
+            }
+            None => {
+                let mut merged = Value::Object(Map::new());
+                merge_patch(&mut merged, &reported);
+
+                microdevice_shadow::ActiveModel {
+                    microdevice_id: Set(device.id),
+                    desired: Set(Value::Object(Map::new())),
+                    reported: Set(merged),
+                    desired_version: Set(0),
+                    reported_version: Set(1),
+                    updated_at: Set(now),
+                }
+                .insert(&txn)
+                .await?
+            }
+        };
+
+        txn.commit().await?;
+
+        Self::publish_delta(mm, &device, &shadow).await;
+
+        Ok(shadow.into())
+    }
+
+    /// Publishes the outstanding delta to the gateway.
+    ///
+    /// Failures are only logged: the shadow is already persisted and the delta
+    /// is published again on the next report from the device.
+    async fn publish_delta(
+        mm: &ModelManager,
+        device: &microdevice::Model,
+        shadow: &microdevice_shadow::Model,
+    ) {
+        let delta = compute_delta(&shadow.desired, &shadow.reported);
+
+        if delta.as_object().is_none_or(|m| m.is_empty()) {
+            debug!("Shadow of microdevice `{}` is in sync", device.id);
+            return;
+        }
+
+        let message = ShadowDeltaMessage {
+            cluster_id: device.cluster_id.to_string(),
+            microdevice_id: device.id,
+            version: shadow.desired_version,
+            delta,
+        };
+
+        let payload = match serde_json::to_value(message) {
+            Ok(v) => v,
+            Err(e) => {
+                error!(error = %e, "Failed to serialize shadow delta.");
+                return;
+            }
+        };
+
+        if let Err(e) = mm
+            .bus
+            .publish_event(SHADOW_DELTA_MESSAGE_TYPE, payload)
+            .await
+        {
+            error!(error = %e, microdevice_id = device.id, "Failed to publish shadow delta.");
+        }
+    }
+
+    fn validate_document(document: &Value) -> Result<()> {
+        if document.is_object() {
+            Ok(())
+        } else {
+            Err(Error {
+                kind: ErrorKind::InvalidShadowDocument,
+                message: "shadow state must be a JSON object".to_string(),
+            })
+        }
+    }
+
+    fn version_conflict(expected: i32, current: i32) -> Error {
+        Error {
+            kind: ErrorKind::ShadowVersionConflict,
+            message: format!(
+                "desired state version `{}` is stale, current version is `{}`",
+                expected, current
+            ),
+        }
+    }
+}
+
+/// Applies `patch` to `target` following JSON merge patch (RFC 7386) rules.
+fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+/// Returns the part of `desired` that is not yet reflected in `reported`.
+fn compute_delta(desired: &Value, reported: &Value) -> Value {
+    let mut delta = Map::new();
+
+    if let Value::Object(desired) = desired {
+        for (key, want) in desired {
+            match (want, reported.get(key)) {
+                (Value::Object(_), Some(have @ Value::Object(_))) => {
+                    let nested = compute_delta(want, have);
+                    if nested.as_object().is_some_and(|m| !m.is_empty()) {
+                        delta.insert(key.clone(), nested);
+                    }
+                }
+                (want, Some(have)) if want == have => {}
+                (want, _) => {
+                    delta.insert(key.clone(), want.clone());
+                }
+            }
+        }
+    }
+
+    Value::Object(delta)
+}
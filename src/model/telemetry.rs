@@ -0,0 +1,109 @@
+use super::error::Result;
+use super::event_bus::LiveEvent;
+use super::lifecycle::LifecycleBaseModelController as LifecycleBMC;
+use super::microdevice::MicrodeviceBaseModelController as MicrodeviceBMC;
+use super::topic::stored_topics;
+use super::ModelManager;
+use crate::context::Ctx;
+use entity::telemetry_record;
+use sea_orm::entity::prelude::*;
+use sea_orm::ActiveValue::Set;
+use sea_orm::{QueryOrder, QuerySelect};
+use serde::Serialize;
+use serde_json::Value;
+
+/// Largest number of samples returned at once
+const MAX_SAMPLES: u64 = 100;
+
+#[derive(Serialize, utoipa::ToSchema, Clone, Debug)]
+pub struct TelemetrySample {
+    #[schema(value_type = String, format = DateTime)]
+    timestamp: chrono::NaiveDateTime,
+    #[schema(example = "/temperature")]
+    source_topic: String,
+    #[schema(example = "AHT10 temperature stream")]
+    source_name: String,
+    #[schema(example = json!({"celsius": 21.5}))]
+    data: Value,
+}
+
+impl From<telemetry_record::Model> for TelemetrySample {
+    fn from(record: telemetry_record::Model) -> Self {
+        Self {
+            timestamp: record.timestamp,
+            source_topic: record.source_topic,
+            source_name: record.source_name,
+            data: record.data,
+        }
+    }
+}
+
+pub struct TelemetryBaseModelController {}
+
+impl TelemetryBaseModelController {
+    /// Stores a telemetry sample sent by the microdevice identified by `ctx`.
+    ///
+    /// Samples of microdevices that are not operational are rejected.
+    pub async fn ingest(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        topic: String,
+        data: Value,
+        timestamp: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<()> {
+        let device = LifecycleBMC::require_operational(mm, ctx).await?;
+
+        // Samples are labelled with the name of the registered topic when known
+        let source_name = stored_topics(device.topics.as_ref())
+            .into_iter()
+            .find(|t| t.topic == topic)
+            .map_or_else(|| topic.clone(), |t| t.name);
+
+        let record = telemetry_record::ActiveModel {
+            timestamp: Set(timestamp.unwrap_or_else(chrono::Utc::now).naive_utc()),
+            microdevice_id: Set(device.id),
+            source_topic: Set(topic),
+            source_name: Set(source_name),
+            data: Set(data),
+        }
+        .insert(&mm.db)
+        .await?;
+
+        mm.events.publish(LiveEvent::Telemetry {
+            cluster_id: device.cluster_id,
+            microdevice_id: device.id,
+            sample: record.into(),
+        });
+
+        Ok(())
+    }
+
+    /// Returns the most recent samples of a microdevice, newest first.
+    pub async fn latest(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        microdevice_id: i32,
+        topic: Option<String>,
+        limit: Option<u64>,
+    ) -> Result<Vec<TelemetrySample>> {
+        let device =
+            MicrodeviceBMC::find_in_cluster(mm, ctx, &cluster_uuid, microdevice_id).await?;
+
+        let mut query = telemetry_record::Entity::find()
+            .filter(telemetry_record::Column::MicrodeviceId.eq(device.id))
+            .order_by_desc(telemetry_record::Column::Timestamp)
+            .limit(limit.unwrap_or(1).clamp(1, MAX_SAMPLES));
+
+        if let Some(topic) = topic {
+            query = query.filter(telemetry_record::Column::SourceTopic.eq(topic));
+        }
+
+        Ok(query
+            .all(&mm.db)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+}
@@ -0,0 +1,296 @@
+use super::error::{Error, ErrorKind, Result};
+use super::microdevice::{MicrodeviceBaseModelController as MicrodeviceBMC, MicrodeviceTopic};
+use super::ModelManager;
+use crate::context::Ctx;
+use entity::microdevice;
+use sea_orm::ActiveValue::Set;
+use sea_orm::{entity::prelude::*, QuerySelect, TransactionTrait};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Maximum length of an MQTT topic in bytes
+const MAX_TOPIC_LEN: usize = 65535;
+
+/// Highest QoS level supported by MQTT
+const MAX_QOS: u8 = 2;
+
+#[derive(Deserialize, utoipa::ToSchema, Debug)]
+pub struct TopicDeleteParams {
+    #[schema(example = "/temperature")]
+    pub topic: String,
+}
+
+/// Topics stored before the topic shape was enforced may be plain strings
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredTopic {
+    Topic(MicrodeviceTopic),
+    Legacy(String),
+}
+
+pub struct TopicBaseModelController {}
+
+impl TopicBaseModelController {
+    pub async fn list_topics(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        microdevice_id: i32,
+    ) -> Result<Vec<MicrodeviceTopic>> {
+        let device =
+            MicrodeviceBMC::find_in_cluster(mm, ctx, &cluster_uuid, microdevice_id).await?;
+
+        Ok(stored_topics(device.topics.as_ref()))
+    }
+
+    pub async fn add_topic(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        microdevice_id: i32,
+        topic: MicrodeviceTopic,
+    ) -> Result<Vec<MicrodeviceTopic>> {
+        validate_topic(&topic)?;
+
+        Self::modify_topics(mm, ctx, cluster_uuid, microdevice_id, |topics| {
+            if topics.iter().any(|t| t.topic == topic.topic) {
+                return Err(Error {
+                    kind: ErrorKind::DuplicateTopic,
+                    message: format!("topic `{}` is already registered", topic.topic),
+                });
+            }
+
+            topics.push(topic);
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn remove_topic(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        microdevice_id: i32,
+        params: TopicDeleteParams,
+    ) -> Result<Vec<MicrodeviceTopic>> {
+        Self::modify_topics(mm, ctx, cluster_uuid, microdevice_id, |topics| {
+            let len = topics.len();
+            topics.retain(|t| t.topic != params.topic);
+
+            if topics.len() == len {
+                return Err(Error {
+                    kind: ErrorKind::TopicNotFound,
+                    message: format!("topic `{}` is not registered", params.topic),
+                });
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Applies `f` to the topics of a microdevice while holding a row lock so
+    /// concurrent modifications do not overwrite each other.
+    async fn modify_topics<F>(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        cluster_uuid: String,
+        microdevice_id: i32,
+        f: F,
+    ) -> Result<Vec<MicrodeviceTopic>>
+    where
+        F: FnOnce(&mut Vec<MicrodeviceTopic>) -> Result<()>,
+    {
+        let device =
+            MicrodeviceBMC::find_in_cluster(mm, ctx, &cluster_uuid, microdevice_id).await?;
+
+        let txn = mm.db.begin().await?;
+
+        let device = microdevice::Entity::find_by_id(device.id)
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(Error {
+                kind: ErrorKind::MicrodeviceNotFound,
+                message: format!("microdevice `{}` not found", microdevice_id),
+            })?;
+
+        let (mut topics, malformed) = parse_stored_topics(device.topics.as_ref());
+        f(&mut topics)?;
+
+        // Entries that cannot be read are written back untouched
+        let mut stored = Vec::with_capacity(topics.len() + malformed.len());
+        for topic in &topics {
+            stored.push(serde_json::to_value(topic)?);
+        }
+        stored.extend(malformed);
+
+        let mut update = microdevice::ActiveModel::from(device);
+        update.topics = Set(Some(Value::Array(stored)));
+        update.update(&txn).await?;
+
+        txn.commit().await?;
+
+        Ok(topics)
+    }
+}
+
+/// Validates a list of topics, rejecting invalid entries and duplicates.
+pub(crate) fn validate_topics(topics: &[MicrodeviceTopic]) -> Result<()> {
+    for (i, topic) in topics.iter().enumerate() {
+        validate_topic(topic)?;
+
+        if topics[..i].iter().any(|t| t.topic == topic.topic) {
+            return Err(Error {
+                kind: ErrorKind::DuplicateTopic,
+                message: format!("topic `{}` is listed more than once", topic.topic),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Validates a topic filter according to the MQTT specification.
+///
+/// Wildcards have to occupy a whole topic level and the multi-level wildcard
+/// `#` is only allowed as the last level.
+pub(crate) fn validate_topic(topic: &MicrodeviceTopic) -> Result<()> {
+    let invalid = |reason: &str| Error {
+        kind: ErrorKind::InvalidTopicFormat,
+        message: format!("invalid topic `{}`: {}", topic.topic, reason),
+    };
+
+    if topic.topic.is_empty() {
+        return Err(invalid("topic must not be empty"));
+    }
+
+    if topic.topic.len() > MAX_TOPIC_LEN {
+        return Err(invalid("topic must not exceed 65535 bytes"));
+    }
+
+    if topic.topic.contains('\0') {
+        return Err(invalid("topic must not contain the NUL character"));
+    }
+
+    let levels: Vec<&str> = topic.topic.split('/').collect();
+
+    for (i, level) in levels.iter().enumerate() {
+        if level.contains('#') && (*level != "#" || i != levels.len() - 1) {
+            return Err(invalid(
+                "`#` must occupy a whole level and be the last level",
+            ));
+        }
+
+        if level.contains('+') && *level != "+" {
+            return Err(invalid("`+` must occupy a whole level"));
+        }
+    }
+
+    if topic.qos > MAX_QOS {
+        return Err(invalid("qos must be 0, 1 or 2"));
+    }
+
+    Ok(())
+}
+
+/// Topics of a microdevice, malformed entries are skipped.
+pub(crate) fn stored_topics(topics: Option<&Value>) -> Vec<MicrodeviceTopic> {
+    parse_stored_topics(topics).0
+}
+
+/// Splits the stored topics of a microdevice into the entries that can be
+/// read and the malformed ones.
+fn parse_stored_topics(topics: Option<&Value>) -> (Vec<MicrodeviceTopic>, Vec<Value>) {
+    let entries = match topics {
+        None | Some(Value::Null) => return (vec![], vec![]),
+        Some(Value::Array(entries)) => entries.clone(),
+        Some(other) => vec![other.clone()],
+    };
+
+    let mut parsed = Vec::new();
+    let mut malformed = Vec::new();
+
+    for entry in entries {
+        match serde_json::from_value::<StoredTopic>(entry.clone()) {
+            Ok(StoredTopic::Topic(topic)) => parsed.push(topic),
+            Ok(StoredTopic::Legacy(topic)) => parsed.push(MicrodeviceTopic {
+                name: topic.clone(),
+                topic,
+                qos: 0,
+            }),
+            Err(_) => malformed.push(entry),
+        }
+    }
+
+    (parsed, malformed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn topic(topic: &str) -> MicrodeviceTopic {
+        MicrodeviceTopic {
+            topic: topic.to_string(),
+            qos: 0,
+            name: "sensor".to_string(),
+        }
+    }
+
+    #[test]
+    fn stored_topics_keep_the_readable_entries() {
+        let stored = json!([
+            { "topic": "/temperature", "qos": 1, "name": "AHT10" },
+            "/legacy",
+            { "topic": "/broken" },
+            42,
+        ]);
+
+        let topics = stored_topics(Some(&stored));
+
+        assert_eq!(topics.len(), 2);
+        assert_eq!(topics[0].topic, "/temperature");
+        assert_eq!(topics[0].qos, 1);
+        assert_eq!(topics[1].topic, "/legacy");
+        assert_eq!(topics[1].name, "/legacy");
+
+        let (_, malformed) = parse_stored_topics(Some(&stored));
+        assert_eq!(malformed, vec![json!({ "topic": "/broken" }), json!(42)]);
+    }
+
+    #[test]
+    fn stored_topics_of_devices_without_topics_are_empty() {
+        assert!(stored_topics(None).is_empty());
+        assert!(stored_topics(Some(&Value::Null)).is_empty());
+        assert!(stored_topics(Some(&json!([]))).is_empty());
+    }
+
+    #[test]
+    fn valid_topics_are_accepted() {
+        for valid in ["/temperature", "sensors/+/humidity", "sensors/#", "#", "+"] {
+            assert!(validate_topic(&topic(valid)).is_ok(), "{}", valid);
+        }
+    }
+
+    #[test]
+    fn invalid_topics_are_rejected() {
+        for invalid in [
+            "",
+            "sensors/#/humidity",
+            "sensors/temp#",
+            "sensors/a+",
+            "a\0b",
+        ] {
+            assert!(validate_topic(&topic(invalid)).is_err(), "{:?}", invalid);
+        }
+
+        assert!(validate_topic(&topic(&"a".repeat(MAX_TOPIC_LEN + 1))).is_err());
+        assert!(validate_topic(&MicrodeviceTopic {
+            qos: 3,
+            ..topic("/temperature")
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,135 @@
+use super::error::Result;
+use crate::context::Ctx;
+use crate::model::action_catalog::{
+    ActionCatalogBaseModelController as ActionCatalogBMC, ActionDefinitionCreate,
+    ActionDefinitionRecord, ActionDefinitionUpdate,
+};
+use crate::model::ModelManager;
+use axum::{
+    extract::{Extension, Json as ExtractJson, Path, State},
+    response::Json,
+};
+
+/// List the user-defined actions of a microdevice
+#[utoipa::path(
+    get,
+    path = "/cluster/{clusterId}/device/{microdeviceId}/actions",
+    tag = "Microdevices",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+        ("microdeviceId" = i32, Path, description="Microdevice ID"),
+    ),
+    responses(
+        (status = 200, body = [ActionDefinitionRecord]),
+        (status = 401),
+        (status = 404),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn list_actions(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path((cluster_id, microdevice_id)): Path<(String, i32)>,
+) -> Result<Json<Vec<ActionDefinitionRecord>>> {
+    Ok(Json(
+        ActionCatalogBMC::list_actions(&mm, &ctx, cluster_id, microdevice_id).await?,
+    ))
+}
+
+/// Declare a user-defined action on a microdevice
+///
+/// Payloads sent when triggering the action are validated against
+/// `payload_schema`. Only cluster owners can manage the action catalog.
+#[utoipa::path(
+    post,
+    path = "/cluster/{clusterId}/device/{microdeviceId}/actions",
+    tag = "Microdevices",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+        ("microdeviceId" = i32, Path, description="Microdevice ID"),
+    ),
+    request_body = ActionDefinitionCreate,
+    responses(
+        (status = 200, body = ActionDefinitionRecord),
+        (status = 400),
+        (status = 401),
+        (status = 403),
+        (status = 409, description = "Action already exists"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn create_action(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path((cluster_id, microdevice_id)): Path<(String, i32)>,
+    ExtractJson(data): Json<ActionDefinitionCreate>,
+) -> Result<Json<ActionDefinitionRecord>> {
+    Ok(Json(
+        ActionCatalogBMC::create_action(&mm, &ctx, cluster_id, microdevice_id, data).await?,
+    ))
+}
+
+/// Update a user-defined action of a microdevice
+#[utoipa::path(
+    put,
+    path = "/cluster/{clusterId}/device/{microdeviceId}/actions/{actionName}",
+    tag = "Microdevices",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+        ("microdeviceId" = i32, Path, description="Microdevice ID"),
+        ("actionName" = String, Path, description="Action name"),
+    ),
+    request_body = ActionDefinitionUpdate,
+    responses(
+        (status = 200, body = ActionDefinitionRecord),
+        (status = 400),
+        (status = 401),
+        (status = 403),
+        (status = 404),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn update_action(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path((cluster_id, microdevice_id, name)): Path<(String, i32, String)>,
+    ExtractJson(data): Json<ActionDefinitionUpdate>,
+) -> Result<Json<ActionDefinitionRecord>> {
+    Ok(Json(
+        ActionCatalogBMC::update_action(&mm, &ctx, cluster_id, microdevice_id, name, data).await?,
+    ))
+}
+
+/// Remove a user-defined action from a microdevice
+#[utoipa::path(
+    delete,
+    path = "/cluster/{clusterId}/device/{microdeviceId}/actions/{actionName}",
+    tag = "Microdevices",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+        ("microdeviceId" = i32, Path, description="Microdevice ID"),
+        ("actionName" = String, Path, description="Action name"),
+    ),
+    responses(
+        (status = 200),
+        (status = 401),
+        (status = 403),
+        (status = 404),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn delete_action(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path((cluster_id, microdevice_id, name)): Path<(String, i32, String)>,
+) -> Result<()> {
+    Ok(ActionCatalogBMC::delete_action(&mm, &ctx, cluster_id, microdevice_id, name).await?)
+}
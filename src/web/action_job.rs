@@ -0,0 +1,77 @@
+use super::error::Result;
+use crate::context::Ctx;
+use crate::model::action_job::{
+    ActionHistoryEntry, ActionHistoryParams, ActionJobBaseModelController as ActionJobBMC,
+    ActionJobRecord,
+};
+use crate::model::ModelManager;
+use axum::{
+    extract::{Extension, Path, Query, State},
+    response::Json,
+};
+use sea_orm::prelude::Uuid;
+
+/// Get an action job
+///
+/// Jobs are created by sending `device.action` with `async: true` to the
+/// JSON-RPC endpoint. Every targeted microdevice goes from `queued` to `sent`
+/// and then to `acknowledged`, `failed`, `timed-out` or `rate-limited`.
+#[utoipa::path(
+    get,
+    path = "/cluster/{clusterId}/actions/{jobId}",
+    tag = "Action Jobs",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+        ("jobId" = String, Path, description="Action job ID"),
+    ),
+    responses(
+        (status = 200, body = ActionJobRecord),
+        (status = 401),
+        (status = 404),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn get_job(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path((cluster_id, job_id)): Path<(String, Uuid)>,
+) -> Result<Json<ActionJobRecord>> {
+    Ok(Json(
+        ActionJobBMC::get_job(&mm, &ctx, cluster_id, job_id).await?,
+    ))
+}
+
+/// Get the action history of a microdevice
+///
+/// Returns the actions sent to the microdevice through action jobs, most
+/// recent first.
+#[utoipa::path(
+    get,
+    path = "/cluster/{clusterId}/device/{microdeviceId}/action-history",
+    tag = "Action Jobs",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+        ("microdeviceId" = i32, Path, description="Microdevice ID"),
+        ("limit" = Option<u64>, Query, description="Number of entries to return, at most 200", example=50),
+    ),
+    responses(
+        (status = 200, body = Vec<ActionHistoryEntry>),
+        (status = 401),
+        (status = 404),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn device_history(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path((cluster_id, microdevice_id)): Path<(String, i32)>,
+    Query(params): Query<ActionHistoryParams>,
+) -> Result<Json<Vec<ActionHistoryEntry>>> {
+    Ok(Json(
+        ActionJobBMC::device_history(&mm, &ctx, cluster_id, microdevice_id, params.limit).await?,
+    ))
+}
@@ -0,0 +1,84 @@
+use super::error::Result;
+use crate::context::Ctx;
+use crate::model::bulk_import::{
+    BulkImportBaseModelController as BulkImportBMC, BulkImportFormat, BulkImportParams,
+    BulkImportReport,
+};
+use crate::model::error::{Error as ModelError, ErrorKind};
+use crate::model::ModelManager;
+use axum::{
+    body::Bytes,
+    extract::{Extension, Path, Query, State},
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+
+/// Import many microdevices at once
+///
+/// The body is either a JSON array of microdevices (`application/json`) or a
+/// CSV document (`text/csv`) with a `name` column and optional `description`
+/// and `topics` columns. A `topics` cell holds a JSON array or `;` separated
+/// `topic|qos|name` entries, e.g. `/temperature|1|temp;/humidity`.
+///
+/// Every row is validated before anything is inserted. In `atomic` mode
+/// (default) nothing is created if a single row is invalid and the report is
+/// returned with status 422. In `best_effort` mode the valid rows are created
+/// and the invalid ones are listed in the report.
+#[utoipa::path(
+    post,
+    path = "/cluster/{clusterId}/devices/bulk",
+    tag = "Microdevices",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+        ("mode" = Option<BulkImportMode>, Query, description="Import mode", example="atomic"),
+    ),
+    request_body(
+        content = [MicrodeviceCreate],
+        content_type = "application/json",
+        description = "JSON array of microdevices or a CSV document sent as text/csv",
+    ),
+    responses(
+        (status = 200, body = BulkImportReport),
+        (status = 400, description = "The document could not be parsed"),
+        (status = 401),
+        (status = 404),
+        (status = 422, body = BulkImportReport, description = "Atomic import rejected"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn import_devices(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path(cluster_uuid): Path<String>,
+    Query(params): Query<BulkImportParams>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response> {
+    let content_type = headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok());
+
+    let format = BulkImportFormat::from_content_type(content_type).ok_or(ModelError {
+        kind: ErrorKind::InvalidBulkImport,
+        message: "expected an application/json or text/csv body".to_string(),
+    })?;
+
+    let rows = BulkImportBMC::parse_rows(format, &body)?;
+
+    let report: BulkImportReport = BulkImportBMC::import(
+        &mm,
+        &ctx,
+        cluster_uuid,
+        rows,
+        params.mode.unwrap_or_default(),
+    )
+    .await?;
+
+    let status = if report.is_rejected() {
+        StatusCode::UNPROCESSABLE_ENTITY
+    } else {
+        StatusCode::OK
+    };
+
+    Ok((status, Json(report)).into_response())
+}
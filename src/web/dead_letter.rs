@@ -0,0 +1,104 @@
+use super::error::Result;
+use crate::context::Ctx;
+use crate::model::dead_letter::{
+    DeadLetter, DeadLetterBaseModelController as DeadLetterBMC, DeadLetterParams,
+    DeadLetterPurgeResult, DeadLetterReplay, DeadLetterReplayResult, DeadLetterSource,
+};
+use crate::model::ModelManager;
+use axum::{
+    extract::{Extension, Path, Query, State},
+    response::Json,
+};
+
+/// Inspect dead-lettered messages
+///
+/// Returns the oldest registrar or telemetry messages that could not be
+/// processed together with the reason they failed. The messages stay in the
+/// dead-letter queue.
+#[utoipa::path(
+    get,
+    path = "/admin/dead-letters/{queue}",
+    tag = "Administration",
+    params(
+        ("queue" = DeadLetterSource, Path, description="Queue the messages failed in"),
+        ("limit" = Option<usize>, Query, description="Number of messages to return, at most 500", example=50),
+    ),
+    responses(
+        (status = 200, body = Vec<DeadLetter>),
+        (status = 401),
+        (status = 403),
+        (status = 503),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn list_dead_letters(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path(source): Path<DeadLetterSource>,
+    Query(params): Query<DeadLetterParams>,
+) -> Result<Json<Vec<DeadLetter>>> {
+    Ok(Json(
+        DeadLetterBMC::list(&mm, &ctx, source, params.limit).await?,
+    ))
+}
+
+/// Replay dead-lettered messages
+///
+/// Moves the oldest dead-lettered messages back to the queue they failed in,
+/// without their failure headers.
+#[utoipa::path(
+    post,
+    path = "/admin/dead-letters/{queue}/replay",
+    tag = "Administration",
+    params(
+        ("queue" = DeadLetterSource, Path, description="Queue the messages failed in"),
+    ),
+    request_body = DeadLetterReplay,
+    responses(
+        (status = 200, body = DeadLetterReplayResult),
+        (status = 401),
+        (status = 403),
+        (status = 503),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn replay_dead_letters(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path(source): Path<DeadLetterSource>,
+    Json(params): Json<DeadLetterReplay>,
+) -> Result<Json<DeadLetterReplayResult>> {
+    Ok(Json(
+        DeadLetterBMC::replay(&mm, &ctx, source, params).await?,
+    ))
+}
+
+/// Purge dead-lettered messages
+#[utoipa::path(
+    delete,
+    path = "/admin/dead-letters/{queue}",
+    tag = "Administration",
+    params(
+        ("queue" = DeadLetterSource, Path, description="Queue the messages failed in"),
+    ),
+    responses(
+        (status = 200, body = DeadLetterPurgeResult),
+        (status = 401),
+        (status = 403),
+        (status = 503),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn purge_dead_letters(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path(source): Path<DeadLetterSource>,
+) -> Result<Json<DeadLetterPurgeResult>> {
+    Ok(Json(DeadLetterBMC::purge(&mm, &ctx, source).await?))
+}
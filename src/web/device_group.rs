@@ -0,0 +1,192 @@
+use super::error::Result;
+use crate::context::Ctx;
+use crate::model::device_group::{
+    DeviceGroupBaseModelController as DeviceGroupBMC, DeviceGroupCreate, DeviceGroupRecord,
+    DeviceGroupUpdate,
+};
+use crate::model::microdevice::MicrodeviceRecord;
+use crate::model::ModelManager;
+use axum::{
+    extract::{Extension, Json as ExtractJson, Path, State},
+    response::Json,
+};
+
+/// List the device groups of a cluster
+#[utoipa::path(
+    get,
+    path = "/cluster/{clusterId}/groups",
+    tag = "Device Groups",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+    ),
+    responses(
+        (status = 200, body = [DeviceGroupRecord]),
+        (status = 401),
+        (status = 404),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn list_groups(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path(cluster_id): Path<String>,
+) -> Result<Json<Vec<DeviceGroupRecord>>> {
+    Ok(Json(
+        DeviceGroupBMC::list_groups(&mm, &ctx, cluster_id).await?,
+    ))
+}
+
+/// Create a device group
+///
+/// A static group lists its `members` explicitly. A dynamic group is defined by
+/// a `filter` on the name, labels and lifecycle state of the microdevices and
+/// is evaluated every time the group is used.
+#[utoipa::path(
+    post,
+    path = "/cluster/{clusterId}/groups",
+    tag = "Device Groups",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+    ),
+    request_body = DeviceGroupCreate,
+    responses(
+        (status = 200, body = DeviceGroupRecord),
+        (status = 400),
+        (status = 401),
+        (status = 403),
+        (status = 404),
+        (status = 409, description = "A group with the same name already exists"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn create_group(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path(cluster_id): Path<String>,
+    ExtractJson(data): Json<DeviceGroupCreate>,
+) -> Result<Json<DeviceGroupRecord>> {
+    Ok(Json(
+        DeviceGroupBMC::create_group(&mm, &ctx, cluster_id, data).await?,
+    ))
+}
+
+/// Get a device group
+#[utoipa::path(
+    get,
+    path = "/cluster/{clusterId}/groups/{groupName}",
+    tag = "Device Groups",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+        ("groupName" = String, Path, description="Group name"),
+    ),
+    responses(
+        (status = 200, body = DeviceGroupRecord),
+        (status = 401),
+        (status = 404),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn get_group(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path((cluster_id, name)): Path<(String, String)>,
+) -> Result<Json<DeviceGroupRecord>> {
+    Ok(Json(
+        DeviceGroupBMC::get_group(&mm, &ctx, cluster_id, name).await?,
+    ))
+}
+
+/// Update a device group
+#[utoipa::path(
+    put,
+    path = "/cluster/{clusterId}/groups/{groupName}",
+    tag = "Device Groups",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+        ("groupName" = String, Path, description="Group name"),
+    ),
+    request_body = DeviceGroupUpdate,
+    responses(
+        (status = 200, body = DeviceGroupRecord),
+        (status = 400),
+        (status = 401),
+        (status = 403),
+        (status = 404),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn update_group(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path((cluster_id, name)): Path<(String, String)>,
+    ExtractJson(data): Json<DeviceGroupUpdate>,
+) -> Result<Json<DeviceGroupRecord>> {
+    Ok(Json(
+        DeviceGroupBMC::update_group(&mm, &ctx, cluster_id, name, data).await?,
+    ))
+}
+
+/// Delete a device group
+///
+/// The microdevices of the group are left untouched.
+#[utoipa::path(
+    delete,
+    path = "/cluster/{clusterId}/groups/{groupName}",
+    tag = "Device Groups",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+        ("groupName" = String, Path, description="Group name"),
+    ),
+    responses(
+        (status = 200),
+        (status = 401),
+        (status = 403),
+        (status = 404),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn delete_group(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path((cluster_id, name)): Path<(String, String)>,
+) -> Result<()> {
+    Ok(DeviceGroupBMC::delete_group(&mm, &ctx, cluster_id, name).await?)
+}
+
+/// List the microdevices currently belonging to a device group
+#[utoipa::path(
+    get,
+    path = "/cluster/{clusterId}/groups/{groupName}/members",
+    tag = "Device Groups",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+        ("groupName" = String, Path, description="Group name"),
+    ),
+    responses(
+        (status = 200, body = [MicrodeviceRecord]),
+        (status = 401),
+        (status = 404),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn list_members(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path((cluster_id, name)): Path<(String, String)>,
+) -> Result<Json<Vec<MicrodeviceRecord>>> {
+    Ok(Json(
+        DeviceGroupBMC::list_members(&mm, &ctx, cluster_id, name).await?,
+    ))
+}
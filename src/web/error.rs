@@ -23,15 +23,22 @@ pub enum Error {
     InvalidHeader(axum::http::header::InvalidHeaderValue),
     AxumHttpError(axum::http::Error),
     ExpectedCookiesNotFound,
-    InvalidTopicFormat,
+    InvalidTopicFormat(String),
     Unauthorized,
     SerdeJson(serde_json::Error),
     InvalidMethod(String),
+    /// The request comes from a web page outside of `ALLOWED_ORIGINS`
+    ForbiddenOrigin,
 }
 
 impl From<crate::model::error::Error> for Error {
     fn from(e: crate::model::error::Error) -> Self {
-        Error::ModelError(e)
+        match e.kind {
+            crate::model::error::ErrorKind::InvalidTopicFormat => {
+                Error::InvalidTopicFormat(e.message)
+            }
+            _ => Error::ModelError(e),
+        }
     }
 }
 
@@ -100,7 +107,8 @@ impl IntoResponse for Error {
             Error::AxumHttpError(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
             Error::ExpectedCookiesNotFound => StatusCode::BAD_REQUEST.into_response(),
             Error::Unauthorized => StatusCode::UNAUTHORIZED.into_response(),
-            Error::InvalidTopicFormat => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            Error::ForbiddenOrigin => StatusCode::FORBIDDEN.into_response(),
+            Error::InvalidTopicFormat(e) => (StatusCode::BAD_REQUEST, e).into_response(),
             Error::SerdeJson(e) => {
                 // add the message to the response body
                 (StatusCode::BAD_REQUEST, e.to_string()).into_response()
@@ -0,0 +1,74 @@
+use super::error::Result;
+use crate::config::FakeDeviceRule;
+use crate::context::Ctx;
+use crate::model::fake_device::{
+    FakeDeviceBaseModelController as FakeDeviceBMC, FakeDeviceMessageResult, InboundQueue,
+};
+use crate::model::ModelManager;
+use axum::{
+    extract::{Extension, Path, State},
+    response::Json,
+};
+use serde_json::Value;
+
+/// Script the fake device
+///
+/// Adds a rule answering the actions it matches, checked before the rules of
+/// `message_bus.fake_device`. Only available with the in-process message bus.
+#[utoipa::path(
+    post,
+    path = "/admin/fake-device/rules",
+    tag = "Administration",
+    request_body = FakeDeviceRule,
+    responses(
+        (status = 200),
+        (status = 401),
+        (status = 403),
+        (status = 409, description = "The API does not run on the in-process message bus"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn script_fake_device(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Json(rule): Json<FakeDeviceRule>,
+) -> Result<()> {
+    Ok(FakeDeviceBMC::script(&mm, &ctx, rule).await?)
+}
+
+/// Send a message as a microdevice
+///
+/// Hands the message to the consumer of a queue as if a microdevice had sent
+/// it, and returns the reply of the consumer. Only available with the
+/// in-process message bus.
+#[utoipa::path(
+    post,
+    path = "/admin/fake-device/messages/{queue}",
+    tag = "Administration",
+    params(
+        ("queue" = InboundQueue, Path, description="Queue the message is sent to"),
+    ),
+    request_body = Object,
+    responses(
+        (status = 200, body = FakeDeviceMessageResult),
+        (status = 401),
+        (status = 403),
+        (status = 409, description = "The API does not run on the in-process message bus"),
+        (status = 500, description = "No consumer is running for the queue"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn send_fake_device_message(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path(queue): Path<InboundQueue>,
+    Json(message): Json<Value>,
+) -> Result<Json<FakeDeviceMessageResult>> {
+    Ok(Json(
+        FakeDeviceBMC::send_message(&mm, &ctx, queue, message).await?,
+    ))
+}
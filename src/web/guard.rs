@@ -28,7 +28,10 @@ pub async fn jwt_guard(
         }
     };
 
-    let ctx = Ctx::new_user(claims.sub);
+    let ctx = Ctx::new_user(claims.sub.clone());
+
+    // Long-lived connections need to know when the token expires
+    request.extensions_mut().insert(claims);
 
     match request.extensions_mut().insert(ctx) {
         Some(_) => {
@@ -0,0 +1,27 @@
+use crate::model::health::{HealthBaseModelController as HealthBMC, HealthRecord};
+use crate::model::ModelManager;
+use axum::{extract::State, http::StatusCode, response::Json};
+
+/// Get the health of the service
+///
+/// Answers `503` while the database or the message broker cannot be reached,
+/// the broker is reconnected to in the background.
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "Health",
+    responses(
+        (status = 200, body = HealthRecord),
+        (status = 503, body = HealthRecord),
+    ),
+)]
+pub async fn health(State(mm): State<ModelManager>) -> (StatusCode, Json<HealthRecord>) {
+    let health = HealthBMC::check(&mm).await;
+
+    let status = match health.is_healthy() {
+        true => StatusCode::OK,
+        false => StatusCode::SERVICE_UNAVAILABLE,
+    };
+
+    (status, Json(health))
+}
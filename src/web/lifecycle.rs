@@ -0,0 +1,80 @@
+use super::error::Result;
+use crate::context::Ctx;
+use crate::model::lifecycle::{
+    LifecycleBaseModelController as LifecycleBMC, LifecycleRecord, LifecycleTransition,
+};
+use crate::model::ModelManager;
+use axum::{
+    extract::{Extension, Json as ExtractJson, Path, State},
+    response::Json,
+};
+
+/// Get the lifecycle state of a microdevice
+///
+/// Returns the current state together with the history of transitions.
+#[utoipa::path(
+    get,
+    path = "/cluster/{clusterId}/device/{microdeviceId}/lifecycle",
+    tag = "Microdevices",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+        ("microdeviceId" = i32, Path, description="Microdevice ID"),
+    ),
+    responses(
+        (status = 200, body = LifecycleRecord),
+        (status = 401),
+        (status = 404),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn get_lifecycle(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path((cluster_id, microdevice_id)): Path<(String, i32)>,
+) -> Result<Json<LifecycleRecord>> {
+    Ok(Json(
+        LifecycleBMC::get_lifecycle(&mm, &ctx, cluster_id, microdevice_id).await?,
+    ))
+}
+
+/// Transition a microdevice to another lifecycle state
+///
+/// Allowed transitions are `provisioned` to any other state, `active` to
+/// `disabled` or `decommissioned` and `disabled` back to `active` or to
+/// `decommissioned`. Decommissioning is final.
+///
+/// Disabled and decommissioned microdevices are skipped by actions, their
+/// telemetry is rejected and registrar requests from them are refused.
+#[utoipa::path(
+    post,
+    path = "/cluster/{clusterId}/device/{microdeviceId}/lifecycle",
+    tag = "Microdevices",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+        ("microdeviceId" = i32, Path, description="Microdevice ID"),
+    ),
+    request_body = LifecycleTransition,
+    responses(
+        (status = 200, body = LifecycleRecord),
+        (status = 400, description = "Missing or too long reason"),
+        (status = 401),
+        (status = 403, description = "Viewers cannot change the lifecycle"),
+        (status = 404),
+        (status = 409, description = "Transition is not allowed"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn transition(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path((cluster_id, microdevice_id)): Path<(String, i32)>,
+    ExtractJson(data): Json<LifecycleTransition>,
+) -> Result<Json<LifecycleRecord>> {
+    Ok(Json(
+        LifecycleBMC::transition(&mm, &ctx, cluster_id, microdevice_id, data).await?,
+    ))
+}
@@ -1,17 +1,38 @@
 use crate::model::ModelManager;
+pub mod action_catalog;
+pub mod action_job;
+pub mod bulk_import;
 pub mod cluster;
+pub mod dead_letter;
+pub mod device_group;
 pub mod error;
+pub mod fake_device;
 mod guard;
+pub mod health;
+pub mod lifecycle;
 pub mod microdevice;
 pub mod rpc;
+pub mod schedule;
 pub mod session;
+pub mod shadow;
+pub mod topic;
 pub mod user;
 
 #[allow(unused_imports)]
-use axum::routing::{delete, get, post, put};
+use axum::routing::{delete, get, patch, post, put};
 use axum::Router;
 use tower_http::cors::AllowOrigin;
 
+/// Origins of the web clients allowed to call the API with credentials, also
+/// checked on WebSocket upgrades which CORS does not cover
+pub const ALLOWED_ORIGINS: [&str; 5] = [
+    "http://localhost:3000",
+    "http://localhost:3001",
+    "http://localhost:5173",
+    "https://iot-orchid.app",
+    "https://www.iot-orchid.app",
+];
+
 pub fn app(model_manager: ModelManager) -> Router {
     Router::new()
         .route("/clusters", post(cluster::create))
@@ -21,10 +42,108 @@ pub fn app(model_manager: ModelManager) -> Router {
             "/cluster/:clusterId/device/:microdeviceId",
             put(microdevice::update_device),
         )
+        .route(
+            "/cluster/:clusterId/actions/:jobId",
+            get(action_job::get_job),
+        )
+        .route(
+            "/cluster/:clusterId/device/:microdeviceId/action-history",
+            get(action_job::device_history),
+        )
+        .route(
+            "/cluster/:clusterId/schedules",
+            get(schedule::list_schedules),
+        )
+        .route(
+            "/cluster/:clusterId/schedules",
+            post(schedule::create_schedule),
+        )
+        .route(
+            "/cluster/:clusterId/schedules/:scheduleId",
+            get(schedule::get_schedule),
+        )
+        .route(
+            "/cluster/:clusterId/schedules/:scheduleId",
+            delete(schedule::delete_schedule),
+        )
+        .route(
+            "/cluster/:clusterId/schedules/:scheduleId/pause",
+            post(schedule::pause_schedule),
+        )
+        .route(
+            "/cluster/:clusterId/schedules/:scheduleId/resume",
+            post(schedule::resume_schedule),
+        )
+        .route("/cluster/:clusterId/groups", get(device_group::list_groups))
+        .route(
+            "/cluster/:clusterId/groups",
+            post(device_group::create_group),
+        )
+        .route(
+            "/cluster/:clusterId/groups/:groupName",
+            get(device_group::get_group),
+        )
+        .route(
+            "/cluster/:clusterId/groups/:groupName",
+            put(device_group::update_group),
+        )
+        .route(
+            "/cluster/:clusterId/groups/:groupName",
+            delete(device_group::delete_group),
+        )
+        .route(
+            "/cluster/:clusterId/groups/:groupName/members",
+            get(device_group::list_members),
+        )
+        .route(
+            "/cluster/:clusterId/device/:microdeviceId/actions",
+            get(action_catalog::list_actions),
+        )
+        .route(
+            "/cluster/:clusterId/device/:microdeviceId/actions",
+            post(action_catalog::create_action),
+        )
+        .route(
+            "/cluster/:clusterId/device/:microdeviceId/actions/:actionName",
+            put(action_catalog::update_action),
+        )
+        .route(
+            "/cluster/:clusterId/device/:microdeviceId/actions/:actionName",
+            delete(action_catalog::delete_action),
+        )
+        .route(
+            "/cluster/:clusterId/device/:microdeviceId/lifecycle",
+            get(lifecycle::get_lifecycle),
+        )
+        .route(
+            "/cluster/:clusterId/device/:microdeviceId/lifecycle",
+            post(lifecycle::transition),
+        )
+        .route(
+            "/cluster/:clusterId/device/:microdeviceId/shadow",
+            get(shadow::get_shadow),
+        )
+        .route(
+            "/cluster/:clusterId/device/:microdeviceId/topics",
+            get(topic::list_topics),
+        )
+        .route(
+            "/cluster/:clusterId/device/:microdeviceId/topics",
+            post(topic::add_topic),
+        )
+        .route(
+            "/cluster/:clusterId/device/:microdeviceId/topics",
+            delete(topic::remove_topic),
+        )
+        .route(
+            "/cluster/:clusterId/device/:microdeviceId/shadow",
+            patch(shadow::update_desired),
+        )
         .route(
             "/cluster/:clusterId/devices/actions",
             post(rpc::rpc_handler),
         )
+        .route("/cluster/:clusterId/ws", get(rpc::ws::ws_handler))
         .route("/cluster/:clusterId/devices", get(microdevice::get_devices))
         .route(
             "/cluster/:clusterId/devices",
@@ -34,29 +153,52 @@ pub fn app(model_manager: ModelManager) -> Router {
             "/cluster/:clusterId/devices",
             delete(microdevice::delete_device),
         )
+        .route(
+            "/cluster/:clusterId/devices/bulk",
+            post(bulk_import::import_devices),
+        )
+        .route(
+            "/admin/dead-letters/:queue",
+            get(dead_letter::list_dead_letters),
+        )
+        .route(
+            "/admin/dead-letters/:queue",
+            delete(dead_letter::purge_dead_letters),
+        )
+        .route(
+            "/admin/dead-letters/:queue/replay",
+            post(dead_letter::replay_dead_letters),
+        )
+        .route(
+            "/admin/fake-device/rules",
+            post(fake_device::script_fake_device),
+        )
+        .route(
+            "/admin/fake-device/messages/:queue",
+            post(fake_device::send_fake_device_message),
+        )
         .route("/logout", post(session::logout))
         .route("/status", get(session::status))
         .layer(axum::middleware::from_fn(guard::jwt_guard))
         .route("/login", post(session::login))
+        .route("/health", get(health::health))
         .layer(
             tower_http::cors::CorsLayer::new()
-                .allow_origin(AllowOrigin::list(vec![
-                    "http://localhost:3000".parse().unwrap(),
-                    "http://localhost:3001".parse().unwrap(),
-                    "http://localhost:5173".parse().unwrap(),
-                    "https://iot-orchid.app".parse().unwrap(),
-                    "https://www.iot-orchid.app".parse().unwrap(),
-                ]))
+                .allow_origin(AllowOrigin::list(
+                    ALLOWED_ORIGINS.map(|origin| origin.parse().unwrap()),
+                ))
                 .allow_credentials(true)
                 .allow_methods([
                     axum::http::Method::GET,
                     axum::http::Method::POST,
                     axum::http::Method::DELETE,
                     axum::http::Method::PUT,
+                    axum::http::Method::PATCH,
                 ])
                 .allow_headers(vec![
                     "content-type".parse().unwrap(),
                     "authorization".parse().unwrap(),
+                    rpc::IDEMPOTENCY_KEY_HEADER.parse().unwrap(),
                 ]),
         )
         .layer(tower_http::trace::TraceLayer::new_for_http())
@@ -1,9 +1,14 @@
+use crate::model::error::{Error as ModelError, ErrorKind};
 use axum_jrpc::error::{JsonRpcError, JsonRpcErrorReason};
 use axum_jrpc::Value;
 #[derive(Debug)]
 pub enum Error {
     SerdeJson(serde_json::Error),
+    Parse(serde_json::Error),
+    InvalidRequest(String),
     InvalidMethod(String),
+    InvalidParams(String),
+    Model(ModelError),
 }
 #[allow(dead_code)]
 pub type Result<T> = std::result::Result<T, Error>;
@@ -18,15 +23,54 @@ impl std::fmt::Display for Error {
 impl From<Error> for JsonRpcError {
     fn from(e: Error) -> Self {
         match e {
+            // Results that cannot be serialized
             Error::SerdeJson(e) => JsonRpcError::new(
-                JsonRpcErrorReason::InvalidRequest,
+                JsonRpcErrorReason::InternalError,
                 e.to_string(),
                 Value::default(),
             ),
 
+            Error::Parse(e) => JsonRpcError::new(
+                JsonRpcErrorReason::ParseError,
+                e.to_string(),
+                Value::default(),
+            ),
+
+            Error::InvalidRequest(e) => {
+                JsonRpcError::new(JsonRpcErrorReason::InvalidRequest, e, Value::default())
+            }
+
             Error::InvalidMethod(e) => {
                 JsonRpcError::new(JsonRpcErrorReason::MethodNotFound, e, Value::default())
             }
+
+            Error::InvalidParams(e) => {
+                JsonRpcError::new(JsonRpcErrorReason::InvalidParams, e, Value::default())
+            }
+
+            // Errors caused by the request itself are reported as invalid params
+            Error::Model(e) => match e.kind {
+                ErrorKind::ConfirmationRequired
+                | ErrorKind::InvalidActionPayload
+                | ErrorKind::InvalidTopicFormat
+                | ErrorKind::InvalidLabel
+                | ErrorKind::InvalidIdempotencyKey => JsonRpcError::new(
+                    JsonRpcErrorReason::InvalidParams,
+                    e.message,
+                    Value::default(),
+                ),
+                // The client should retry once the first request has finished
+                ErrorKind::IdempotencyKeyInUse => JsonRpcError::new(
+                    JsonRpcErrorReason::ServerError(-32000),
+                    e.message,
+                    Value::default(),
+                ),
+                _ => JsonRpcError::new(
+                    JsonRpcErrorReason::InternalError,
+                    e.to_string(),
+                    Value::default(),
+                ),
+            },
         }
     }
 }
@@ -36,3 +80,9 @@ impl From<serde_json::Error> for Error {
         Error::SerdeJson(e)
     }
 }
+
+impl From<ModelError> for Error {
+    fn from(e: ModelError) -> Self {
+        Error::Model(e)
+    }
+}
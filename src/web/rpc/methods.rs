@@ -0,0 +1,362 @@
+use super::error::{Error, Result};
+use crate::context::Ctx;
+use crate::model::action_job::ActionJobBaseModelController as ActionJobBMC;
+use crate::model::cluster::ClusterBaseModelController as ClusterBMC;
+use crate::model::device_group::DeviceGroupBaseModelController as DeviceGroupBMC;
+use crate::model::idempotency::IdempotencyBaseModelController as IdempotencyBMC;
+use crate::model::microdevice::{
+    ActionDryRun, ActionSummary, MicrodeviceBaseModelController as MicrodeviceBMC, MicrodeviceId,
+    MicrodeviceRecord,
+};
+use crate::model::telemetry::TelemetryBaseModelController as TelemetryBMC;
+use crate::model::ModelManager;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use strum::IntoEnumIterator;
+
+/// Methods served by the JSON-RPC endpoint
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, strum::EnumString, strum::AsRefStr, strum::EnumIter,
+)]
+pub enum RpcMethod {
+    #[strum(serialize = "device.action")]
+    DeviceAction,
+    #[strum(serialize = "device.list")]
+    DeviceList,
+    #[strum(serialize = "device.get")]
+    DeviceGet,
+    #[strum(serialize = "cluster.summary")]
+    ClusterSummary,
+    #[strum(serialize = "telemetry.latest")]
+    TelemetryLatest,
+    #[strum(serialize = "rpc.discover")]
+    Discover,
+}
+
+#[derive(Serialize)]
+struct MethodDescription {
+    name: String,
+    description: &'static str,
+    params: Value,
+}
+
+impl RpcMethod {
+    fn description(self) -> &'static str {
+        match self {
+            Self::DeviceAction => {
+                "Triggers an action on microdevices selected by id, group or the whole cluster, `dry_run` only reports what would be sent"
+            }
+            Self::DeviceList => "Lists the microdevices of the cluster",
+            Self::DeviceGet => "Returns a single microdevice",
+            Self::ClusterSummary => "Counts the microdevices and groups of the cluster",
+            Self::TelemetryLatest => "Returns the most recent telemetry samples of a microdevice",
+            Self::Discover => "Lists the available methods",
+        }
+    }
+
+    /// Example params shown by `rpc.discover`
+    fn params_example(self) -> Value {
+        match self {
+            Self::DeviceAction => json!({
+                "action": "restart",
+                "microdevice_id": [1, 2],
+                "group": null,
+                "cluster_wide": false,
+                "confirm": false,
+                "async": false,
+                "dry_run": false,
+                "idempotency_key": "3f6c1a52-restart-line-2",
+                "payload": {}
+            }),
+            Self::DeviceList => json!({
+                "name": "sensor-1",
+                "include_topics": true,
+                "include_description": true
+            }),
+            Self::DeviceGet => json!({ "microdevice_id": 1 }),
+            Self::ClusterSummary | Self::Discover => Value::Null,
+            Self::TelemetryLatest => json!({
+                "microdevice_id": 1,
+                "topic": "/temperature",
+                "limit": 10
+            }),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MicrodeviceActionParamsId {
+    Single(MicrodeviceId),
+    Multiple(Vec<MicrodeviceId>),
+}
+
+#[derive(Deserialize)]
+struct MicrodeviceActionParams {
+    action: String,
+    cluster_wide: Option<bool>,
+    microdevice_id: Option<MicrodeviceActionParamsId>,
+    /// Name of a device group whose members are targeted
+    group: Option<String>,
+    payload: Option<Value>,
+    /// Confirms destructive actions sent to the whole cluster
+    confirm: Option<bool>,
+    /// Records an action job and returns it without waiting for the microdevices
+    #[serde(rename = "async")]
+    run_async: Option<bool>,
+    /// Returns the messages that would be sent without sending them
+    dry_run: Option<bool>,
+    /// Repeating a key returns the result of the first request instead of
+    /// sending the action again
+    idempotency_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DeviceListParams {
+    name: Option<String>,
+    include_topics: Option<bool>,
+    include_description: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct DeviceGetParams {
+    microdevice_id: i32,
+}
+
+#[derive(Deserialize)]
+struct TelemetryLatestParams {
+    microdevice_id: i32,
+    topic: Option<String>,
+    limit: Option<u64>,
+}
+
+/// Executes `method` and returns its serialized result.
+///
+/// `idempotency_key` is used for actions that do not carry their own key.
+pub async fn dispatch(
+    mm: &ModelManager,
+    ctx: &Ctx,
+    cluster_id: &String,
+    method: RpcMethod,
+    params: Value,
+    idempotency_key: Option<String>,
+) -> Result<Value> {
+    let result = match method {
+        RpcMethod::DeviceAction => {
+            // The key is not part of the request it deduplicates
+            let mut request = params.clone();
+            if let Value::Object(request) = &mut request {
+                request.remove("idempotency_key");
+            }
+
+            let params: MicrodeviceActionParams = parse_params(params)?;
+
+            // Dry runs send nothing and must not take the key of the real request
+            let key = match params.dry_run {
+                Some(true) => None,
+                _ => params.idempotency_key.clone().or(idempotency_key),
+            };
+
+            match key {
+                Some(key) => {
+                    IdempotencyBMC::run(mm, ctx, cluster_id, key, request, || {
+                        device_action(mm, ctx, cluster_id, params)
+                    })
+                    .await?
+                }
+                None => device_action(mm, ctx, cluster_id, params).await?,
+            }
+        }
+        RpcMethod::DeviceList => {
+            let params: DeviceListParams = parse_params(params)?;
+
+            serde_json::to_value(
+                MicrodeviceBMC::get_microdevice_from_cluster(
+                    mm,
+                    ctx,
+                    cluster_id.to_owned(),
+                    None::<Vec<i32>>,
+                    params.name.map(|name| vec![name]),
+                    params.include_topics,
+                    params.include_description,
+                    Some(true),
+                )
+                .await?,
+            )?
+        }
+        RpcMethod::DeviceGet => {
+            let params: DeviceGetParams = parse_params(params)?;
+            let device =
+                MicrodeviceBMC::find_in_cluster(mm, ctx, cluster_id, params.microdevice_id).await?;
+
+            serde_json::to_value(MicrodeviceRecord::from(device))?
+        }
+        RpcMethod::ClusterSummary => {
+            serde_json::to_value(ClusterBMC::summary(mm, ctx, cluster_id.to_owned()).await?)?
+        }
+        RpcMethod::TelemetryLatest => {
+            let params: TelemetryLatestParams = parse_params(params)?;
+
+            serde_json::to_value(
+                TelemetryBMC::latest(
+                    mm,
+                    ctx,
+                    cluster_id.to_owned(),
+                    params.microdevice_id,
+                    params.topic,
+                    params.limit,
+                )
+                .await?,
+            )?
+        }
+        RpcMethod::Discover => serde_json::to_value(
+            RpcMethod::iter()
+                .map(|m| MethodDescription {
+                    name: m.as_ref().to_string(),
+                    description: m.description(),
+                    params: m.params_example(),
+                })
+                .collect::<Vec<_>>(),
+        )?,
+    };
+
+    Ok(result)
+}
+
+/// Deserializes the params of a method, missing params count as an empty object.
+pub(super) fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T> {
+    let params = match params {
+        Value::Null => json!({}),
+        params => params,
+    };
+
+    serde_json::from_value(params).map_err(|e| Error::InvalidParams(e.to_string()))
+}
+
+async fn device_action(
+    mm: &ModelManager,
+    ctx: &Ctx,
+    cluster_id: &String,
+    params: MicrodeviceActionParams,
+) -> Result<Value> {
+    let cluster_wide = Some(true) == params.cluster_wide;
+    let targets = [
+        cluster_wide,
+        params.group.is_some(),
+        params.microdevice_id.is_some(),
+    ];
+
+    if targets.iter().filter(|t| **t).count() > 1 {
+        return Err(Error::InvalidParams(
+            "`cluster_wide`, `group` and `microdevice_id` are mutually exclusive".to_string(),
+        ));
+    }
+
+    let dry_run = Some(true) == params.dry_run;
+    let run_async = Some(true) == params.run_async;
+
+    if dry_run && run_async {
+        return Err(Error::InvalidParams(
+            "`dry_run` and `async` are mutually exclusive".to_string(),
+        ));
+    }
+
+    let action = params.action;
+    let payload = params.payload.unwrap_or_default();
+
+    if dry_run || run_async {
+        let ids: Vec<MicrodeviceId> = match (cluster_wide, params.group, params.microdevice_id) {
+            (true, _, _) => MicrodeviceBMC::cluster_action_targets(
+                mm,
+                ctx,
+                cluster_id,
+                &action.clone().into(),
+                params.confirm.unwrap_or(false),
+            )
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+            (false, Some(group), _) => {
+                DeviceGroupBMC::resolve_member_ids(mm, ctx, cluster_id, &group)
+                    .await?
+                    .into_iter()
+                    .map(Into::into)
+                    .collect()
+            }
+            (false, None, Some(MicrodeviceActionParamsId::Single(id))) => vec![id],
+            (false, None, Some(MicrodeviceActionParamsId::Multiple(ids))) => ids,
+            (false, None, None) => return Err(missing_target()),
+        };
+
+        if dry_run {
+            // Nothing to resolve for an empty cluster or group
+            let dry_run = match ids.is_empty() {
+                true => ActionDryRun::empty(),
+                false => {
+                    MicrodeviceBMC::dry_run_action(
+                        mm,
+                        ctx,
+                        cluster_id.to_owned(),
+                        ids,
+                        action,
+                        payload,
+                    )
+                    .await?
+                }
+            };
+
+            return Ok(serde_json::to_value(dry_run)?);
+        }
+
+        let job = ActionJobBMC::submit(mm, ctx, cluster_id.to_owned(), ids, action.into(), payload)
+            .await?;
+
+        return Ok(serde_json::to_value(job)?);
+    }
+
+    // Send the action to every eligible microdevice of the cluster
+    if cluster_wide {
+        let summary = MicrodeviceBMC::trigger_cluster_action(
+            mm,
+            ctx,
+            cluster_id.to_owned(),
+            action,
+            payload,
+            params.confirm.unwrap_or(false),
+        )
+        .await?;
+
+        return Ok(serde_json::to_value(summary)?);
+    }
+
+    let ids: Vec<MicrodeviceId> = match (params.group, params.microdevice_id) {
+        // Fan out the action to every current member of the group
+        (Some(group), _) => DeviceGroupBMC::resolve_member_ids(mm, ctx, cluster_id, &group)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+        (None, Some(MicrodeviceActionParamsId::Single(id))) => vec![id],
+        (None, Some(MicrodeviceActionParamsId::Multiple(ids))) => ids,
+        (None, None) => return Err(missing_target()),
+    };
+
+    // An empty group has nothing to act on
+    if ids.is_empty() {
+        return Ok(serde_json::to_value(ActionSummary::new(vec![]))?);
+    }
+
+    let summary =
+        MicrodeviceBMC::trigger_action(mm, ctx, cluster_id.to_owned(), ids, action, payload)
+            .await?;
+
+    Ok(serde_json::to_value(summary)?)
+}
+
+fn missing_target() -> Error {
+    Error::InvalidParams(
+        "one of `microdevice_id`, `group` or `cluster_wide` is required".to_string(),
+    )
+}
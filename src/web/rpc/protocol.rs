@@ -0,0 +1,419 @@
+use super::error::Error;
+use axum_jrpc::error::JsonRpcError;
+use axum_jrpc::{Id, JsonRpcResponse};
+use serde_json::Value;
+use std::future::Future;
+
+const JSONRPC_VERSION: &str = "2.0";
+
+/// A single entry of a JSON-RPC payload
+#[derive(Debug)]
+enum Request {
+    Call {
+        id: Id,
+        method: String,
+        params: Value,
+    },
+    /// A request without `id`, executed without answering
+    Notification { method: String, params: Value },
+    /// An entry that is not a valid request object, answered with an error
+    Invalid(JsonRpcResponse),
+}
+
+/// Handles a JSON-RPC 2.0 payload, either a single request or a batch.
+///
+/// `execute` is called with the id, method name and params of every valid
+/// request, notifications have no id.
+/// Returns `None` when nothing must be sent back, i.e. when the payload only
+/// contained notifications.
+pub async fn handle<F, Fut>(body: &[u8], max_batch_size: usize, execute: F) -> Option<Value>
+where
+    F: Fn(Option<Id>, String, Value) -> Fut,
+    Fut: Future<Output = std::result::Result<Value, JsonRpcError>>,
+{
+    let payload: Value = match serde_json::from_slice(body) {
+        Ok(payload) => payload,
+        Err(e) => {
+            return Some(to_value(JsonRpcResponse::error(
+                Id::None(()),
+                Error::Parse(e).into(),
+            )))
+        }
+    };
+
+    let reqs = match payload {
+        Value::Array(reqs) => reqs,
+        req => {
+            return handle_request(req, &execute).await.map(to_value);
+        }
+    };
+
+    if reqs.is_empty() {
+        return Some(invalid_request(Id::None(()), "batch must not be empty"));
+    }
+
+    if reqs.len() > max_batch_size {
+        return Some(invalid_request(
+            Id::None(()),
+            &format!(
+                "batch must not contain more than {} requests",
+                max_batch_size
+            ),
+        ));
+    }
+
+    // Responses keep the order of the requests they answer
+    let responses: Vec<JsonRpcResponse> =
+        futures::future::join_all(reqs.into_iter().map(|req| handle_request(req, &execute)))
+            .await
+            .into_iter()
+            .flatten()
+            .collect();
+
+    // A batch made only of notifications is not answered, not even with an empty array
+    if responses.is_empty() {
+        return None;
+    }
+
+    Some(to_value(responses))
+}
+
+async fn handle_request<F, Fut>(req: Value, execute: &F) -> Option<JsonRpcResponse>
+where
+    F: Fn(Option<Id>, String, Value) -> Fut,
+    Fut: Future<Output = std::result::Result<Value, JsonRpcError>>,
+{
+    match parse_request(req) {
+        Request::Call { id, method, params } => {
+            Some(match execute(Some(id.clone()), method, params).await {
+                Ok(result) => JsonRpcResponse::success(id, result),
+                Err(e) => JsonRpcResponse::error(id, e),
+            })
+        }
+        Request::Notification { method, params } => {
+            // Errors of notifications are not reported back to the client
+            let _ = execute(None, method, params).await;
+            None
+        }
+        Request::Invalid(response) => Some(response),
+    }
+}
+
+/// Validates a request object as described by the JSON-RPC 2.0 specification.
+fn parse_request(req: Value) -> Request {
+    let Value::Object(mut req) = req else {
+        return Request::Invalid(JsonRpcResponse::error(
+            Id::None(()),
+            Error::InvalidRequest("request must be an object".to_string()).into(),
+        ));
+    };
+
+    // An absent `id` marks a notification, an explicit `null` is still a call
+    let id = match req.remove("id").map(serde_json::from_value::<Id>) {
+        None => None,
+        Some(Ok(id)) => Some(id),
+        Some(Err(_)) => {
+            return Request::Invalid(JsonRpcResponse::error(
+                Id::None(()),
+                Error::InvalidRequest("`id` must be a string, an integer or null".to_string())
+                    .into(),
+            ))
+        }
+    };
+
+    let invalid = |message: &str| {
+        Request::Invalid(JsonRpcResponse::error(
+            id.clone().unwrap_or(Id::None(())),
+            Error::InvalidRequest(message.to_string()).into(),
+        ))
+    };
+
+    if req.get("jsonrpc").and_then(Value::as_str) != Some(JSONRPC_VERSION) {
+        return invalid("`jsonrpc` must be exactly \"2.0\"");
+    }
+
+    let method = match req.remove("method") {
+        Some(Value::String(method)) => method,
+        _ => return invalid("`method` must be a string"),
+    };
+
+    let params = match req.remove("params") {
+        None => Value::Null,
+        Some(params @ (Value::Array(_) | Value::Object(_))) => params,
+        Some(_) => return invalid("`params` must be an array or an object"),
+    };
+
+    match id {
+        Some(id) => Request::Call { id, method, params },
+        None => Request::Notification { method, params },
+    }
+}
+
+fn invalid_request(id: Id, message: &str) -> Value {
+    to_value(JsonRpcResponse::error(
+        id,
+        Error::InvalidRequest(message.to_string()).into(),
+    ))
+}
+
+fn to_value<T: serde::Serialize>(response: T) -> Value {
+    serde_json::to_value(response).unwrap_or_default()
+}
+
+/// Conformance tests based on the examples of the JSON-RPC 2.0 specification
+/// (https://www.jsonrpc.org/specification#examples)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum_jrpc::error::JsonRpcErrorReason;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Methods used by the examples of the specification
+    async fn execute(
+        notified: &AtomicUsize,
+        method: String,
+        params: Value,
+    ) -> std::result::Result<Value, JsonRpcError> {
+        match method.as_str() {
+            "subtract" => {
+                let (minuend, subtrahend) = match &params {
+                    Value::Array(p) => (p[0].as_i64(), p[1].as_i64()),
+                    p => (p["minuend"].as_i64(), p["subtrahend"].as_i64()),
+                };
+                Ok(json!(
+                    minuend.unwrap_or_default() - subtrahend.unwrap_or_default()
+                ))
+            }
+            "sum" => Ok(json!(params
+                .as_array()
+                .map(|p| p.iter().filter_map(Value::as_i64).sum::<i64>())
+                .unwrap_or_default())),
+            "get_data" => Ok(json!(["hello", 5])),
+            "update" | "notify_hello" | "notify_sum" => {
+                notified.fetch_add(1, Ordering::SeqCst);
+                Ok(Value::Null)
+            }
+            _ => Err(JsonRpcError::new(
+                JsonRpcErrorReason::MethodNotFound,
+                "Method not found".to_string(),
+                Value::Null,
+            )),
+        }
+    }
+
+    async fn call(body: &str) -> (Option<Value>, usize) {
+        call_with_limit(body, 100).await
+    }
+
+    async fn call_with_limit(body: &str, max_batch_size: usize) -> (Option<Value>, usize) {
+        let notified = AtomicUsize::new(0);
+        let response = handle(body.as_bytes(), max_batch_size, |_, method, params| {
+            execute(&notified, method, params)
+        })
+        .await;
+
+        (response, notified.load(Ordering::SeqCst))
+    }
+
+    fn assert_error(response: &Value, code: i64, id: Value) {
+        assert_eq!(response["jsonrpc"], "2.0");
+        assert_eq!(
+            response["error"]["code"], code,
+            "unexpected error in {}",
+            response
+        );
+        assert_eq!(response["id"], id);
+        assert!(response.get("result").is_none());
+    }
+
+    #[tokio::test]
+    async fn positional_params() {
+        let (response, _) =
+            call(r#"{"jsonrpc": "2.0", "method": "subtract", "params": [42, 23], "id": 1}"#).await;
+        assert_eq!(
+            response,
+            Some(json!({"jsonrpc": "2.0", "result": 19, "id": 1}))
+        );
+
+        let (response, _) =
+            call(r#"{"jsonrpc": "2.0", "method": "subtract", "params": [23, 42], "id": 2}"#).await;
+        assert_eq!(
+            response,
+            Some(json!({"jsonrpc": "2.0", "result": -19, "id": 2}))
+        );
+    }
+
+    #[tokio::test]
+    async fn named_params() {
+        let (response, _) = call(
+            r#"{"jsonrpc": "2.0", "method": "subtract", "params": {"subtrahend": 23, "minuend": 42}, "id": 3}"#,
+        )
+        .await;
+        assert_eq!(
+            response,
+            Some(json!({"jsonrpc": "2.0", "result": 19, "id": 3}))
+        );
+
+        let (response, _) = call(
+            r#"{"jsonrpc": "2.0", "method": "subtract", "params": {"minuend": 42, "subtrahend": 23}, "id": 4}"#,
+        )
+        .await;
+        assert_eq!(
+            response,
+            Some(json!({"jsonrpc": "2.0", "result": 19, "id": 4}))
+        );
+    }
+
+    #[tokio::test]
+    async fn notifications_are_executed_without_response() {
+        let (response, notified) =
+            call(r#"{"jsonrpc": "2.0", "method": "update", "params": [1,2,3,4,5]}"#).await;
+        assert_eq!(response, None);
+        assert_eq!(notified, 1);
+
+        let (response, _) = call(r#"{"jsonrpc": "2.0", "method": "foobar"}"#).await;
+        assert_eq!(response, None);
+    }
+
+    #[tokio::test]
+    async fn non_existent_method() {
+        let (response, _) = call(r#"{"jsonrpc": "2.0", "method": "foobar", "id": "1"}"#).await;
+        assert_error(&response.unwrap(), -32601, json!("1"));
+    }
+
+    #[tokio::test]
+    async fn invalid_json() {
+        let (response, _) =
+            call(r#"{"jsonrpc": "2.0", "method": "foobar, "params": "bar", "baz]"#).await;
+        assert_error(&response.unwrap(), -32700, Value::Null);
+    }
+
+    #[tokio::test]
+    async fn invalid_request_object() {
+        let (response, _) = call(r#"{"jsonrpc": "2.0", "method": 1, "params": "bar"}"#).await;
+        assert_error(&response.unwrap(), -32600, Value::Null);
+    }
+
+    #[tokio::test]
+    async fn invalid_request_keeps_id() {
+        let (response, _) = call(r#"{"jsonrpc": "1.0", "method": "sum", "id": 7}"#).await;
+        assert_error(&response.unwrap(), -32600, json!(7));
+
+        let (response, _) =
+            call(r#"{"jsonrpc": "2.0", "method": "sum", "params": 3, "id": "a"}"#).await;
+        assert_error(&response.unwrap(), -32600, json!("a"));
+    }
+
+    #[tokio::test]
+    async fn null_id_is_answered() {
+        let (response, _) =
+            call(r#"{"jsonrpc": "2.0", "method": "sum", "params": [1, 2], "id": null}"#).await;
+        assert_eq!(
+            response,
+            Some(json!({"jsonrpc": "2.0", "result": 3, "id": null}))
+        );
+    }
+
+    #[tokio::test]
+    async fn batch_invalid_json() {
+        let (response, _) = call(
+            r#"[
+                {"jsonrpc": "2.0", "method": "sum", "params": [1,2,4], "id": "1"},
+                {"jsonrpc": "2.0", "method"
+            ]"#,
+        )
+        .await;
+        assert_error(&response.unwrap(), -32700, Value::Null);
+    }
+
+    #[tokio::test]
+    async fn empty_batch() {
+        let (response, _) = call("[]").await;
+        let response = response.unwrap();
+        assert!(
+            response.is_object(),
+            "expected a single response, got {}",
+            response
+        );
+        assert_error(&response, -32600, Value::Null);
+    }
+
+    #[tokio::test]
+    async fn invalid_batch() {
+        let (response, _) = call("[1]").await;
+        let responses = response.unwrap();
+        assert_eq!(responses.as_array().unwrap().len(), 1);
+        assert_error(&responses[0], -32600, Value::Null);
+
+        let (response, _) = call("[1,2,3]").await;
+        let responses = response.unwrap();
+        assert_eq!(responses.as_array().unwrap().len(), 3);
+        for response in responses.as_array().unwrap() {
+            assert_error(response, -32600, Value::Null);
+        }
+    }
+
+    #[tokio::test]
+    async fn mixed_batch() {
+        let (response, notified) = call(
+            r#"[
+                {"jsonrpc": "2.0", "method": "sum", "params": [1,2,4], "id": "1"},
+                {"jsonrpc": "2.0", "method": "notify_hello", "params": [7]},
+                {"jsonrpc": "2.0", "method": "subtract", "params": [42,23], "id": "2"},
+                {"foo": "boo"},
+                {"jsonrpc": "2.0", "method": "foo.get", "params": {"name": "myself"}, "id": "5"},
+                {"jsonrpc": "2.0", "method": "get_data", "id": "9"}
+            ]"#,
+        )
+        .await;
+        let responses = response.unwrap();
+        let responses = responses.as_array().unwrap();
+
+        assert_eq!(notified, 1);
+        assert_eq!(responses.len(), 5);
+        assert_eq!(
+            responses[0],
+            json!({"jsonrpc": "2.0", "result": 7, "id": "1"})
+        );
+        assert_eq!(
+            responses[1],
+            json!({"jsonrpc": "2.0", "result": 19, "id": "2"})
+        );
+        assert_error(&responses[2], -32600, Value::Null);
+        assert_error(&responses[3], -32601, json!("5"));
+        assert_eq!(
+            responses[4],
+            json!({"jsonrpc": "2.0", "result": ["hello", 5], "id": "9"})
+        );
+    }
+
+    #[tokio::test]
+    async fn batch_of_notifications() {
+        let (response, notified) = call(
+            r#"[
+                {"jsonrpc": "2.0", "method": "notify_sum", "params": [1,2,4]},
+                {"jsonrpc": "2.0", "method": "notify_hello", "params": [7]}
+            ]"#,
+        )
+        .await;
+        assert_eq!(response, None);
+        assert_eq!(notified, 2);
+    }
+
+    #[tokio::test]
+    async fn batch_size_limit() {
+        let batch = r#"[
+            {"jsonrpc": "2.0", "method": "sum", "params": [1], "id": 1},
+            {"jsonrpc": "2.0", "method": "sum", "params": [2], "id": 2},
+            {"jsonrpc": "2.0", "method": "notify_hello"}
+        ]"#;
+
+        let (response, notified) = call_with_limit(batch, 2).await;
+        assert_error(&response.unwrap(), -32600, Value::Null);
+        assert_eq!(notified, 0);
+
+        let (response, _) = call_with_limit(batch, 3).await;
+        assert_eq!(response.unwrap().as_array().unwrap().len(), 2);
+    }
+}
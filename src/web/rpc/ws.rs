@@ -0,0 +1,429 @@
+use super::error::{Error, Result};
+use super::methods::parse_params;
+use super::protocol;
+use crate::auth::jwt_auth::Claims;
+use crate::config::CONFIG;
+use crate::context::Ctx;
+use crate::model::action_job::ActionJobBaseModelController as ActionJobBMC;
+use crate::model::cluster::ClusterBaseModelController as ClusterBMC;
+use crate::model::common::parse_cluster_id;
+use crate::model::error::{Error as ModelError, ErrorKind};
+use crate::model::event_bus::LiveEvent;
+use crate::model::microdevice::MicrodeviceBaseModelController as MicrodeviceBMC;
+use crate::model::ModelManager;
+use crate::web::ALLOWED_ORIGINS;
+use axum::body::Bytes;
+use axum::extract::ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Extension, Path, State};
+use axum::http::{header, HeaderMap};
+use axum::response::Response;
+use axum_jrpc::error::JsonRpcError;
+use futures::{SinkExt, StreamExt};
+use sea_orm::prelude::Uuid;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{mpsc, Semaphore};
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+/// Messages waiting to be written to a slow client
+const OUTBOX_SIZE: usize = 256;
+
+/// Live events a connection can subscribe to
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+enum EventTopic {
+    DeviceStatus,
+    JobProgress,
+    Telemetry,
+}
+
+#[derive(Deserialize)]
+struct SubscribeParams {
+    topic: EventTopic,
+    /// Microdevices whose events are pushed, every microdevice when missing
+    microdevice_id: Option<Vec<i32>>,
+    /// Job whose progress is pushed, every job of the cluster when missing
+    job_id: Option<Uuid>,
+}
+
+#[derive(Deserialize)]
+struct UnsubscribeParams {
+    subscription: u64,
+}
+
+struct Subscription {
+    topic: EventTopic,
+    microdevice_ids: Option<HashSet<i32>>,
+    job_id: Option<Uuid>,
+}
+
+impl Subscription {
+    fn matches(&self, event: &LiveEvent) -> bool {
+        let topic_matches = match event {
+            LiveEvent::DeviceStatus { .. } => self.topic == EventTopic::DeviceStatus,
+            LiveEvent::JobProgress { job_id, .. } => {
+                self.topic == EventTopic::JobProgress && self.job_id.is_none_or(|id| id == *job_id)
+            }
+            LiveEvent::Telemetry { .. } => self.topic == EventTopic::Telemetry,
+        };
+
+        // Job completion is not tied to a microdevice and reaches every job subscriber
+        topic_matches
+            && match (&self.microdevice_ids, event.microdevice_id()) {
+                (Some(ids), Some(id)) => ids.contains(&id),
+                _ => true,
+            }
+    }
+}
+
+/// Subscriptions of a single connection
+#[derive(Default)]
+struct Subscriptions {
+    next_id: u64,
+    active: BTreeMap<u64, Subscription>,
+}
+
+/// State shared by the tasks serving a connection
+struct Session {
+    mm: ModelManager,
+    ctx: Ctx,
+    cluster_id: String,
+    subscriptions: Mutex<Subscriptions>,
+    outbox: mpsc::Sender<Message>,
+}
+
+/// Open a JSON-RPC WebSocket connection
+///
+/// The connection speaks the JSON-RPC methods of the `devices/actions`
+/// endpoint. Live events are subscribed with `events.subscribe` (`topic`:
+/// `device_status`, `job_progress` or `telemetry`, optional `microdevice_id`
+/// list, optional `job_id`), which returns a subscription id, and cancelled
+/// with `events.unsubscribe` (`subscription`). Events are pushed as
+/// `events.notify` notifications carrying the subscription id and the event,
+/// a connection that falls behind receives `events.lagged` with the number of
+/// skipped events.
+///
+/// The connection is closed once the access token it was opened with expires
+/// or the user cannot access the cluster anymore, which is checked every
+/// `rpc.access_check_interval` seconds.
+#[utoipa::path(
+    get,
+    path = "/cluster/{clusterId}/ws",
+    tag = "Microdevices",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID of an existing cluster"),
+    ),
+    responses(
+        (status = 101, description = "Switching to the WebSocket protocol"),
+        (status = 401),
+        (status = 403, description = "The request comes from an origin that is not allowed"),
+        (status = 404),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn ws_handler(
+    State(mm): State<ModelManager>,
+    Path(cluster_id): Path<String>,
+    Extension(ctx): Extension<Ctx>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> crate::web::error::Result<Response> {
+    // Browsers send the cookies of the user with an upgrade started by any
+    // page, only the web clients of the API may open a connection. Clients
+    // that are not browsers send no origin.
+    if let Some(origin) = headers.get(header::ORIGIN) {
+        if !ALLOWED_ORIGINS.iter().any(|allowed| origin == allowed) {
+            warn!("WebSocket upgrade refused for origin {:?}", origin);
+            return Err(crate::web::error::Error::ForbiddenOrigin);
+        }
+    }
+
+    // Refuse the upgrade for clusters the user cannot see
+    ClusterBMC::exists(&mm, &ctx, cluster_id.clone()).await?;
+    let cluster_uuid = parse_cluster_id(&cluster_id)?;
+
+    let now = chrono::Utc::now().timestamp().max(0) as u64;
+    let expires_at = Instant::now() + Duration::from_secs((claims.exp as u64).saturating_sub(now));
+
+    Ok(ws.on_upgrade(move |socket| serve(socket, mm, ctx, cluster_id, cluster_uuid, expires_at)))
+}
+
+async fn serve(
+    socket: WebSocket,
+    mm: ModelManager,
+    ctx: Ctx,
+    cluster_id: String,
+    cluster_uuid: Uuid,
+    expires_at: Instant,
+) {
+    let (mut sink, mut stream) = socket.split();
+    let (outbox, mut outgoing) = mpsc::channel::<Message>(OUTBOX_SIZE);
+
+    let mut writer = tokio::spawn(async move {
+        while let Some(msg) = outgoing.recv().await {
+            let closing = matches!(msg, Message::Close(_));
+            if sink.send(msg).await.is_err() || closing {
+                break;
+            }
+        }
+    });
+
+    let session = Arc::new(Session {
+        mm,
+        ctx,
+        cluster_id,
+        subscriptions: Mutex::new(Subscriptions::default()),
+        outbox,
+    });
+
+    let forwarder = tokio::spawn(forward_events(session.clone(), cluster_uuid));
+    let pending = Arc::new(Semaphore::new(CONFIG.rpc.max_pending_requests.max(1)));
+
+    let expiry = tokio::time::sleep_until(expires_at);
+    tokio::pin!(expiry);
+
+    let check_interval = Duration::from_secs(CONFIG.rpc.access_check_interval.max(1));
+    let mut access_check =
+        tokio::time::interval_at(Instant::now() + check_interval, check_interval);
+
+    let mut close_reason = None;
+
+    loop {
+        let msg = tokio::select! {
+            msg = stream.next() => msg,
+            _ = &mut expiry => {
+                close_reason = Some("access token expired");
+                break;
+            }
+            _ = access_check.tick() => {
+                let (mm, ctx, cluster_id) = (&session.mm, &session.ctx, &session.cluster_id);
+
+                // Database failures keep the connection open until the next check
+                if let Err(ModelError {
+                    kind: ErrorKind::ClusterNotFound,
+                    ..
+                }) = ClusterBMC::exists(mm, ctx, cluster_id.clone()).await
+                {
+                    close_reason = Some("cluster is not accessible anymore");
+                    break;
+                }
+                continue;
+            }
+        };
+
+        let Some(msg) = msg else {
+            break;
+        };
+
+        let body = match msg {
+            Ok(Message::Text(text)) => Bytes::from(text),
+            Ok(Message::Binary(data)) => Bytes::from(data),
+            Ok(Message::Close(_)) | Err(_) => break,
+            // Pings are answered by axum
+            Ok(_) => continue,
+        };
+
+        // Requests run concurrently so a slow action does not hold back
+        // others, up to `rpc.max_pending_requests` before reading stops
+        let Ok(permit) = pending.clone().acquire_owned().await else {
+            break;
+        };
+
+        let session = session.clone();
+        tokio::spawn(async move {
+            let response =
+                protocol::handle(&body, CONFIG.rpc.max_batch_size, |_, method, params| {
+                    execute(&session, method, params)
+                })
+                .await;
+
+            if let Some(response) = response {
+                let _ = session
+                    .outbox
+                    .send(Message::Text(response.to_string()))
+                    .await;
+            }
+
+            drop(permit);
+        });
+    }
+
+    debug!(
+        "WebSocket connection to cluster `{}` closed",
+        session.cluster_id
+    );
+
+    forwarder.abort();
+
+    if let Some(reason) = close_reason {
+        let frame = CloseFrame {
+            code: close_code::POLICY,
+            reason: reason.into(),
+        };
+
+        // Gives the writer a moment to deliver the frame after pending messages
+        if session
+            .outbox
+            .send(Message::Close(Some(frame)))
+            .await
+            .is_ok()
+        {
+            let _ = tokio::time::timeout(Duration::from_secs(1), &mut writer).await;
+        }
+    }
+
+    writer.abort();
+}
+
+/// Pushes the live events of the cluster matching a subscription of the session.
+async fn forward_events(session: Arc<Session>, cluster_uuid: Uuid) {
+    let mut events = session.mm.events.subscribe();
+
+    loop {
+        let notifications: Vec<Value> = match events.recv().await {
+            Ok(event) if event.cluster_id() == cluster_uuid => {
+                let subscriptions = match session.subscriptions.lock() {
+                    Ok(subscriptions) => subscriptions,
+                    Err(_) => return,
+                };
+
+                subscriptions
+                    .active
+                    .iter()
+                    .filter(|(_, subscription)| subscription.matches(&event))
+                    .map(|(id, _)| {
+                        notification(
+                            "events.notify",
+                            json!({ "subscription": id, "event": event }),
+                        )
+                    })
+                    .collect()
+            }
+            Ok(_) => continue,
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("WebSocket connection skipped {} live events", skipped);
+                vec![notification("events.lagged", json!({ "skipped": skipped }))]
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        for notification in notifications {
+            if session
+                .outbox
+                .send(Message::Text(notification.to_string()))
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+    }
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+// Function executing a JSON-RPC method received over the WebSocket connection
+async fn execute(
+    session: &Session,
+    method: String,
+    params: Value,
+) -> std::result::Result<Value, JsonRpcError> {
+    match method.as_str() {
+        "events.subscribe" => Ok(subscribe(session, parse_params(params)?).await?),
+        "events.unsubscribe" => Ok(unsubscribe(session, parse_params(params)?)?),
+        _ => {
+            super::execute(
+                &session.mm,
+                &session.ctx,
+                &session.cluster_id,
+                method,
+                params,
+                None,
+            )
+            .await
+        }
+    }
+}
+
+async fn subscribe(session: &Session, params: SubscribeParams) -> Result<Value> {
+    let (mm, ctx, cluster_id) = (&session.mm, &session.ctx, &session.cluster_id);
+
+    if params.job_id.is_some() && params.topic != EventTopic::JobProgress {
+        return Err(Error::InvalidParams(
+            "`job_id` is only allowed for `job_progress`".to_string(),
+        ));
+    }
+
+    // Telemetry of a whole cluster would flood the connection
+    if params.topic == EventTopic::Telemetry
+        && params.microdevice_id.as_ref().is_none_or(Vec::is_empty)
+    {
+        return Err(Error::InvalidParams(
+            "`telemetry` requires at least one `microdevice_id`".to_string(),
+        ));
+    }
+
+    if let Some(job_id) = params.job_id {
+        ActionJobBMC::get_job(mm, ctx, cluster_id.to_owned(), job_id).await?;
+    }
+
+    match &params.microdevice_id {
+        Some(ids) => {
+            for id in ids {
+                MicrodeviceBMC::find_in_cluster(mm, ctx, cluster_id, *id).await?;
+            }
+        }
+        None => ClusterBMC::exists(mm, ctx, cluster_id.to_owned()).await?,
+    }
+
+    let mut subscriptions = session
+        .subscriptions
+        .lock()
+        .map_err(|_| Error::InvalidRequest("connection is closing".to_string()))?;
+
+    if subscriptions.active.len() >= CONFIG.rpc.max_subscriptions {
+        return Err(Error::InvalidParams(format!(
+            "a connection can hold at most {} subscriptions",
+            CONFIG.rpc.max_subscriptions
+        )));
+    }
+
+    subscriptions.next_id += 1;
+    let id = subscriptions.next_id;
+
+    subscriptions.active.insert(
+        id,
+        Subscription {
+            topic: params.topic,
+            microdevice_ids: params.microdevice_id.map(|ids| ids.into_iter().collect()),
+            job_id: params.job_id,
+        },
+    );
+
+    Ok(json!({ "subscription": id }))
+}
+
+fn unsubscribe(session: &Session, params: UnsubscribeParams) -> Result<Value> {
+    let mut subscriptions = session
+        .subscriptions
+        .lock()
+        .map_err(|_| Error::InvalidRequest("connection is closing".to_string()))?;
+
+    match subscriptions.active.remove(&params.subscription) {
+        Some(_) => Ok(json!({ "subscription": params.subscription })),
+        None => Err(Error::InvalidParams(format!(
+            "subscription `{}` not found",
+            params.subscription
+        ))),
+    }
+}
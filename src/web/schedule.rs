@@ -0,0 +1,194 @@
+use super::error::Result;
+use crate::context::Ctx;
+use crate::model::schedule::{
+    ScheduleBaseModelController as ScheduleBMC, ScheduleCreate, ScheduleRecord,
+};
+use crate::model::ModelManager;
+use axum::{
+    extract::{Extension, Json as ExtractJson, Path, State},
+    response::Json,
+};
+
+/// List the schedules of a cluster
+#[utoipa::path(
+    get,
+    path = "/cluster/{clusterId}/schedules",
+    tag = "Schedules",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+    ),
+    responses(
+        (status = 200, body = [ScheduleRecord]),
+        (status = 401),
+        (status = 404),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn list_schedules(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path(cluster_id): Path<String>,
+) -> Result<Json<Vec<ScheduleRecord>>> {
+    Ok(Json(
+        ScheduleBMC::list_schedules(&mm, &ctx, cluster_id).await?,
+    ))
+}
+
+/// Create a schedule
+///
+/// A schedule sends an action to a list of microdevices or to the members of
+/// a device group, either repeatedly following a `cron` expression or once at
+/// `run_at`. Both are evaluated in `timezone`.
+///
+/// The action is sent on behalf of the user creating the schedule.
+#[utoipa::path(
+    post,
+    path = "/cluster/{clusterId}/schedules",
+    tag = "Schedules",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+    ),
+    request_body = ScheduleCreate,
+    responses(
+        (status = 200, body = ScheduleRecord),
+        (status = 400),
+        (status = 401),
+        (status = 403),
+        (status = 404),
+        (status = 409, description = "A schedule with the same name already exists"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn create_schedule(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path(cluster_id): Path<String>,
+    ExtractJson(data): Json<ScheduleCreate>,
+) -> Result<Json<ScheduleRecord>> {
+    Ok(Json(
+        ScheduleBMC::create_schedule(&mm, &ctx, cluster_id, data).await?,
+    ))
+}
+
+/// Get a schedule
+///
+/// Includes the next run and the time and outcome of the last run.
+#[utoipa::path(
+    get,
+    path = "/cluster/{clusterId}/schedules/{scheduleId}",
+    tag = "Schedules",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+        ("scheduleId" = i32, Path, description="Schedule ID"),
+    ),
+    responses(
+        (status = 200, body = ScheduleRecord),
+        (status = 401),
+        (status = 404),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn get_schedule(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path((cluster_id, schedule_id)): Path<(String, i32)>,
+) -> Result<Json<ScheduleRecord>> {
+    Ok(Json(
+        ScheduleBMC::get_schedule(&mm, &ctx, cluster_id, schedule_id).await?,
+    ))
+}
+
+/// Delete a schedule
+#[utoipa::path(
+    delete,
+    path = "/cluster/{clusterId}/schedules/{scheduleId}",
+    tag = "Schedules",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+        ("scheduleId" = i32, Path, description="Schedule ID"),
+    ),
+    responses(
+        (status = 200),
+        (status = 401),
+        (status = 403),
+        (status = 404),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn delete_schedule(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path((cluster_id, schedule_id)): Path<(String, i32)>,
+) -> Result<()> {
+    Ok(ScheduleBMC::delete_schedule(&mm, &ctx, cluster_id, schedule_id).await?)
+}
+
+/// Pause a schedule
+#[utoipa::path(
+    post,
+    path = "/cluster/{clusterId}/schedules/{scheduleId}/pause",
+    tag = "Schedules",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+        ("scheduleId" = i32, Path, description="Schedule ID"),
+    ),
+    responses(
+        (status = 200, body = ScheduleRecord),
+        (status = 401),
+        (status = 403),
+        (status = 404),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn pause_schedule(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path((cluster_id, schedule_id)): Path<(String, i32)>,
+) -> Result<Json<ScheduleRecord>> {
+    Ok(Json(
+        ScheduleBMC::pause_schedule(&mm, &ctx, cluster_id, schedule_id).await?,
+    ))
+}
+
+/// Resume a paused schedule
+///
+/// Recurring runs missed while the schedule was paused are skipped. A single
+/// run whose time has passed cannot be resumed.
+#[utoipa::path(
+    post,
+    path = "/cluster/{clusterId}/schedules/{scheduleId}/resume",
+    tag = "Schedules",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+        ("scheduleId" = i32, Path, description="Schedule ID"),
+    ),
+    responses(
+        (status = 200, body = ScheduleRecord),
+        (status = 400, description = "The single run has already passed"),
+        (status = 401),
+        (status = 403),
+        (status = 404),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn resume_schedule(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path((cluster_id, schedule_id)): Path<(String, i32)>,
+) -> Result<Json<ScheduleRecord>> {
+    Ok(Json(
+        ScheduleBMC::resume_schedule(&mm, &ctx, cluster_id, schedule_id).await?,
+    ))
+}
@@ -0,0 +1,80 @@
+use super::error::Result;
+use crate::context::Ctx;
+use crate::model::shadow::{
+    ShadowBaseModelController as ShadowBMC, ShadowDesiredUpdate, ShadowRecord,
+};
+use crate::model::ModelManager;
+use axum::{
+    extract::{Extension, Json as ExtractJson, Path, State},
+    response::Json,
+};
+
+/// Get the shadow of a microdevice
+///
+/// Returns the desired and reported state of the microdevice together with the
+/// delta that still has to be applied by the device.
+#[utoipa::path(
+    get,
+    path = "/cluster/{clusterId}/device/{microdeviceId}/shadow",
+    tag = "Microdevices",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+        ("microdeviceId" = i32, Path, description="Microdevice ID"),
+    ),
+    responses(
+        (status = 200, body = ShadowRecord),
+        (status = 401),
+        (status = 404),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn get_shadow(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path((cluster_id, microdevice_id)): Path<(String, i32)>,
+) -> Result<Json<ShadowRecord>> {
+    Ok(Json(
+        ShadowBMC::get_shadow(&mm, &ctx, cluster_id, microdevice_id).await?,
+    ))
+}
+
+/// Update the desired state of a microdevice
+///
+/// The `desired` document is merged into the current desired state, keys set to
+/// `null` are removed. If `version` is provided and does not match the current
+/// desired version the update is rejected with `409 Conflict`.
+///
+/// The resulting delta is published to the gateway so the device can converge,
+/// devices that are offline receive it again when they report their state.
+#[utoipa::path(
+    patch,
+    path = "/cluster/{clusterId}/device/{microdeviceId}/shadow",
+    tag = "Microdevices",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+        ("microdeviceId" = i32, Path, description="Microdevice ID"),
+    ),
+    request_body = ShadowDesiredUpdate,
+    responses(
+        (status = 200, body = ShadowRecord),
+        (status = 400),
+        (status = 401),
+        (status = 404),
+        (status = 409, description = "Desired state version is stale"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn update_desired(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path((cluster_id, microdevice_id)): Path<(String, i32)>,
+    ExtractJson(data): Json<ShadowDesiredUpdate>,
+) -> Result<Json<ShadowRecord>> {
+    Ok(Json(
+        ShadowBMC::update_desired(&mm, &ctx, cluster_id, microdevice_id, data).await?,
+    ))
+}
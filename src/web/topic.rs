@@ -0,0 +1,106 @@
+use super::error::Result;
+use crate::context::Ctx;
+use crate::model::microdevice::MicrodeviceTopic;
+use crate::model::topic::{TopicBaseModelController as TopicBMC, TopicDeleteParams};
+use crate::model::ModelManager;
+use axum::{
+    extract::{Extension, Json as ExtractJson, Path, Query, State},
+    response::Json,
+};
+
+/// List the topics of a microdevice
+#[utoipa::path(
+    get,
+    path = "/cluster/{clusterId}/device/{microdeviceId}/topics",
+    tag = "Microdevices",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+        ("microdeviceId" = i32, Path, description="Microdevice ID"),
+    ),
+    responses(
+        (status = 200, body = [MicrodeviceTopic]),
+        (status = 401),
+        (status = 404),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn list_topics(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path((cluster_id, microdevice_id)): Path<(String, i32)>,
+) -> Result<Json<Vec<MicrodeviceTopic>>> {
+    Ok(Json(
+        TopicBMC::list_topics(&mm, &ctx, cluster_id, microdevice_id).await?,
+    ))
+}
+
+/// Add a topic to a microdevice
+///
+/// The topic has to be a valid MQTT topic filter, wildcards must occupy a whole
+/// level and `#` is only allowed as the last level. `qos` must be 0, 1 or 2.
+///
+/// Returns the topics of the microdevice after the change.
+#[utoipa::path(
+    post,
+    path = "/cluster/{clusterId}/device/{microdeviceId}/topics",
+    tag = "Microdevices",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+        ("microdeviceId" = i32, Path, description="Microdevice ID"),
+    ),
+    request_body = MicrodeviceTopic,
+    responses(
+        (status = 200, body = [MicrodeviceTopic]),
+        (status = 400, body = String, description = "Invalid topic"),
+        (status = 401),
+        (status = 404),
+        (status = 409, description = "Topic is already registered"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn add_topic(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path((cluster_id, microdevice_id)): Path<(String, i32)>,
+    ExtractJson(data): Json<MicrodeviceTopic>,
+) -> Result<Json<Vec<MicrodeviceTopic>>> {
+    Ok(Json(
+        TopicBMC::add_topic(&mm, &ctx, cluster_id, microdevice_id, data).await?,
+    ))
+}
+
+/// Remove a topic from a microdevice
+///
+/// Returns the topics of the microdevice after the change.
+#[utoipa::path(
+    delete,
+    path = "/cluster/{clusterId}/device/{microdeviceId}/topics",
+    tag = "Microdevices",
+    params(
+        ("clusterId" = String, Path, description="Cluster ID a existing cluster"),
+        ("microdeviceId" = i32, Path, description="Microdevice ID"),
+        ("topic" = String, Query, description="Topic to remove", example="/temperature"),
+    ),
+    responses(
+        (status = 200, body = [MicrodeviceTopic]),
+        (status = 401),
+        (status = 404),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+)]
+pub async fn remove_topic(
+    State(mm): State<ModelManager>,
+    Extension(ctx): Extension<Ctx>,
+    Path((cluster_id, microdevice_id)): Path<(String, i32)>,
+    Query(params): Query<TopicDeleteParams>,
+) -> Result<Json<Vec<MicrodeviceTopic>>> {
+    Ok(Json(
+        TopicBMC::remove_topic(&mm, &ctx, cluster_id, microdevice_id, params).await?,
+    ))
+}